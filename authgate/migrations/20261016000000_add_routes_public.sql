@@ -0,0 +1,2 @@
+-- Routes that are served without authentication
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS public BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Per-route rate limit, as JSON; NULL when the route has none
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS rate_limit JSONB;
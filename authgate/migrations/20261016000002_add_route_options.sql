@@ -0,0 +1,7 @@
+-- Route options outside the require block; NULL when unset
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS session_url TEXT;
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS port INTEGER;
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS forbidden_redirect TEXT;
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS methods JSONB;
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS session_cache_ttl BIGINT;
+ALTER TABLE routes ADD COLUMN IF NOT EXISTS audience TEXT;
@@ -0,0 +1,18 @@
+-- Create auth_config table
+CREATE TABLE IF NOT EXISTS auth_config (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_url TEXT NOT NULL,
+    login_redirect TEXT NOT NULL,
+    cookie_name TEXT
+);
+
+-- Create routes table; require holds JSON text and timestamps have millisecond
+-- precision so consecutive updates change the routes version
+CREATE TABLE IF NOT EXISTS routes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    host TEXT NOT NULL,
+    path TEXT NOT NULL,
+    require TEXT NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+);
@@ -0,0 +1,2 @@
+-- Routes that are served without authentication
+ALTER TABLE routes ADD COLUMN public BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Per-route rate limit, as JSON text; NULL when the route has none
+ALTER TABLE routes ADD COLUMN rate_limit TEXT;
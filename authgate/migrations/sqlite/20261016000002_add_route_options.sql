@@ -0,0 +1,7 @@
+-- Route options outside the require block; NULL when unset, methods as JSON text
+ALTER TABLE routes ADD COLUMN session_url TEXT;
+ALTER TABLE routes ADD COLUMN port INTEGER;
+ALTER TABLE routes ADD COLUMN forbidden_redirect TEXT;
+ALTER TABLE routes ADD COLUMN methods TEXT;
+ALTER TABLE routes ADD COLUMN session_cache_ttl INTEGER;
+ALTER TABLE routes ADD COLUMN audience TEXT;
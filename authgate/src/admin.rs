@@ -1,18 +1,129 @@
-use crate::auth::AuthService;
-use crate::config::{ConfigManager, DEFAULT_COOKIE_NAME};
-use crate::types::{AuthGateError, RequireConfig, Route, SessionResponse};
+use crate::auth::{bearer_token, AuthService};
+use crate::cache::{CacheBackend, CacheFactory, CachePurpose, SessionCache};
+use crate::config::{config_schema, ConfigManager, RouteChange};
+use crate::config_provider::{
+    ambiguous_scope, conflicting_role, has_requirements, max_routes, strict_route_overlaps,
+    tenant_problem, validate_route_config, RouteStore,
+};
+use crate::expr::RequireExpr;
+use crate::matcher::find_route_overlaps;
+use crate::types::{AuthGateError, RateLimit, RequireConfig, Route, SessionResponse};
 use axum::{
-    extract::{Path, Request, State},
+    extract::{FromRef, Path, Query, Request, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::env;
+use std::future::Future;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Log target for security audit events
+const AUDIT_TARGET: &str = "authgate::audit";
+
+/// How long a used break-glass token is remembered, so it can't be replayed
+const BREAK_GLASS_USED_TTL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Records break-glass token use, shared between replicas and restarts
+///
+/// `None` unless the admin cache backend is Redis: an in-memory record is lost
+/// on restart and isn't seen by other replicas, either of which would let the
+/// token be replayed.
+static BREAK_GLASS_USES: Lazy<Option<Arc<dyn SessionCache>>> =
+    Lazy::new(|| match CacheFactory::backend_for(CachePurpose::Admin) {
+        CacheBackend::Redis { .. } => Some(CacheFactory::create_for(CachePurpose::Admin)),
+        CacheBackend::Memory => None,
+    });
+
+/// State of Admin API endpoints
+///
+/// Admin sessions are validated by the app's auth service, so they reuse its
+/// HTTP connections and session cache instead of opening their own.
+#[derive(Clone)]
+pub struct AdminState {
+    pub config_manager: Arc<ConfigManager>,
+    pub auth_service: Arc<AuthService>,
+}
+
+impl FromRef<AdminState> for Arc<ConfigManager> {
+    fn from_ref(state: &AdminState) -> Self {
+        state.config_manager.clone()
+    }
+}
+
+impl FromRef<AdminState> for Arc<AuthService> {
+    fn from_ref(state: &AdminState) -> Self {
+        state.auth_service.clone()
+    }
+}
+
+/// Default number of times a transient database failure is retried
+const DEFAULT_DB_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each further one
+const DB_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Retries for Admin API database operations
+///
+/// Only transient failures ([`AuthGateError::DatabaseUnavailable`]), such as a
+/// dropped connection or a timeout, are retried. Authoritative errors like a
+/// missing route or a constraint violation are returned straight away.
+#[derive(Debug, Clone, Copy)]
+pub struct DbRetryPolicy {
+    retries: u32,
+    backoff: Duration,
+}
+
+impl DbRetryPolicy {
+    /// Retry up to `retries` times, waiting `backoff` before the first retry
+    pub fn new(retries: u32, backoff: Duration) -> Self {
+        Self { retries, backoff }
+    }
+
+    /// Create a policy retrying `AUTHGATE_ADMIN_DB_RETRIES` times
+    pub fn from_env() -> Self {
+        let retries = env::var("AUTHGATE_ADMIN_DB_RETRIES")
+            .ok()
+            .and_then(|retries| retries.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_DB_RETRIES);
+        Self::new(retries, DB_RETRY_BACKOFF)
+    }
+
+    /// Run a database operation, retrying transient failures with exponential backoff
+    pub async fn run<T, F, Fut>(&self, operation: &str, mut attempt: F) -> Result<T, AuthGateError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, AuthGateError>>,
+    {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            match attempt().await {
+                Err(AuthGateError::DatabaseUnavailable(e)) if retries < self.retries => {
+                    retries += 1;
+                    warn!(
+                        "Failed to {}, retrying in {}ms ({}/{}): {}",
+                        operation,
+                        backoff.as_millis(),
+                        retries,
+                        self.retries,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
 
 /// Check if the Admin API is enabled
 pub fn is_admin_api_enabled() -> bool {
@@ -22,18 +133,19 @@ pub fn is_admin_api_enabled() -> bool {
         .to_lowercase()
         == "true";
 
-    // Check if the config backend is set to postgres (Admin API is not available with json backend)
+    // Check if the config backend includes a database (Admin API is not available with json backend)
     let config_backend = env::var("AUTHGATE_CONFIG_BACKEND")
         .unwrap_or_else(|_| "json".to_string())
         .to_lowercase();
 
-    let is_postgres_backend = config_backend == "postgres";
+    let is_database_backend =
+        matches!(config_backend.as_str(), "postgres" | "composite" | "sqlite");
 
     // Admin API is enabled only if both conditions are met
-    let is_enabled = admin_api_enabled && is_postgres_backend;
+    let is_enabled = admin_api_enabled && is_database_backend;
 
-    if admin_api_enabled && !is_postgres_backend {
-        info!("Admin API is enabled in environment but disabled because config backend is not postgres");
+    if admin_api_enabled && !is_database_backend {
+        info!("Admin API is enabled in environment but disabled because config backend is not a database");
     } else if is_enabled {
         info!("Admin API is enabled");
     } else {
@@ -43,22 +155,27 @@ pub fn is_admin_api_enabled() -> bool {
     is_enabled
 }
 
-/// Create the Admin API router
-pub fn create_admin_router<S>() -> Router<S>
+/// Create the Admin API router, validating admin sessions with the app's auth
+/// service and session cookie
+pub fn create_admin_router<S>(state: AdminState) -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
 {
-    create_admin_router_with_enabled(is_admin_api_enabled())
+    create_admin_router_with_enabled(is_admin_api_enabled(), state)
 }
 
 /// Create the Admin API router with explicit enabled flag (for testing)
-pub fn create_admin_router_with_enabled<S>(enabled: bool) -> Router<S>
+pub fn create_admin_router_with_enabled<S>(enabled: bool, state: AdminState) -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
 {
     if enabled {
         // Create a router with actual admin endpoints
-        Router::new().route("/health", get(health_handler))
+        Router::new()
+            .route("/health", get(health_handler))
+            .route("/validate-require", post(validate_require))
+            .route("/config/schema", get(get_config_schema))
+            .with_state(state)
         // We can't add the routes API endpoints here because they require a different state type
         // Instead, we'll add them in the main.rs file
     } else {
@@ -75,7 +192,25 @@ pub struct RouteDto {
     pub id: i32,
     pub host: String,
     pub path: String,
+    /// May be omitted for public routes
+    #[serde(default)]
     pub require: RequireConfig,
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forbidden_redirect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_cache_ttl: Option<u64>,
+    #[serde(default, alias = "app", skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
 }
 
 impl From<Route> for RouteDto {
@@ -84,52 +219,152 @@ impl From<Route> for RouteDto {
             id: route.id.unwrap_or_default(),
             host: route.host,
             path: route.path,
+            public: route.public,
+            rate_limit: route.rate_limit,
+            session_url: route.session_url,
+            port: route.port,
+            forbidden_redirect: route.forbidden_redirect,
+            methods: route.methods,
+            session_cache_ttl: route.session_cache_ttl,
+            audience: route.audience,
             require: serde_json::from_value(route.require).unwrap_or_else(|_| RequireConfig {
                 roles: None,
                 permissions: None,
                 scopes: None,
                 teams: None,
+                ..Default::default()
             }),
         }
     }
 }
 
+/// Query parameters accepted by Admin API read endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    /// Pretty-print the JSON response for humans
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+/// Filter selecting routes for the list and bulk delete endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct RouteFilter {
+    /// Only routes for this host, compared case-insensitively
+    pub host: Option<String>,
+}
+
+impl RouteFilter {
+    /// Whether the filter would select every route
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none()
+    }
+
+    /// Whether a route is selected by the filter
+    pub fn matches(&self, route: &Route) -> bool {
+        self.host
+            .as_ref()
+            .is_none_or(|host| route.host.eq_ignore_ascii_case(host))
+    }
+
+    /// Keep only the routes selected by the filter
+    pub fn select(&self, routes: Vec<Route>) -> Vec<Route> {
+        routes
+            .into_iter()
+            .filter(|route| self.matches(route))
+            .collect()
+    }
+}
+
+/// Confirmation required by destructive bulk endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfirmQuery {
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// JSON response that is compact by default and indented on request
+#[derive(Debug)]
+pub struct AdminJson<T> {
+    pub value: T,
+    pub pretty: bool,
+}
+
+impl<T> AdminJson<T> {
+    fn new(value: T, format: &FormatQuery) -> Self {
+        Self {
+            value,
+            pretty: format.pretty,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for AdminJson<T> {
+    fn into_response(self) -> Response {
+        let body = if self.pretty {
+            serde_json::to_string_pretty(&self.value)
+        } else {
+            serde_json::to_string(&self.value)
+        };
+
+        match body {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.into())
+                .unwrap(),
+            Err(e) => ApiError::InternalError(format!("Failed to serialize response: {}", e))
+                .into_response(),
+        }
+    }
+}
+
 /// List all routes
 pub async fn list_routes(
     State(config_manager): State<Arc<ConfigManager>>,
-) -> Result<Json<Vec<RouteDto>>, ApiError> {
-    // Get the postgres provider
-    let provider = get_postgres_provider(&config_manager)?;
+    Query(format): Query<FormatQuery>,
+    Query(filter): Query<RouteFilter>,
+) -> Result<AdminJson<Vec<RouteDto>>, ApiError> {
+    // Get the route store
+    let provider = get_route_store(&config_manager)?;
 
     // Get all routes from the database
-    let routes = provider.get_all_routes().await?;
-
-    // Convert to DTOs
-    let route_dtos = routes.into_iter().map(RouteDto::from).collect();
+    let routes = DbRetryPolicy::from_env()
+        .run("list routes", || provider.get_all_routes())
+        .await?;
+
+    // Convert the selected routes to DTOs
+    let route_dtos = filter
+        .select(routes)
+        .into_iter()
+        .map(RouteDto::from)
+        .collect();
 
-    Ok(Json(route_dtos))
+    Ok(AdminJson::new(route_dtos, &format))
 }
 
 /// Get a specific route by ID
 pub async fn get_route(
     State(config_manager): State<Arc<ConfigManager>>,
     Path(id): Path<String>,
-) -> Result<Json<RouteDto>, ApiError> {
+    Query(format): Query<FormatQuery>,
+) -> Result<AdminJson<RouteDto>, ApiError> {
     // Parse the ID as integer
     let id: i32 = id
         .parse()
         .map_err(|_| ApiError::ValidationError(format!("Invalid ID: {}", id)))?;
 
-    // Get the postgres provider
-    let provider = get_postgres_provider(&config_manager)?;
+    // Get the route store
+    let provider = get_route_store(&config_manager)?;
 
     // Get the route from the database
-    let route = provider.get_route_by_id(&id).await?;
+    let route = DbRetryPolicy::from_env()
+        .run("get route", || provider.get_route_by_id(&id))
+        .await?;
 
     // Convert to DTO
     let route_dto = RouteDto::from(route);
 
-    Ok(Json(route_dto))
+    Ok(AdminJson::new(route_dto, &format))
 }
 
 /// Create a new route
@@ -140,26 +375,49 @@ pub async fn create_route(
     // Validate the route
     validate_route(&route_dto)?;
 
-    // Get the postgres provider
-    let provider = get_postgres_provider(&config_manager)?;
+    // Get the route store
+    let provider = get_route_store(&config_manager)?;
+
+    // Refuse to grow the route table past the configured maximum
+    let max_routes = max_routes();
+    if config_manager.get_config_ref().read().await.routes.len() >= max_routes {
+        return Err(ApiError::ValidationError(format!(
+            "Route limit of {} reached; raise AUTHGATE_MAX_ROUTES to add more",
+            max_routes
+        )));
+    }
 
     // Create a new route; let the database assign the ID
     let route = Route {
         id: None,
+        require: route_require(&route_dto)?,
         host: route_dto.host,
         path: route_dto.path,
-        require: serde_json::to_value(route_dto.require)
-            .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?,
+        public: route_dto.public,
+        rate_limit: route_dto.rate_limit,
+        session_url: route_dto.session_url,
+        port: route_dto.port,
+        forbidden_redirect: route_dto.forbidden_redirect,
+        methods: route_dto.methods,
+        session_cache_ttl: route_dto.session_cache_ttl,
+        audience: route_dto.audience,
+        require_expr: None,
     };
+    check_route(&config_manager, &route).await?;
 
     // Save the route to the database
-    let created_route = provider.create_route(route).await?;
-
-    // Reload the configuration
-    config_manager.load_config().await.map_err(|e| {
-        error!("Failed to reload configuration after creating route: {}", e);
-        ApiError::InternalError(format!("Failed to reload configuration: {}", e))
-    })?;
+    let created_route = DbRetryPolicy::from_env()
+        .run("create route", || provider.create_route(route.clone()))
+        .await?;
+
+    // Apply the change to the loaded configuration
+    config_manager
+        .apply_route_change(RouteChange::Upsert(Box::new(created_route.clone())))
+        .await
+        .map_err(|e| {
+            error!("Failed to reload configuration after creating route: {}", e);
+            ApiError::InternalError(format!("Failed to reload configuration: {}", e))
+        })?;
 
     info!("Created new route: {:?}", created_route.id);
 
@@ -183,29 +441,46 @@ pub async fn update_route(
     // Validate the route
     validate_route(&route_dto)?;
 
-    // Get the postgres provider
-    let provider = get_postgres_provider(&config_manager)?;
+    // Get the route store
+    let provider = get_route_store(&config_manager)?;
 
     // Check if the route exists
-    let _ = provider.get_route_by_id(&id).await?;
+    let retry = DbRetryPolicy::from_env();
+    let _ = retry
+        .run("get route", || provider.get_route_by_id(&id))
+        .await?;
 
     // Update the route
     let route = Route {
         id: Some(id),
+        require: route_require(&route_dto)?,
         host: route_dto.host,
         path: route_dto.path,
-        require: serde_json::to_value(route_dto.require)
-            .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?,
+        public: route_dto.public,
+        rate_limit: route_dto.rate_limit,
+        session_url: route_dto.session_url,
+        port: route_dto.port,
+        forbidden_redirect: route_dto.forbidden_redirect,
+        methods: route_dto.methods,
+        session_cache_ttl: route_dto.session_cache_ttl,
+        audience: route_dto.audience,
+        require_expr: None,
     };
+    check_route(&config_manager, &route).await?;
 
     // Save the route to the database
-    let updated_route = provider.update_route(route).await?;
-
-    // Reload the configuration
-    config_manager.load_config().await.map_err(|e| {
-        error!("Failed to reload configuration after updating route: {}", e);
-        ApiError::InternalError(format!("Failed to reload configuration: {}", e))
-    })?;
+    let updated_route = retry
+        .run("update route", || provider.update_route(route.clone()))
+        .await?;
+
+    // Apply the change to the loaded configuration
+    config_manager
+        .apply_route_change(RouteChange::Upsert(Box::new(updated_route.clone())))
+        .await
+        .map_err(|e| {
+            error!("Failed to reload configuration after updating route: {}", e);
+            ApiError::InternalError(format!("Failed to reload configuration: {}", e))
+        })?;
 
     info!("Updated route: {}", updated_route.id.as_ref().unwrap());
 
@@ -225,20 +500,28 @@ pub async fn delete_route(
         .parse()
         .map_err(|_| ApiError::ValidationError(format!("Invalid ID: {}", id)))?;
 
-    // Get the postgres provider
-    let provider = get_postgres_provider(&config_manager)?;
+    // Get the route store
+    let provider = get_route_store(&config_manager)?;
 
     // Check if the route exists
-    let _ = provider.get_route_by_id(&id).await?;
+    let retry = DbRetryPolicy::from_env();
+    let _ = retry
+        .run("get route", || provider.get_route_by_id(&id))
+        .await?;
 
     // Delete the route
-    provider.delete_route(&id).await?;
-
-    // Reload the configuration
-    config_manager.load_config().await.map_err(|e| {
-        error!("Failed to reload configuration after deleting route: {}", e);
-        ApiError::InternalError(format!("Failed to reload configuration: {}", e))
-    })?;
+    retry
+        .run("delete route", || provider.delete_route(&id))
+        .await?;
+
+    // Apply the change to the loaded configuration
+    config_manager
+        .apply_route_change(RouteChange::Delete(id))
+        .await
+        .map_err(|e| {
+            error!("Failed to reload configuration after deleting route: {}", e);
+            ApiError::InternalError(format!("Failed to reload configuration: {}", e))
+        })?;
 
     info!("Deleted route: {}", id);
 
@@ -248,17 +531,124 @@ pub async fn delete_route(
     ))
 }
 
-/// Get the postgres provider from the config manager
-fn get_postgres_provider(
-    config_manager: &Arc<ConfigManager>,
-) -> Result<crate::config_provider::PostgresProvider, ApiError> {
-    // Get the provider from the config manager
-    let provider = config_manager.get_postgres_provider().ok_or_else(|| {
-        error!("Postgres provider not available");
-        ApiError::ConfigError("Postgres provider not available".to_string())
+/// Delete every route matching a filter in one transaction
+///
+/// The routes are selected the same way `GET /admin/routes` lists them, so a
+/// listing with the same filter previews the deletion. A filter and
+/// `confirm=true` are both required so a bare request can't delete every route.
+pub async fn delete_routes(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+    Query(filter): Query<RouteFilter>,
+    Query(confirm): Query<ConfirmQuery>,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    if filter.is_empty() {
+        return ApiError::ValidationError("A route filter is required".to_string()).into_response();
+    }
+
+    if !confirm.confirm {
+        return ApiError::ValidationError(
+            "Bulk deletion must be confirmed with confirm=true".to_string(),
+        )
+        .into_response();
+    }
+
+    let provider = match get_route_store(&config_manager) {
+        Ok(provider) => provider,
+        Err(e) => return e.into_response(),
+    };
+
+    let retry = DbRetryPolicy::from_env();
+    let routes = match retry.run("list routes", || provider.get_all_routes()).await {
+        Ok(routes) => routes,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    let ids: Vec<i32> = filter
+        .select(routes)
+        .into_iter()
+        .filter_map(|route| route.id)
+        .collect();
+
+    let deleted = match retry
+        .run("delete routes", || provider.delete_routes(&ids))
+        .await
+    {
+        Ok(deleted) => deleted,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    // Several routes changed at once, so reload rather than apply each change
+    if let Err(e) = config_manager.load_config().await {
+        error!(
+            "Failed to reload configuration after deleting routes: {}",
+            e
+        );
+        return ApiError::InternalError(format!("Failed to reload configuration: {}", e))
+            .into_response();
+    }
+
+    info!("Deleted {} routes matching {:?}", deleted, filter);
+
+    Json(json!({
+        "status": "success",
+        "deleted": deleted,
+        "ids": ids
+    }))
+    .into_response()
+}
+
+/// Export the active configuration in the format read by the JSON file provider
+pub async fn export_config(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    let mut config = config_manager.get_config().await;
+
+    // Database IDs have no meaning in a JSON config file
+    for route in &mut config.routes {
+        route.id = None;
+    }
+
+    info!("Exported configuration with {} routes", config.routes.len());
+
+    AdminJson::new(config, &format).into_response()
+}
+
+/// Serve the JSON Schema of the configuration file
+pub async fn get_config_schema(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    AdminJson::new(config_schema(), &format).into_response()
+}
+
+/// Get the database holding the routes from the config manager
+fn get_route_store(config_manager: &Arc<ConfigManager>) -> Result<Arc<dyn RouteStore>, ApiError> {
+    // Get the route store from the config manager
+    let route_store = config_manager.get_route_store().ok_or_else(|| {
+        error!("Route store not available");
+        ApiError::ConfigError("Route store not available".to_string())
     })?;
 
-    Ok(provider)
+    Ok(route_store)
 }
 
 /// Validate a route
@@ -284,18 +674,164 @@ fn validate_route(route: &RouteDto) -> Result<(), ApiError> {
         ));
     }
 
-    // Validate require
-    if route.require.roles.is_none()
-        && route.require.permissions.is_none()
-        && route.require.scopes.is_none()
-        && route.require.teams.is_none()
+    validate_role_lists(&route.require)?;
+    validate_tenants(&route.require)
+}
+
+/// Check a route as it would be checked on the next configuration load
+///
+/// A route that fails these checks would be stored, then fail every reload and
+/// startup after it. With AUTHGATE_STRICT_ROUTE_OVERLAPS enabled, a route
+/// overlapping one already loaded is rejected too.
+async fn check_route(config_manager: &ConfigManager, route: &Route) -> Result<(), ApiError> {
+    validate_route_config("Route", route).map_err(|e| match e {
+        AuthGateError::ConfigError(message) => ApiError::ValidationError(message),
+        other => ApiError::ValidationError(other.to_string()),
+    })?;
+
+    if !strict_route_overlaps() {
+        return Ok(());
+    }
+
+    let mut routes: Vec<Route> = config_manager
+        .get_config_ref()
+        .read()
+        .await
+        .routes
+        .iter()
+        .filter(|existing| route.id.is_none() || existing.id != route.id)
+        .cloned()
+        .collect();
+    routes.push(route.clone());
+    let candidate = routes.len() - 1;
+
+    match find_route_overlaps(&routes)
+        .into_iter()
+        .find(|overlap| overlap.winner == candidate || overlap.shadowed == candidate)
     {
+        Some(overlap) => {
+            let other = &routes[overlap.winner.min(overlap.shadowed)];
+            Err(ApiError::ValidationError(format!(
+                "Route overlaps route {}{} and AUTHGATE_STRICT_ROUTE_OVERLAPS is enabled",
+                other.host, other.path
+            )))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Reject a require block that both requires and denies the same role
+fn validate_role_lists(require: &RequireConfig) -> Result<(), ApiError> {
+    let value = serde_json::to_value(require)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?;
+    match conflicting_role(&value) {
+        Some(role) => Err(ApiError::ValidationError(format!(
+            "Role {} cannot be both required and denied",
+            role
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Reject a require block with an empty or ambiguous tenant restriction
+fn validate_tenants(require: &RequireConfig) -> Result<(), ApiError> {
+    let value = serde_json::to_value(require)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?;
+    match tenant_problem(&value) {
+        Some(problem) => Err(ApiError::ValidationError(format!("Route {}", problem))),
+        None => Ok(()),
+    }
+}
+
+/// Convert a require block to the canonical JSON stored for a route
+///
+/// Null fields are omitted at every level and keys are sorted, so a route written
+/// through the Admin API is stored exactly like its JSON file equivalent. The
+/// result is checked the same way `validate_config` checks loaded routes.
+pub fn normalize_require(require: &RequireConfig) -> Result<serde_json::Value, ApiError> {
+    let mut value = serde_json::to_value(require)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?;
+    strip_nulls(&mut value);
+
+    if !has_requirements(&value) {
         return Err(ApiError::ValidationError(
-            "At least one of roles, permissions, scopes, or teams must be specified".to_string(),
+            "At least one requirement must be specified".to_string(),
         ));
     }
 
-    Ok(())
+    if let Some(expr) = &require.expr {
+        RequireExpr::parse(expr).map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    }
+
+    if let Some(scope) = ambiguous_scope(&value) {
+        return Err(ApiError::ValidationError(format!(
+            "Scope {} cannot set both resource_id and resource_ids",
+            scope
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Normalize a route's require block, which a public route may leave empty
+fn route_require(route: &RouteDto) -> Result<serde_json::Value, ApiError> {
+    if route.public {
+        let mut value = serde_json::to_value(&route.require)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid require config: {}", e)))?;
+        strip_nulls(&mut value);
+        if !has_requirements(&value) {
+            return Ok(value);
+        }
+    }
+
+    normalize_require(&route.require)
+}
+
+/// Validate a require block without creating a route, returning its normalized form
+///
+/// Runs the same checks as route creation, so policies can be tried out while
+/// they are being written. Nothing is stored.
+pub async fn validate_require(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    let require: RequireConfig = match serde_json::from_value(body) {
+        Ok(require) => require,
+        Err(e) => {
+            return ApiError::ValidationError(format!("Invalid require config: {}", e))
+                .into_response()
+        }
+    };
+
+    match validate_role_lists(&require)
+        .and_then(|_| validate_tenants(&require))
+        .and_then(|_| normalize_require(&require))
+    {
+        Ok(normalized) => Json(json!({
+            "status": "ok",
+            "require": normalized
+        }))
+        .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Remove null object fields recursively
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
 }
 
 /// API Error types
@@ -305,6 +841,7 @@ pub enum ApiError {
     ValidationError(String),
     ConfigError(String),
     DatabaseError(String),
+    Unavailable(String),
     InternalError(String),
 }
 
@@ -315,6 +852,7 @@ impl IntoResponse for ApiError {
             ApiError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::ConfigError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             ApiError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            ApiError::Unavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             ApiError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
         };
 
@@ -333,29 +871,124 @@ impl From<AuthGateError> for ApiError {
             AuthGateError::NotFound(msg) => ApiError::NotFound(msg),
             AuthGateError::ConfigError(msg) => ApiError::ConfigError(msg),
             AuthGateError::DatabaseError(msg) => ApiError::DatabaseError(msg),
+            AuthGateError::DatabaseUnavailable(msg) => ApiError::Unavailable(msg),
             _ => ApiError::InternalError(format!("Unexpected error: {}", err)),
         }
     }
 }
 
 /// Health check handler for the Admin API
-async fn health_handler<B>(request: Request<B>) -> Response {
+async fn health_handler<B>(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    request: Request<B>,
+) -> Response {
+    match authenticate_admin(&config_manager, &auth_service, request.headers()).await {
+        Ok(()) => health_response(),
+        Err(response) => response,
+    }
+}
+
+/// Request body for the session test endpoint
+#[derive(Debug, Deserialize)]
+pub struct TestSessionRequest {
+    pub token: String,
+}
+
+/// Validate a session token against the configured session endpoint and return
+/// the resolved session, so operators can check a cookie without browser tooling
+pub async fn test_session(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+    Json(request): Json<TestSessionRequest>,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    let config = config_manager.get_config().await;
+
+    let session_url = auth_service.select_session_url(&config.auth);
+
+    // Never log the token itself
+    info!(
+        "Testing a session token ({} chars) against {}",
+        request.token.len(),
+        session_url
+    );
+
+    // Bypass the cache so the token is always checked against the real upstream
+    match auth_service
+        .validate_session_uncached(session_url, &request.token)
+        .await
+    {
+        Ok(session) => Json(session).into_response(),
+        Err(e) => {
+            debug!("Session test failed: {}", e);
+            ApiError::ValidationError(e.to_string()).into_response()
+        }
+    }
+}
+
+/// Reload the configuration from its provider, e.g. after routes were changed
+/// directly in the database
+///
+/// A configuration that fails to load is reported with a `500` and the
+/// previous one stays in effect.
+pub async fn reload_config(
+    State(config_manager): State<Arc<ConfigManager>>,
+    State(auth_service): State<Arc<AuthService>>,
+    headers: header::HeaderMap,
+) -> Response {
+    if let Err(response) = authenticate_admin(&config_manager, &auth_service, &headers).await {
+        return response;
+    }
+
+    if let Err(e) = config_manager.load_config().await {
+        error!("Failed to reload configuration: {}", e);
+        return ApiError::InternalError(format!("Failed to reload configuration: {}", e))
+            .into_response();
+    }
+
+    let routes = config_manager.get_config().await.routes.len();
+    info!("Reloaded configuration with {} routes", routes);
+
+    Json(json!({
+        "status": "ok",
+        "routes": routes
+    }))
+    .into_response()
+}
+
+/// Authenticate an Admin API request by bearer token or session cookie
+async fn authenticate_admin(
+    config_manager: &ConfigManager,
+    auth_service: &AuthService,
+    headers: &header::HeaderMap,
+) -> Result<(), Response> {
     // Try token authentication first
-    if let Some(token) = try_extract_token(request.headers()) {
+    if let Some(token) = try_extract_token(headers) {
         if is_valid_token(&token) {
             debug!("Admin token validated successfully");
-            return health_response();
+            return Ok(());
+        }
+    }
+
+    // Then the one-time break-glass token
+    if let Some(token) = try_extract_token(headers) {
+        if let Some(result) = use_break_glass_token(&token, headers).await {
+            return result;
         }
     }
 
     // If token auth failed, try session authentication
-    if let Some(session_token) = extract_session_token(request.headers()) {
+    // The same cookie forward auth reads
+    let cookie_name = config_manager.get_cookie_name().await;
+    if let Some(session_token) = extract_session_token(headers, &cookie_name) {
         // Get the session URL from environment
         if let Ok(session_url) = env::var("AUTHGATE_SESSION_URL") {
             if !session_url.is_empty() {
-                // Create an auth service
-                let auth_service = AuthService::new();
-
                 // Validate the session
                 match auth_service
                     .validate_session(&session_url, &session_token)
@@ -368,10 +1001,10 @@ async fn health_handler<B>(request: Request<B>) -> Response {
                                 "Session authentication successful for user: {}",
                                 session.user.email
                             );
-                            return health_response();
+                            return Ok(());
                         } else {
                             debug!("User does not have any of the allowed roles");
-                            return forbidden_response("Insufficient permissions");
+                            return Err(forbidden_response("Insufficient permissions"));
                         }
                     }
                     Err(e) => {
@@ -384,15 +1017,65 @@ async fn health_handler<B>(request: Request<B>) -> Response {
 
     // If we get here, both authentication methods failed
     debug!("Both token and session authentication failed");
-    unauthorized_response("Authentication required")
+    Err(unauthorized_response("Authentication required"))
 }
 
-/// Extract the session token from the cookie
-fn extract_session_token(headers: &header::HeaderMap) -> Option<String> {
-    // Get the session cookie name from environment or use default
-    let cookie_name =
-        env::var("AUTHGATE_SESSION_COOKIE").unwrap_or_else(|_| DEFAULT_COOKIE_NAME.to_string());
+/// Check a token against the break-glass token hash and claim its single use
+///
+/// Returns `None` when no break-glass token is configured or the token doesn't
+/// match it, so other authentication methods can be tried.
+async fn use_break_glass_token(
+    token: &str,
+    headers: &header::HeaderMap,
+) -> Option<Result<(), Response>> {
+    let expected_hash = env::var("AUTHGATE_BREAK_GLASS_TOKEN_SHA256").ok()?;
+    let expected_hash = expected_hash.trim().to_lowercase();
+    let token_hash = format!("{:x}", Sha256::digest(token.as_bytes()));
+    if expected_hash.is_empty() || token_hash != expected_hash {
+        return None;
+    }
+
+    let source = crate::proxy::client_ip(headers);
 
+    let Some(uses) = BREAK_GLASS_USES.as_ref() else {
+        error!(
+            target: AUDIT_TARGET,
+            "Refused the break-glass admin token from {}: its single use can't be recorded without the Redis cache backend",
+            source
+        );
+        return Some(Err(unauthorized_response("Authentication required")));
+    };
+
+    // The first increment claims the token; replicas share the counter through Redis
+    let key = format!("break-glass:{}", token_hash);
+    match uses.increment(&key, BREAK_GLASS_USED_TTL).await {
+        Ok(1) => {
+            error!(
+                target: AUDIT_TARGET,
+                "Break-glass admin token used from {}; it is now invalidated and must be rotated",
+                source
+            );
+            Some(Ok(()))
+        }
+        Ok(_) => {
+            error!(
+                target: AUDIT_TARGET,
+                "Rejected reuse of the break-glass admin token from {}", source
+            );
+            Some(Err(unauthorized_response(
+                "Break-glass token has already been used",
+            )))
+        }
+        Err(e) => {
+            // Without a record of its use, the token could be replayed
+            warn!("Failed to record break-glass token use: {}", e);
+            Some(Err(unauthorized_response("Authentication required")))
+        }
+    }
+}
+
+/// Extract the session token from the cookie
+fn extract_session_token(headers: &header::HeaderMap, cookie_name: &str) -> Option<String> {
     // Extract the cookie header
     let cookie_header = headers.get(header::COOKIE)?;
     let cookie_str = cookie_header.to_str().ok()?;
@@ -413,19 +1096,7 @@ fn extract_session_token(headers: &header::HeaderMap) -> Option<String> {
 
 /// Try to extract a Bearer token from the headers
 fn try_extract_token(headers: &header::HeaderMap) -> Option<String> {
-    // Extract the Authorization header
-    let auth_header = headers.get(header::AUTHORIZATION)?;
-
-    // Convert the header to a string
-    let auth_header_str = auth_header.to_str().ok()?;
-
-    // Check if it's a Bearer token
-    if !auth_header_str.starts_with("Bearer ") {
-        return None;
-    }
-
-    // Extract the token
-    Some(auth_header_str["Bearer ".len()..].to_string())
+    bearer_token(headers)
 }
 
 /// Check if the token is valid
@@ -534,7 +1205,7 @@ fn unauthorized_response(message: &str) -> Response {
 async fn disabled_handler() -> Response {
     let json_response = json!({
         "status": "error",
-        "message": "Admin API is not available. Set AUTHGATE_ENABLE_ADMIN_API=true and use postgres config backend to enable."
+        "message": "Admin API is not available. Set AUTHGATE_ENABLE_ADMIN_API=true and use the postgres, composite or sqlite config backend to enable."
     });
 
     (StatusCode::FORBIDDEN, Json(json_response)).into_response()
@@ -1,26 +1,463 @@
-use crate::cache::{extract_jwt_expiration, CacheFactory, SessionCache};
+use crate::authorizer::Authorizer;
+use crate::breaker::{BreakerState, CircuitBreaker};
+use crate::cache::{
+    extract_jwt_expiration, session_key, CacheFactory, CacheStats, CacheStatus, SessionCache,
+};
+use crate::jwt::JwtVerifier;
 use crate::types::{
-    AuthGateError, AuthResult, RequestContext, Scope, ScopeRequirement, SessionResponse,
-    TeamRequirement,
+    AuthConfig, AuthGateError, AuthResult, CombineMode, RateLimit, RequestContext, Route,
+    SessionResponse,
 };
+use crate::webhook::{DenialEvent, DenialKind, DenialWebhook};
 use anyhow::Result;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use http::HeaderMap;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use http::{HeaderMap, HeaderName};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::header::{HeaderValue, COOKIE};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, info, warn};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
+
+/// Maximum number of characters of a session payload written to the diagnostic log
+const MAX_LOGGED_PAYLOAD_CHARS: usize = 1024;
+
+/// TTL for cached sessions whose token carries no expiration
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Upper bound on the grace period for expired cached sessions
+const MAX_CACHE_GRACE: Duration = Duration::from_secs(300);
+
+/// Header read when `header` is listed as a session token source without a name
+const DEFAULT_TOKEN_HEADER: &str = "X-Session-Token";
+
+/// Query parameter read when `query` is listed as a session token source without a name
+const DEFAULT_TOKEN_QUERY_PARAM: &str = "session_token";
+
+/// Default maximum length of the original URL carried through a login redirect
+const DEFAULT_MAX_ORIGINAL_URL_LEN: usize = 2048;
+
+/// Default size limit of the `X-Auth-Scopes` header, well below common proxy limits
+const DEFAULT_SCOPES_HEADER_MAX_BYTES: usize = 4096;
+
+/// Maximum number of sessions whose hits are counted for per-token revalidation
+/// sampling; the counts are reset once exceeded
+const MAX_SAMPLED_SESSIONS: usize = 10_000;
+
+/// Maximum number of rejected sessions remembered by the negative cache; expired
+/// entries are dropped once exceeded, and all of them if that isn't enough
+const MAX_REJECTED_SESSIONS: usize = 10_000;
+
+/// Substrings of JSON keys whose values are never written to the logs
+const SENSITIVE_KEYS: &[&str] = &[
+    "token",
+    "cookie",
+    "session",
+    "secret",
+    "password",
+    "authorization",
+    "jwt",
+];
+
+/// Regex for scrubbing sensitive string values from payloads that aren't valid JSON
+static SENSITIVE_VALUE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)("[^"]*(?:token|cookie|session|secret|password|authorization|jwt)[^"]*"\s*:\s*)"[^"]*""#)
+        .expect("Failed to compile sensitive value regex")
+});
+
+/// How teams are listed in the `X-Auth-User-Teams` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeamsHeader {
+    /// Team IDs, e.g. `team-1,team-2`
+    Ids,
+    /// Team IDs with their names, e.g. `team-1:Engineering,team-2:Sales`
+    IdNames,
+    /// No teams header
+    Off,
+}
+
+/// Names of the user information headers sent on authorized requests, and
+/// which of the tenant headers are sent
+///
+/// Defaults to the `X-Auth-*` names. Each can be renamed with its
+/// `AUTHGATE_HEADER_*` variable, e.g. `AUTHGATE_HEADER_USER_ID=Remote-User`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthHeaderNames {
+    pub user_id: HeaderName,
+    pub email: HeaderName,
+    pub roles: HeaderName,
+    pub permissions: HeaderName,
+    pub tenant_id: HeaderName,
+    pub teams: HeaderName,
+    pub scopes: HeaderName,
+    pub audience: HeaderName,
+    /// Whether the tenant header is sent, from `AUTHGATE_TENANT_HEADER`
+    pub tenant_header: bool,
+    /// Teams header format from `AUTHGATE_TEAMS_HEADER`
+    pub teams_header: TeamsHeader,
+    /// Whether the scopes header is sent, from `AUTHGATE_SCOPES_HEADER`
+    pub scopes_header: bool,
+    /// Size limit of the scopes header, from `AUTHGATE_SCOPES_HEADER_MAX_BYTES`
+    pub scopes_header_max_bytes: usize,
+}
+
+impl Default for AuthHeaderNames {
+    fn default() -> Self {
+        Self {
+            user_id: HeaderName::from_static("x-auth-user-id"),
+            email: HeaderName::from_static("x-auth-user-email"),
+            roles: HeaderName::from_static("x-auth-user-roles"),
+            permissions: HeaderName::from_static("x-auth-user-permissions"),
+            tenant_id: HeaderName::from_static("x-auth-tenant-id"),
+            teams: HeaderName::from_static("x-auth-user-teams"),
+            scopes: HeaderName::from_static("x-auth-scopes"),
+            audience: HeaderName::from_static("x-auth-audience"),
+            tenant_header: true,
+            teams_header: TeamsHeader::Ids,
+            scopes_header: false,
+            scopes_header_max_bytes: DEFAULT_SCOPES_HEADER_MAX_BYTES,
+        }
+    }
+}
+
+impl AuthHeaderNames {
+    /// Read the header names from the `AUTHGATE_HEADER_*` variables, and the
+    /// tenant header settings from theirs
+    ///
+    /// Invalid names are logged and the default name is kept.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            user_id: header_name_from_env("AUTHGATE_HEADER_USER_ID", defaults.user_id),
+            email: header_name_from_env("AUTHGATE_HEADER_EMAIL", defaults.email),
+            roles: header_name_from_env("AUTHGATE_HEADER_ROLES", defaults.roles),
+            permissions: header_name_from_env("AUTHGATE_HEADER_PERMISSIONS", defaults.permissions),
+            tenant_id: header_name_from_env("AUTHGATE_HEADER_TENANT_ID", defaults.tenant_id),
+            teams: header_name_from_env("AUTHGATE_HEADER_TEAMS", defaults.teams),
+            scopes: header_name_from_env("AUTHGATE_HEADER_SCOPES", defaults.scopes),
+            audience: header_name_from_env("AUTHGATE_HEADER_AUDIENCE", defaults.audience),
+            tenant_header: header_enabled("AUTHGATE_TENANT_HEADER", defaults.tenant_header),
+            teams_header: teams_header_from_env(),
+            scopes_header: header_enabled("AUTHGATE_SCOPES_HEADER", defaults.scopes_header),
+            scopes_header_max_bytes: env::var("AUTHGATE_SCOPES_HEADER_MAX_BYTES")
+                .ok()
+                .and_then(|max| max.parse::<usize>().ok())
+                .unwrap_or(defaults.scopes_header_max_bytes),
+        }
+    }
+}
+
+/// Teams header format from `AUTHGATE_TEAMS_HEADER`: `ids` (the default), `id_name` or `off`
+fn teams_header_from_env() -> TeamsHeader {
+    match env::var("AUTHGATE_TEAMS_HEADER")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "id_name" => TeamsHeader::IdNames,
+        "off" | "false" => TeamsHeader::Off,
+        _ => TeamsHeader::Ids,
+    }
+}
+
+/// Whether a header is enabled by a boolean environment variable
+fn header_enabled(var: &str, default: bool) -> bool {
+    env::var(var)
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(default)
+}
+
+/// Read a header name from an environment variable, falling back to `default`
+fn header_name_from_env(var: &str, default: HeaderName) -> HeaderName {
+    match env::var(var) {
+        Ok(name) if !name.trim().is_empty() => HeaderName::from_bytes(name.trim().as_bytes())
+            .unwrap_or_else(|_| {
+                warn!("{} is not a valid header name, using {}", var, default);
+                default
+            }),
+        _ => default,
+    }
+}
+
+/// Where a session token is read from on a forwarded request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenSource {
+    /// The configured session cookie
+    Cookie,
+    /// The `Authorization` header, with the `Bearer` scheme
+    Bearer,
+    /// A request header
+    Header(String),
+    /// A query parameter of the forwarded URI
+    Query(String),
+}
+
+impl TokenSource {
+    /// Parse a source as written in `AUTHGATE_SESSION_TOKEN_SOURCES`
+    ///
+    /// Sources are `cookie`, `bearer`, `header` or `query`, the latter two with
+    /// an optional header or parameter name after a colon, e.g.
+    /// `header:X-Api-Session`.
+    pub fn parse(source: &str) -> Result<Self, AuthGateError> {
+        let (kind, name) = match source.split_once(':') {
+            Some((kind, name)) => (kind.trim(), Some(name.trim())),
+            None => (source.trim(), None),
+        };
+        if name == Some("") {
+            return Err(AuthGateError::ConfigError(format!(
+                "Session token source {} has an empty name",
+                source
+            )));
+        }
+
+        match (kind.to_lowercase().as_str(), name) {
+            ("cookie", None) => Ok(TokenSource::Cookie),
+            ("bearer", None) => Ok(TokenSource::Bearer),
+            ("header", name) => Ok(TokenSource::Header(
+                name.unwrap_or(DEFAULT_TOKEN_HEADER).to_string(),
+            )),
+            ("query", name) => Ok(TokenSource::Query(
+                name.unwrap_or(DEFAULT_TOKEN_QUERY_PARAM).to_string(),
+            )),
+            _ => Err(AuthGateError::ConfigError(format!(
+                "Unknown session token source: {}",
+                source
+            ))),
+        }
+    }
+}
+
+/// Session token sources from `AUTHGATE_SESSION_TOKEN_SOURCES`, in lookup order
+///
+/// Defaults to the session cookie, then a bearer token. Invalid sources are
+/// logged and skipped.
+pub fn token_sources_from_env() -> Vec<TokenSource> {
+    let sources: Vec<TokenSource> = env::var("AUTHGATE_SESSION_TOKEN_SOURCES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|source| !source.trim().is_empty())
+        .filter_map(|source| match TokenSource::parse(source) {
+            Ok(source) => Some(source),
+            Err(e) => {
+                error!("{}, ignoring it", e);
+                None
+            }
+        })
+        .collect();
+
+    if sources.is_empty() {
+        vec![TokenSource::Cookie, TokenSource::Bearer]
+    } else {
+        sources
+    }
+}
+
+/// Static headers sent with every session validation request, from
+/// `AUTHGATE_SESSION_EXTRA_HEADERS` as comma-separated `Name:value` pairs
+///
+/// Values are marked sensitive, as they typically carry API keys. Invalid
+/// entries, and the `Cookie` header carrying the session, are logged and skipped.
+pub fn session_headers_from_env() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for entry in env::var("AUTHGATE_SESSION_EXTRA_HEADERS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+    {
+        // Only the name is logged, so a malformed entry can't leak its value
+        let (name, value) = entry.split_once(':').unwrap_or((entry, ""));
+        let name = name.trim();
+        let header = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .ok()
+            .zip(HeaderValue::from_str(value.trim()).ok());
+        match header {
+            Some((name, _)) if name == COOKIE => {
+                warn!("The session cookie can't be replaced, ignoring extra Cookie header");
+            }
+            Some((name, mut value)) if !value.is_empty() => {
+                value.set_sensitive(true);
+                headers.append(name, value);
+            }
+            _ => error!("Invalid extra session header {}, ignoring it", name),
+        }
+    }
+    headers
+}
+
+/// Headers of the forwarded request passed on to the session endpoint, from
+/// `AUTHGATE_SESSION_FORWARD_HEADERS` as a comma-separated list of names
+///
+/// Invalid names and `Cookie`, which would pass on every cookie of the
+/// request, are logged and skipped.
+pub fn forwarded_headers_from_env() -> Vec<HeaderName> {
+    env::var("AUTHGATE_SESSION_FORWARD_HEADERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(name) if name == http::header::COOKIE => {
+                warn!("Not forwarding the Cookie header to the session endpoint");
+                None
+            }
+            Ok(name) => Some(name),
+            Err(_) => {
+                error!("Invalid forwarded session header {}, ignoring it", name);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Extract a token from an `Authorization: Bearer <token>` header
+///
+/// The scheme is matched case-insensitively. Other schemes yield `None`.
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(http::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    Some(token.trim().to_string())
+}
+
+/// What happens to an original URL too long to carry through a login redirect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongUrlPolicy {
+    /// Drop the query string, then cut the URL down to the limit
+    Truncate,
+    /// Redirect to the login page without a `next` parameter
+    Omit,
+}
+
+/// How cache hits are picked for revalidation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingMode {
+    /// Each hit is revalidated with a probability of 1 in N
+    Random,
+    /// Every Nth hit of each session is revalidated
+    PerToken,
+}
+
+/// Picks cache hits to revalidate against the session endpoint, so revoked
+/// sessions are noticed before their cache entry expires
+pub struct RevalidationSampler {
+    every: u64,
+    mode: SamplingMode,
+    hits: Mutex<HashMap<String, u64>>,
+}
+
+impl RevalidationSampler {
+    /// Revalidate one in `every` cache hits
+    pub fn new(every: u64, mode: SamplingMode) -> Self {
+        Self {
+            every: every.max(1),
+            mode,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sampler configured by `AUTHGATE_CACHE_REVALIDATE_EVERY` and
+    /// `AUTHGATE_CACHE_REVALIDATE_SAMPLING`, if sampling is enabled
+    pub fn from_env() -> Option<Self> {
+        let every = env::var("AUTHGATE_CACHE_REVALIDATE_EVERY")
+            .ok()
+            .and_then(|every| every.parse::<u64>().ok())
+            .filter(|every| *every > 0)?;
+
+        let mode = match env::var("AUTHGATE_CACHE_REVALIDATE_SAMPLING")
+            .unwrap_or_else(|_| "random".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "random" => SamplingMode::Random,
+            "token" => SamplingMode::PerToken,
+            other => {
+                warn!(
+                    "Unknown cache revalidation sampling mode {}, sampling randomly",
+                    other
+                );
+                SamplingMode::Random
+            }
+        };
+
+        info!("Revalidating 1 in {} cached session hits", every);
+        Some(Self::new(every, mode))
+    }
+
+    /// Record a cache hit for `cache_key`, returning whether to revalidate it
+    pub fn should_revalidate(&self, cache_key: &str) -> bool {
+        match self.mode {
+            // Each RandomState is freshly seeded, so hashing with one is a cheap
+            // random draw without pulling in an RNG
+            SamplingMode::Random => RandomState::new().hash_one(cache_key) % self.every == 0,
+            SamplingMode::PerToken => {
+                let mut hits = self.hits.lock().unwrap();
+                if hits.len() >= MAX_SAMPLED_SESSIONS && !hits.contains_key(cache_key) {
+                    hits.clear();
+                }
+
+                let count = hits.entry(cache_key.to_string()).or_insert(0);
+                *count += 1;
+                if *count >= self.every {
+                    hits.remove(cache_key);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Session endpoint request shared by concurrent validations of the same session
+type InFlightFetch = Shared<BoxFuture<'static, Result<SessionResponse, FetchError>>>;
 
 /// AuthService handles authentication and authorization
 pub struct AuthService {
     client: reqwest::Client,
     cache: Arc<dyn SessionCache>,
     cache_enabled: bool,
+    cache_strict: bool,
+    cache_grace: Duration,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    in_flight: Arc<Mutex<HashMap<String, InFlightFetch>>>,
+    negative_ttl: Duration,
+    rejected: Mutex<HashMap<String, (reqwest::StatusCode, Instant)>>,
+    session_url_counter: AtomicUsize,
+    log_invalid_sessions: bool,
+    jwt_verifier: Option<Arc<JwtVerifier>>,
+    token_sources: Vec<TokenSource>,
+    header_names: AuthHeaderNames,
+    revalidation: Option<RevalidationSampler>,
+    max_original_url_len: usize,
+    long_url_policy: LongUrlPolicy,
+    authorizer: Authorizer,
+    denial_webhook: Option<Arc<DenialWebhook>>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    session_headers: reqwest::header::HeaderMap,
+    forwarded_headers: Vec<HeaderName>,
+}
+
+impl Default for AuthService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AuthService {
     /// Create a new AuthService
     pub fn new() -> Self {
+        Self::with_cache(CacheFactory::create())
+    }
+
+    /// Create a new AuthService backed by an explicit session cache
+    pub fn with_cache(cache: Arc<dyn SessionCache>) -> Self {
         // Check if caching is enabled
         let cache_enabled = env::var("AUTHGATE_CACHE_ENABLED")
             .unwrap_or_else(|_| "true".to_string())
@@ -33,8 +470,75 @@ impl AuthService {
             info!("Session caching is disabled");
         }
 
-        // Create the cache
-        let cache = CacheFactory::create();
+        // Check if undeserializable session payloads should be logged for diagnosis
+        let log_invalid_sessions = env::var("AUTHGATE_LOG_INVALID_SESSIONS")
+            .unwrap_or_else(|_| "false".to_string())
+            .to_lowercase()
+            == "true";
+
+        // Check if cache backend errors should fail requests instead of bypassing the cache
+        let cache_strict = env::var("AUTHGATE_CACHE_STRICT")
+            .unwrap_or_else(|_| "false".to_string())
+            .to_lowercase()
+            == "true";
+
+        // Grace period for serving just-expired cached sessions while they are revalidated
+        let cache_grace = env::var("AUTHGATE_CACHE_GRACE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::ZERO);
+
+        // Remember rejected sessions so repeated invalid tokens don't reach the session endpoint
+        let negative_ttl = env::var("AUTHGATE_NEGATIVE_CACHE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::ZERO);
+
+        // Bound the original URL carried through login redirects
+        let max_original_url_len = env::var("AUTHGATE_MAX_ORIGINAL_URL_LENGTH")
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_ORIGINAL_URL_LEN);
+        let long_url_policy = match env::var("AUTHGATE_LONG_URL_POLICY")
+            .unwrap_or_else(|_| "truncate".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "omit" => LongUrlPolicy::Omit,
+            "truncate" => LongUrlPolicy::Truncate,
+            other => {
+                warn!("Unknown long URL policy {}, truncating long URLs", other);
+                LongUrlPolicy::Truncate
+            }
+        };
+
+        // How requirements combine on routes that don't set `combine`
+        let default_combine = match env::var("AUTHGATE_DEFAULT_COMBINE")
+            .unwrap_or_else(|_| "all".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "all" | "and" => CombineMode::All,
+            "any" | "or" => CombineMode::Any,
+            other => {
+                warn!(
+                    "Unknown default combine mode {}, requiring every requirement",
+                    other
+                );
+                CombineMode::All
+            }
+        };
+
+        // Verify session JWTs locally instead of calling the session endpoint
+        let jwt_verifier = match JwtVerifier::from_env() {
+            Ok(verifier) => verifier.map(Arc::new),
+            Err(e) => {
+                error!("{}, validating sessions against the session endpoint", e);
+                None
+            }
+        };
 
         Self {
             client: reqwest::Client::builder()
@@ -43,278 +547,744 @@ impl AuthService {
                 .expect("Failed to create HTTP client"),
             cache,
             cache_enabled,
+            cache_strict,
+            cache_grace: bounded_cache_grace(cache_grace),
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            negative_ttl,
+            rejected: Mutex::new(HashMap::new()),
+            session_url_counter: AtomicUsize::new(0),
+            log_invalid_sessions,
+            jwt_verifier,
+            token_sources: token_sources_from_env(),
+            header_names: AuthHeaderNames::from_env(),
+            revalidation: RevalidationSampler::from_env(),
+            max_original_url_len,
+            long_url_policy,
+            authorizer: Authorizer::new().with_default_combine(default_combine),
+            denial_webhook: DenialWebhook::from_env().map(Arc::new),
+            breaker: CircuitBreaker::from_env().map(Arc::new),
+            session_headers: session_headers_from_env(),
+            forwarded_headers: forwarded_headers_from_env(),
+        }
+    }
+
+    /// Set whether cache backend errors fail session validation (`AUTHGATE_CACHE_STRICT`)
+    pub fn with_strict_cache(mut self, strict: bool) -> Self {
+        self.cache_strict = strict;
+        self
+    }
+
+    /// Set how long an expired cached session is still served while it is
+    /// revalidated in the background (`AUTHGATE_CACHE_GRACE_SECS`)
+    ///
+    /// The grace period is capped at five minutes.
+    pub fn with_cache_grace(mut self, grace: Duration) -> Self {
+        self.cache_grace = bounded_cache_grace(grace);
+        self
+    }
+
+    /// Set how long a session rejected by the session endpoint is rejected
+    /// again without asking it, `Duration::ZERO` to disable
+    /// (`AUTHGATE_NEGATIVE_CACHE_SECS`)
+    pub fn with_negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = ttl;
+        self
+    }
+
+    /// Verify session JWTs locally with `verifier` instead of calling the session
+    /// endpoint (`AUTHGATE_SESSION_MODE=jwt`)
+    pub fn with_jwt_verifier(mut self, verifier: JwtVerifier) -> Self {
+        self.jwt_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Post denied requests to `webhook` (`AUTHGATE_DENIAL_WEBHOOK_URL`)
+    pub fn with_denial_webhook(mut self, webhook: DenialWebhook) -> Self {
+        self.denial_webhook = Some(Arc::new(webhook));
+        self
+    }
+
+    /// Report a denied request to the denial webhook, if one is configured
+    ///
+    /// Never waits for the webhook; see [`DenialWebhook::emit`].
+    pub fn report_denial(
+        &self,
+        kind: DenialKind,
+        ctx: &RequestContext,
+        client_ip: &str,
+        reason: &str,
+    ) {
+        if let Some(webhook) = &self.denial_webhook {
+            webhook.emit(DenialEvent::new(kind, ctx, client_ip, reason));
         }
     }
 
+    /// Fail fast while the session endpoint is down (`AUTHGATE_BREAKER_FAILURES`)
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(Arc::new(breaker));
+        self
+    }
+
+    /// Send static headers with every session validation request
+    /// (`AUTHGATE_SESSION_EXTRA_HEADERS`)
+    pub fn with_session_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.session_headers = headers;
+        self
+    }
+
+    /// Pass headers of the forwarded request on to the session endpoint
+    /// (`AUTHGATE_SESSION_FORWARD_HEADERS`)
+    pub fn with_forwarded_headers(mut self, names: Vec<HeaderName>) -> Self {
+        self.forwarded_headers = names;
+        self
+    }
+
+    /// State of the session endpoint circuit breaker, if one is configured
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.breaker.as_ref().map(|breaker| breaker.state())
+    }
+
+    /// Set how requirements combine on routes that don't set `combine`
+    /// (`AUTHGATE_DEFAULT_COMBINE`)
+    pub fn with_default_combine(mut self, combine: CombineMode) -> Self {
+        self.authorizer = self.authorizer.with_default_combine(combine);
+        self
+    }
+
+    /// Set the names of the user information headers (`AUTHGATE_HEADER_*`)
+    pub fn with_header_names(mut self, names: AuthHeaderNames) -> Self {
+        self.header_names = names;
+        self
+    }
+
+    /// Names of the user information headers sent on authorized requests
+    pub fn header_names(&self) -> &AuthHeaderNames {
+        &self.header_names
+    }
+
+    /// Set where session tokens are read from, in lookup order
+    /// (`AUTHGATE_SESSION_TOKEN_SOURCES`)
+    pub fn with_token_sources(mut self, sources: Vec<TokenSource>) -> Self {
+        self.token_sources = sources;
+        self
+    }
+
+    /// Revalidate cache hits picked by `sampler` in the background
+    /// (`AUTHGATE_CACHE_REVALIDATE_EVERY`)
+    pub fn with_revalidation_sampler(mut self, sampler: RevalidationSampler) -> Self {
+        self.revalidation = Some(sampler);
+        self
+    }
+
+    /// Set the maximum length of the original URL carried through a login
+    /// redirect, `0` for no limit, and what to do with longer ones
+    /// (`AUTHGATE_MAX_ORIGINAL_URL_LENGTH`, `AUTHGATE_LONG_URL_POLICY`)
+    pub fn with_original_url_limit(mut self, max_len: usize, policy: LongUrlPolicy) -> Self {
+        self.max_original_url_len = max_len;
+        self.long_url_policy = policy;
+        self
+    }
+
+    /// Pick the session endpoint for the next validation request
+    ///
+    /// With weighted `session_urls` configured, requests are spread across them in
+    /// weighted round-robin order; otherwise `session_url` is always used.
+    pub fn select_session_url<'a>(&self, auth: &'a AuthConfig) -> &'a str {
+        let n = self.session_url_counter.fetch_add(1, Ordering::Relaxed);
+        session_url_at(auth, n as u64)
+    }
+
+    /// Session endpoint the next validation request will use, without taking
+    /// its turn in the round-robin order
+    ///
+    /// Lets probes check an endpoint without skewing the weights of validations.
+    pub fn peek_session_url<'a>(&self, auth: &'a AuthConfig) -> &'a str {
+        let n = self.session_url_counter.load(Ordering::Relaxed);
+        session_url_at(auth, n as u64)
+    }
+
     /// Validate a session by calling the session endpoint
     pub async fn validate_session(
         &self,
         session_url: &str,
         session_token: &str,
     ) -> Result<SessionResponse, AuthGateError> {
-        // Check cache first if enabled
-        if self.cache_enabled {
-            if let Some(cached_session) = self.cache.get(session_token).await {
-                debug!(
-                    "Using cached session for user: {}",
-                    cached_session.user.email
-                );
-                return Ok(cached_session);
-            }
+        self.validate_session_with_cache_status(session_url, session_token)
+            .await
+            .map(|(session, _)| session)
+    }
+
+    /// Validate a session, also reporting whether it was served from the cache
+    pub async fn validate_session_with_cache_status(
+        &self,
+        session_url: &str,
+        session_token: &str,
+    ) -> Result<(SessionResponse, CacheStatus), AuthGateError> {
+        let headers = self.session_headers.clone();
+        self.validate_session_cached_as(session_url, session_token, session_token, None, headers)
+            .await
+    }
+
+    /// Validate a session without the cache, so the session endpoint itself is
+    /// always asked
+    pub async fn validate_session_uncached(
+        &self,
+        session_url: &str,
+        session_token: &str,
+    ) -> Result<SessionResponse, AuthGateError> {
+        if self.jwt_verifier.is_some() {
+            return self.validate_session_local(session_token).await;
         }
 
-        debug!("Validating session at {}", session_url);
+        fetch_session(
+            &self.client,
+            session_url,
+            session_token,
+            &self.session_headers,
+            self.log_invalid_sessions,
+        )
+        .await
+        .map_err(AuthGateError::from)
+    }
 
-        let response = self
-            .client
-            .get(session_url)
-            .header("Cookie", format!("session={}", session_token))
-            .send()
-            .await
-            .map_err(|e| {
-                error!("Failed to send session validation request: {}", e);
-                AuthGateError::AuthError(format!("Failed to validate session: {}", e))
-            })?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            warn!("Session validation failed with status: {}", status);
-            return Err(AuthGateError::AuthError(format!(
-                "Session validation failed with status: {}",
-                status
-            )));
+    /// Validate a session JWT locally, without calling the session endpoint
+    pub async fn validate_session_local(
+        &self,
+        session_token: &str,
+    ) -> Result<SessionResponse, AuthGateError> {
+        match &self.jwt_verifier {
+            Some(verifier) => verifier.verify(session_token).await,
+            None => Err(AuthGateError::ConfigError(
+                "Local session validation requires AUTHGATE_SESSION_MODE=jwt".to_string(),
+            )),
         }
+    }
 
-        let session: SessionResponse = response.json().await.map_err(|e| {
-            error!("Failed to parse session response: {}", e);
-            AuthGateError::AuthError(format!("Failed to parse session response: {}", e))
-        })?;
+    /// Validate a session for a route, honouring its `session_url` and
+    /// `session_cache_ttl` overrides
+    ///
+    /// Sessions validated against an override are cached under a key that includes
+    /// the override URL, so the same token issued by different session services
+    /// never collides. Sessions for the global endpoints keep the bare token key,
+    /// shared across all `auth.session_urls`.
+    ///
+    /// Routes with a `session_cache_ttl` cache sessions under a key of their own,
+    /// so a session cached for longer by another route is never served to them.
+    pub async fn validate_route_session(
+        &self,
+        auth: &AuthConfig,
+        route: &Route,
+        session_token: &str,
+    ) -> Result<(SessionResponse, CacheStatus), AuthGateError> {
+        self.validate_route_session_with_headers(auth, route, session_token, &HeaderMap::new())
+            .await
+    }
 
-        debug!(
-            "Session validated successfully for user: {}",
-            session.user.email
-        );
+    /// Validate a session for a route like [`Self::validate_route_session`],
+    /// passing the configured headers of the forwarded request on to the
+    /// session endpoint
+    ///
+    /// Sessions validated with forwarded headers are cached under a key that
+    /// includes a digest of their values, so a session endpoint answering by
+    /// e.g. client address never has its answer for one client served to
+    /// another. Concurrent validations share one request only when they forward
+    /// the same values.
+    pub async fn validate_route_session_with_headers(
+        &self,
+        auth: &AuthConfig,
+        route: &Route,
+        session_token: &str,
+        request_headers: &HeaderMap,
+    ) -> Result<(SessionResponse, CacheStatus), AuthGateError> {
+        let session_url = match &route.session_url {
+            Some(session_url) => session_url.as_str(),
+            None => self.select_session_url(auth),
+        };
+        let cache_key =
+            self.forwarded_cache_key(route_cache_key(route, session_token), request_headers);
+        let max_ttl = route.session_cache_ttl.map(Duration::from_secs);
+        let headers = self.session_request_headers(request_headers);
 
-        // Cache the session if caching is enabled
-        if self.cache_enabled {
-            // Extract JWT expiration time for TTL
-            if let Some(ttl) = extract_jwt_expiration(session_token) {
-                // Cache with the extracted TTL
-                if let Err(e) = self.cache.set(session_token, session.clone(), ttl).await {
-                    warn!("Failed to cache session: {}", e);
-                }
-            } else {
-                // If we can't extract expiration, use a default TTL
-                let default_ttl = Duration::from_secs(300); // 5 minutes
-                if let Err(e) = self
-                    .cache
-                    .set(session_token, session.clone(), default_ttl)
-                    .await
-                {
-                    warn!("Failed to cache session with default TTL: {}", e);
+        self.validate_session_cached_as(session_url, &cache_key, session_token, max_ttl, headers)
+            .await
+    }
+
+    /// Headers of a session validation request: the static headers, plus the
+    /// forwarded headers the request carries
+    ///
+    /// A static header isn't overridden by a forwarded one of the same name.
+    fn session_request_headers(&self, request_headers: &HeaderMap) -> reqwest::header::HeaderMap {
+        let mut headers = self.session_headers.clone();
+        for name in &self.forwarded_headers {
+            let Ok(outbound) = reqwest::header::HeaderName::from_bytes(name.as_str().as_bytes())
+            else {
+                continue;
+            };
+            if headers.contains_key(&outbound) {
+                continue;
+            }
+            for value in request_headers.get_all(name) {
+                if let Ok(value) = HeaderValue::from_bytes(value.as_bytes()) {
+                    headers.append(outbound.clone(), value);
                 }
             }
         }
+        headers
+    }
 
-        Ok(session)
+    /// Key of a session validated with the forwarded headers `request_headers`
+    /// carries: `cache_key` prefixed with a digest of their values, or
+    /// `cache_key` itself when it carries none
+    fn forwarded_cache_key(&self, cache_key: String, request_headers: &HeaderMap) -> String {
+        let mut forwarded = String::new();
+        for name in &self.forwarded_headers {
+            for value in request_headers.get_all(name) {
+                forwarded.push_str(name.as_str());
+                forwarded.push(':');
+                forwarded.push_str(&String::from_utf8_lossy(value.as_bytes()));
+                forwarded.push('\n');
+            }
+        }
+        if forwarded.is_empty() {
+            cache_key
+        } else {
+            format!("fwd={}|{}", session_key(&forwarded), cache_key)
+        }
     }
 
-    /// Authorize a request based on the matched route and session
-    pub fn authorize(&self, ctx: &RequestContext) -> AuthResult {
-        let session = match &ctx.session {
-            Some(session) => session,
-            None => return AuthResult::Unauthenticated,
-        };
+    /// Remove a session from the cache, e.g. when its user logs out upstream
+    ///
+    /// Routes with their own `session_url` or `session_cache_ttl` cache sessions
+    /// under keys of their own, so the session is removed under every key one of
+    /// `routes` may have cached it with. Succeeds whether or not it was cached.
+    pub async fn invalidate_session(
+        &self,
+        routes: &[Route],
+        session_token: &str,
+    ) -> Result<(), AuthGateError> {
+        self.invalidate_session_with_headers(routes, session_token, &HeaderMap::new())
+            .await
+    }
 
-        let route = match &ctx.matched_route {
-            Some(route) => route,
-            None => return AuthResult::Error("No matching route found".to_string()),
-        };
+    /// Remove a session from the cache like [`Self::invalidate_session`], also
+    /// under the keys it was cached with for the forwarded headers
+    /// `request_headers` carries
+    ///
+    /// Sessions cached with other forwarded values, e.g. for another client
+    /// address, can't be found by their key and stay cached until they expire.
+    pub async fn invalidate_session_with_headers(
+        &self,
+        routes: &[Route],
+        session_token: &str,
+        request_headers: &HeaderMap,
+    ) -> Result<(), AuthGateError> {
+        let mut cache_keys: HashSet<String> = HashSet::new();
+        let route_keys = routes
+            .iter()
+            .map(|route| route_cache_key(route, session_token))
+            .chain(std::iter::once(session_token.to_string()));
+        for cache_key in route_keys {
+            cache_keys.insert(self.forwarded_cache_key(cache_key.clone(), request_headers));
+            cache_keys.insert(cache_key);
+        }
 
-        // Check if the user has the required roles
-        if let Some(required_roles) = route.require.get("roles").and_then(|v| v.as_array()) {
-            let required_roles: Vec<String> = required_roles
-                .iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect();
-            if !self.has_any_role(&session.user.roles, &required_roles) {
-                return AuthResult::Unauthorized(format!(
-                    "User does not have any of the required roles: {:?}",
-                    required_roles
-                ));
-            }
+        for cache_key in &cache_keys {
+            self.cache.remove(cache_key).await?;
         }
+        debug!("Removed session from {} cache keys", cache_keys.len());
+        Ok(())
+    }
 
-        // Check if the user has the required permissions
-        if let Some(required_permissions) =
-            route.require.get("permissions").and_then(|v| v.as_array())
-        {
-            let required_permissions: Vec<String> = required_permissions
-                .iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect();
-            if !self.has_any_permission(&session.user.permissions, &required_permissions) {
-                return AuthResult::Unauthorized(format!(
-                    "User does not have any of the required permissions: {:?}",
-                    required_permissions
-                ));
-            }
+    /// Validate a session, caching the result under `cache_key` for at most `max_ttl`
+    async fn validate_session_cached_as(
+        &self,
+        session_url: &str,
+        cache_key: &str,
+        session_token: &str,
+        max_ttl: Option<Duration>,
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<(SessionResponse, CacheStatus), AuthGateError> {
+        // Local verification is cheap enough not to need the cache
+        if self.jwt_verifier.is_some() {
+            return self
+                .validate_session_local(session_token)
+                .await
+                .map(|session| (session, CacheStatus::Bypass));
         }
 
-        // Check if the user has the required scopes
-        if let Some(required_scopes_value) = route.require.get("scopes") {
-            if let Some(required_scopes_array) = required_scopes_value.as_array() {
-                // Convert JSON array to Vec<ScopeRequirement>
-                let mut required_scopes: Vec<ScopeRequirement> = Vec::new();
-                for scope_val in required_scopes_array {
-                    if let Ok(scope_req) =
-                        serde_json::from_value::<ScopeRequirement>(scope_val.clone())
-                    {
-                        required_scopes.push(scope_req);
-                    } else {
-                        return AuthResult::Error("Invalid scope requirement format".to_string());
+        // Check cache first if enabled
+        if self.cache_enabled {
+            let cached = if self.cache_strict {
+                self.cache.try_get_with_ttl(cache_key).await?
+            } else {
+                self.cache.get_with_ttl(cache_key).await
+            };
+
+            if let Some((cached_session, ttl)) = cached {
+                // Sessions are cached for the grace period past their expiry
+                if let Some(remaining) = ttl.filter(|_| !self.cache_grace.is_zero()) {
+                    if remaining <= self.cache_grace {
+                        debug!(
+                            "Using expired cached session within grace period for user: {}",
+                            cached_session.user.email
+                        );
+                        self.spawn_refresh(
+                            session_url,
+                            cache_key,
+                            session_token,
+                            max_ttl,
+                            &headers,
+                        );
+                        return Ok((cached_session, CacheStatus::Stale));
                     }
                 }
 
-                // Collect all scopes from all teams
-                let all_scopes: Vec<Scope> = session
-                    .user
-                    .teams
-                    .iter()
-                    .flat_map(|team| team.scopes.clone())
-                    .collect();
-
-                if !self.has_required_scopes(&all_scopes, &required_scopes) {
-                    return AuthResult::Unauthorized(format!(
-                        "User does not have the required scopes: {:?}",
-                        required_scopes
-                    ));
+                debug!(
+                    "Using cached session for user: {}",
+                    cached_session.user.email
+                );
+                if self
+                    .revalidation
+                    .as_ref()
+                    .is_some_and(|sampler| sampler.should_revalidate(cache_key))
+                {
+                    debug!("Revalidating sampled cached session");
+                    self.spawn_refresh(session_url, cache_key, session_token, max_ttl, &headers);
                 }
+                let ttl = ttl.map(|remaining| remaining.saturating_sub(self.cache_grace));
+                return Ok((cached_session, CacheStatus::Hit(ttl)));
             }
         }
 
-        // Check if the user is in any of the required teams with the required scopes
-        if let Some(required_teams_value) = route.require.get("teams") {
-            if let Some(required_teams_array) = required_teams_value.as_array() {
-                // Convert JSON array to Vec<TeamRequirement>
-                let mut required_teams: Vec<TeamRequirement> = Vec::new();
-                for team_val in required_teams_array {
-                    if let Ok(team_req) =
-                        serde_json::from_value::<TeamRequirement>(team_val.clone())
-                    {
-                        required_teams.push(team_req);
-                    } else {
-                        return AuthResult::Error("Invalid team requirement format".to_string());
-                    }
+        if let Some(status) = self.recent_rejection(cache_key) {
+            debug!("Session was rejected recently, not validating it again");
+            return Err(FetchError::Rejected(status).into());
+        }
+
+        if self
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| !breaker.allow())
+        {
+            debug!("Session endpoint circuit breaker is open, not validating the session");
+            return Err(AuthGateError::AuthError(
+                "Session endpoint is unavailable".to_string(),
+            ));
+        }
+
+        let result = fetch_session_once(
+            &self.in_flight,
+            &self.client,
+            session_url,
+            cache_key,
+            session_token,
+            headers,
+            self.log_invalid_sessions,
+        )
+        .await;
+        if let Some(breaker) = &self.breaker {
+            record_fetch(breaker, &result);
+        }
+
+        let session = match result {
+            Ok(session) => session,
+            Err(e) => {
+                if let FetchError::Rejected(status) = &e {
+                    self.remember_rejection(cache_key, *status);
                 }
+                return Err(e.into());
+            }
+        };
 
-                if !self.has_team_access(&session.user.teams, &required_teams) {
-                    return AuthResult::Unauthorized(format!(
-                        "User does not have access through any of the required teams: {:?}",
-                        required_teams
-                    ));
+        // Cache the session if caching is enabled
+        if self.cache_enabled {
+            let result = self
+                .cache
+                .set(
+                    cache_key,
+                    session.clone(),
+                    session_ttl(session_token, max_ttl) + self.cache_grace,
+                )
+                .await;
+
+            if let Err(e) = result {
+                if self.cache_strict {
+                    error!("Failed to cache session: {}", e);
+                    return Err(AuthGateError::CacheError(format!(
+                        "Failed to cache session: {}",
+                        e
+                    )));
                 }
+                warn!("Failed to cache session: {}", e);
             }
         }
 
-        // If we've made it here, the user is authorized
-        AuthResult::Authorized
+        let cache_status = if self.cache_enabled {
+            CacheStatus::Miss
+        } else {
+            CacheStatus::Bypass
+        };
+
+        Ok((session, cache_status))
     }
 
-    /// Check if the user has any of the required roles
-    fn has_any_role(&self, user_roles: &[String], required_roles: &[String]) -> bool {
-        for role in required_roles {
-            if user_roles.contains(role) {
-                debug!("User has required role: {}", role);
-                return true;
+    /// Status the session endpoint rejected the session under `cache_key` with,
+    /// if that was less than the negative cache TTL ago
+    fn recent_rejection(&self, cache_key: &str) -> Option<reqwest::StatusCode> {
+        if self.negative_ttl.is_zero() {
+            return None;
+        }
+
+        let mut rejected = self.rejected.lock().unwrap();
+        let key = session_key(cache_key);
+        match rejected.get(&key) {
+            Some((status, at)) if at.elapsed() < self.negative_ttl => Some(*status),
+            Some(_) => {
+                rejected.remove(&key);
+                None
             }
+            None => None,
         }
-        false
     }
 
-    /// Check if the user has any of the required permissions
-    fn has_any_permission(
-        &self,
-        user_permissions: &[String],
-        required_permissions: &[String],
-    ) -> bool {
-        for permission in required_permissions {
-            if user_permissions.contains(permission) {
-                debug!("User has required permission: {}", permission);
-                return true;
+    /// Record that the session endpoint rejected the session under `cache_key`
+    ///
+    /// Rejections are kept apart from the session cache, so they never replace a
+    /// cached session and are forgotten after the short negative cache TTL.
+    /// Endpoint failures aren't recorded, as they say nothing about the session.
+    fn remember_rejection(&self, cache_key: &str, status: reqwest::StatusCode) {
+        if self.negative_ttl.is_zero() {
+            return;
+        }
+
+        let mut rejected = self.rejected.lock().unwrap();
+        if rejected.len() >= MAX_REJECTED_SESSIONS {
+            rejected.retain(|_, (_, at)| at.elapsed() < self.negative_ttl);
+            if rejected.len() >= MAX_REJECTED_SESSIONS {
+                rejected.clear();
             }
         }
-        false
+        rejected.insert(session_key(cache_key), (status, Instant::now()));
     }
 
-    /// Check if the user has the required scopes
-    fn has_required_scopes(
+    /// Revalidate a cached session in the background, either because it is
+    /// within its grace period or because the cache hit was sampled
+    ///
+    /// A refreshed session replaces the cached one. If the session endpoint
+    /// rejects the session it is evicted, so it stops being served; if the
+    /// endpoint can't be reached, or the circuit breaker is open, the cached
+    /// session is left to run out its grace period. Refreshes share in-flight
+    /// requests and the breaker with request-time validation.
+    fn spawn_refresh(
         &self,
-        user_scopes: &[Scope],
-        required_scopes: &[ScopeRequirement],
-    ) -> bool {
-        for required_scope in required_scopes {
-            let mut found = false;
+        session_url: &str,
+        cache_key: &str,
+        session_token: &str,
+        max_ttl: Option<Duration>,
+        headers: &reqwest::header::HeaderMap,
+    ) {
+        if !self
+            .refreshing
+            .lock()
+            .unwrap()
+            .insert(cache_key.to_string())
+        {
+            debug!("Cached session is already being refreshed");
+            return;
+        }
 
-            for user_scope in user_scopes {
-                // Match resource type and action
-                if user_scope.resource_type == required_scope.resource_type
-                    && user_scope.action == required_scope.action
-                {
-                    // If resource_id is specified, it must match
-                    if let Some(required_resource_id) = &required_scope.resource_id {
-                        if &user_scope.resource_id == required_resource_id {
-                            found = true;
-                            break;
-                        }
-                    } else {
-                        // No specific resource_id required
-                        found = true;
-                        break;
+        let client = self.client.clone();
+        let in_flight = self.in_flight.clone();
+        let breaker = self.breaker.clone();
+        let cache = self.cache.clone();
+        let refreshing = self.refreshing.clone();
+        let grace = self.cache_grace;
+        let log_invalid_sessions = self.log_invalid_sessions;
+        let session_url = session_url.to_string();
+        let cache_key = cache_key.to_string();
+        let session_token = session_token.to_string();
+        let headers = headers.clone();
+
+        tokio::spawn(async move {
+            if breaker.as_ref().is_some_and(|breaker| !breaker.allow()) {
+                debug!("Session endpoint circuit breaker is open, not refreshing the session");
+                refreshing.lock().unwrap().remove(&cache_key);
+                return;
+            }
+
+            let result = fetch_session_once(
+                &in_flight,
+                &client,
+                &session_url,
+                &cache_key,
+                &session_token,
+                headers,
+                log_invalid_sessions,
+            )
+            .await;
+            if let Some(breaker) = &breaker {
+                record_fetch(breaker, &result);
+            }
+
+            match result {
+                Ok(session) => {
+                    let ttl = session_ttl(&session_token, max_ttl) + grace;
+                    if let Err(e) = cache.set(&cache_key, session, ttl).await {
+                        warn!("Failed to cache refreshed session: {}", e);
+                    }
+                }
+                Err(FetchError::Rejected(status)) => {
+                    info!(
+                        "Session rejected on refresh with status {}, evicting",
+                        status
+                    );
+                    if let Err(e) = cache.remove(&cache_key).await {
+                        warn!("Failed to evict rejected session: {}", e);
                     }
                 }
+                Err(FetchError::Failed(e)) => {
+                    warn!(
+                        "Failed to revalidate cached session, serving it until it expires: {}",
+                        e
+                    );
+                }
             }
+            refreshing.lock().unwrap().remove(&cache_key);
+        });
+    }
 
-            if !found {
-                return false;
+    /// Count a request against a route's rate limit, returning whether it is allowed
+    ///
+    /// Counters live in the session cache backend so that replicas sharing a Redis
+    /// cache also share limits. Backend errors fail open.
+    pub async fn check_rate_limit(
+        &self,
+        route: &Route,
+        rate_limit: &RateLimit,
+        subject: &str,
+    ) -> bool {
+        // Routes that differ only in port or methods keep separate budgets
+        let port = route.port.map(|port| port.to_string()).unwrap_or_default();
+        let methods = route
+            .methods
+            .as_ref()
+            .map(|methods| methods.join(","))
+            .unwrap_or_default();
+        let key = format!(
+            "ratelimit:{}:{}{}:{}:{}",
+            route.host, port, route.path, methods, subject
+        );
+        let window = Duration::from_secs(rate_limit.window_secs);
+
+        match self.cache.increment(&key, window).await {
+            Ok(count) => count <= rate_limit.requests,
+            Err(e) => {
+                warn!("Failed to check rate limit, allowing request: {}", e);
+                true
             }
         }
-
-        true
     }
 
-    /// Check if the user has access through any of the required teams
-    fn has_team_access(
+    /// Check that a session endpoint answers within `timeout`
+    ///
+    /// Any HTTP response counts as reachable, since the probe carries no session.
+    pub async fn probe_session_url(
         &self,
-        user_teams: &[crate::types::Team],
-        required_teams: &[TeamRequirement],
-    ) -> bool {
-        for team_req in required_teams {
-            for user_team in user_teams {
-                let id_match = team_req.id.as_ref().map_or(false, |id| id == &user_team.id);
-                let name_match = team_req
-                    .name
-                    .as_ref()
-                    .map_or(false, |name| name == &user_team.name);
-
-                // If either ID or name matches
-                if id_match || name_match {
-                    // If scopes are required, check them
-                    if let Some(required_scopes) = &team_req.scopes {
-                        if self.has_required_scopes(&user_team.scopes, required_scopes) {
-                            debug!("User has access through team: {}", user_team.name);
-                            return true;
-                        }
-                    } else {
-                        // No scopes required, team membership is enough
-                        debug!("User has access through team: {}", user_team.name);
-                        return true;
-                    }
+        session_url: &str,
+        timeout: Duration,
+    ) -> Result<(), AuthGateError> {
+        self.client
+            .get(session_url)
+            .timeout(timeout)
+            .send()
+            .await
+            .map(|response| {
+                debug!(
+                    "Session endpoint {} answered with {}",
+                    session_url,
+                    response.status()
+                );
+            })
+            .map_err(|e| AuthGateError::HttpError(format!("Session endpoint unreachable: {}", e)))
+    }
+
+    /// Persist the session cache, for backends that would otherwise lose it on shutdown
+    pub async fn persist_cache(&self) {
+        if let Err(e) = self.cache.persist().await {
+            warn!("Failed to persist session cache: {}", e);
+        }
+    }
+
+    /// Release cache resources on shutdown
+    ///
+    /// Persists the cache first, then closes its backend connections so they are
+    /// not left lingering on the server side.
+    pub async fn shutdown(&self) {
+        self.persist_cache().await;
+        if let Err(e) = self.cache.close().await {
+            warn!("Failed to close session cache: {}", e);
+        }
+    }
+
+    /// Check that the session cache's backend can be reached
+    pub async fn ping_cache(&self) -> Result<(), AuthGateError> {
+        self.cache.ping().await
+    }
+
+    /// Hit, miss and store counts of the session cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Authorize a request based on the matched route and session
+    pub fn authorize(&self, ctx: &RequestContext) -> AuthResult {
+        let session = match &ctx.session {
+            Some(session) => session,
+            None => return AuthResult::Unauthenticated,
+        };
+
+        let route = match &ctx.matched_route {
+            Some(route) => route,
+            None => return AuthResult::Error("No matching route found".to_string()),
+        };
+
+        self.authorizer.authorize(session, route)
+    }
+
+    /// Fit an original URL within the configured limit before it is carried
+    /// through a login redirect
+    ///
+    /// Returns `None` when the URL is too long and the policy is to omit it.
+    pub fn bounded_original_url(&self, original_url: &str) -> Option<String> {
+        let max_len = self.max_original_url_len;
+        if max_len == 0 || original_url.len() <= max_len {
+            return Some(original_url.to_string());
+        }
+
+        match self.long_url_policy {
+            LongUrlPolicy::Truncate => {
+                info!(
+                    "Original URL of {} bytes exceeds the {} byte limit, truncating it",
+                    original_url.len(),
+                    max_len
+                );
+                let url = original_url.split('?').next().unwrap_or_default();
+                let mut end = url.len().min(max_len);
+                while !url.is_char_boundary(end) {
+                    end -= 1;
                 }
+                Some(url[..end].to_string())
+            }
+            LongUrlPolicy::Omit => {
+                info!(
+                    "Original URL of {} bytes exceeds the {} byte limit, omitting it from the login redirect",
+                    original_url.len(),
+                    max_len
+                );
+                None
             }
         }
-
-        false
     }
 
     /// Create a login redirect URL with the next parameter
@@ -328,6 +1298,31 @@ impl AuthService {
         }
     }
 
+    /// Find the session token of a forwarded request
+    ///
+    /// The configured token sources are tried in order and the first one carrying
+    /// a non-empty token wins. Query sources are read from `request_uri`, the
+    /// forwarded URI including its query string.
+    pub fn find_session_token(
+        &self,
+        headers: &HeaderMap,
+        cookie_name: &str,
+        request_uri: &str,
+    ) -> Option<String> {
+        self.token_sources
+            .iter()
+            .filter_map(|source| match source {
+                TokenSource::Cookie => self.extract_session_token(headers, cookie_name),
+                TokenSource::Bearer => bearer_token(headers),
+                TokenSource::Header(name) => headers
+                    .get(name.as_str())
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.trim().to_string()),
+                TokenSource::Query(name) => query_param(request_uri, name),
+            })
+            .find(|token| !token.is_empty())
+    }
+
     /// Extract session token from cookies
     pub fn extract_session_token(&self, headers: &HeaderMap, cookie_name: &str) -> Option<String> {
         let cookie_header = headers.get(http::header::COOKIE)?;
@@ -346,3 +1341,294 @@ impl AuthService {
         None
     }
 }
+
+/// Session endpoint of the `n`th validation request in weighted round-robin
+/// order, or `session_url` without weighted `session_urls`
+fn session_url_at(auth: &AuthConfig, n: u64) -> &str {
+    let total_weight: u64 = auth.session_urls.iter().map(|u| u.weight as u64).sum();
+    if total_weight == 0 {
+        return &auth.session_url;
+    }
+
+    let mut slot = n % total_weight;
+    for endpoint in &auth.session_urls {
+        let weight = endpoint.weight as u64;
+        if slot < weight {
+            return &endpoint.url;
+        }
+        slot -= weight;
+    }
+
+    &auth.session_url
+}
+
+/// Key a session validated for `route` is cached under
+///
+/// Includes the route's `session_url` and `session_cache_ttl` overrides, if any,
+/// and is the bare token otherwise.
+fn route_cache_key(route: &Route, session_token: &str) -> String {
+    let cache_key = match &route.session_url {
+        Some(session_url) => format!("{}|{}", session_url, session_token),
+        None => session_token.to_string(),
+    };
+    match route.session_cache_ttl {
+        Some(max_ttl) => format!("ttl={}|{}", max_ttl, cache_key),
+        None => cache_key,
+    }
+}
+
+/// Read a decoded query parameter from a request URI
+fn query_param(request_uri: &str, name: &str) -> Option<String> {
+    let (_, query) = request_uri.split_once('?')?;
+    let query = query.split('#').next().unwrap_or_default();
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Why a session couldn't be fetched from the session endpoint
+#[derive(Clone)]
+enum FetchError {
+    /// The session endpoint rejected the session
+    Rejected(reqwest::StatusCode),
+    /// The session endpoint couldn't be reached or gave an unusable answer, and why
+    Failed(String),
+}
+
+impl From<FetchError> for AuthGateError {
+    fn from(err: FetchError) -> Self {
+        match err {
+            FetchError::Rejected(status) => AuthGateError::AuthError(format!(
+                "Session validation failed with status: {}",
+                status
+            )),
+            FetchError::Failed(reason) => AuthGateError::AuthError(reason),
+        }
+    }
+}
+
+/// Build a session endpoint request whose session cookie can't leak into logs
+///
+/// The cookie value is marked sensitive, so reqwest and hyper print it as
+/// `Sensitive` in their own debug and trace output.
+fn session_request(
+    client: &reqwest::Client,
+    session_url: &str,
+    session_token: &str,
+    headers: &reqwest::header::HeaderMap,
+) -> Result<reqwest::Request, FetchError> {
+    let mut cookie = HeaderValue::from_str(&format!("session={}", session_token))
+        .map_err(|_| FetchError::Failed("Session token is not a valid cookie value".to_string()))?;
+    cookie.set_sensitive(true);
+
+    let request = client
+        .get(session_url)
+        .headers(headers.clone())
+        .header(COOKIE, cookie)
+        .build()
+        .map_err(|e| {
+            error!("Failed to build session validation request: {}", e);
+            FetchError::Failed(format!("Failed to validate session: {}", e))
+        })?;
+    trace!(
+        "Session validation request headers: {:?}",
+        request.headers()
+    );
+    Ok(request)
+}
+
+/// Validate a session against the session endpoint
+async fn fetch_session(
+    client: &reqwest::Client,
+    session_url: &str,
+    session_token: &str,
+    headers: &reqwest::header::HeaderMap,
+    log_invalid_sessions: bool,
+) -> Result<SessionResponse, FetchError> {
+    debug!("Validating session at {}", session_url);
+
+    let request = session_request(client, session_url, session_token, headers)?;
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let response = client.execute(request).await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_session_validation(started.elapsed());
+    let response = response.map_err(|e| {
+        error!("Failed to send session validation request: {}", e);
+        FetchError::Failed(format!("Failed to validate session: {}", e))
+    })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        warn!("Session validation failed with status: {}", status);
+        if status.is_client_error() {
+            return Err(FetchError::Rejected(status));
+        }
+        return Err(FetchError::Failed(format!(
+            "Session validation failed with status: {}",
+            status
+        )));
+    }
+
+    let body = response.text().await.map_err(|e| {
+        error!("Failed to read session response: {}", e);
+        FetchError::Failed(format!("Failed to read session response: {}", e))
+    })?;
+
+    let session: SessionResponse = serde_json::from_str(&body).map_err(|e| {
+        error!("Failed to parse session response: {}", e);
+        if log_invalid_sessions {
+            debug!(
+                "Undeserializable session response body: {}",
+                scrub_session_payload(&body, session_token)
+            );
+        }
+        FetchError::Failed(format!("Failed to parse session response: {}", e))
+    })?;
+
+    debug!(
+        "Session validated successfully for user: {}",
+        session.user.email
+    );
+
+    Ok(session)
+}
+
+/// Fetch a session from the session endpoint, sharing one request between
+/// concurrent validations of the same session
+///
+/// Callers arriving while a request for `cache_key` is in flight await its
+/// result instead of sending their own, so an expiring cache entry under load
+/// doesn't stampede the session endpoint.
+async fn fetch_session_once(
+    in_flight: &Mutex<HashMap<String, InFlightFetch>>,
+    client: &reqwest::Client,
+    session_url: &str,
+    cache_key: &str,
+    session_token: &str,
+    headers: reqwest::header::HeaderMap,
+    log_invalid_sessions: bool,
+) -> Result<SessionResponse, FetchError> {
+    let fetch = {
+        let mut in_flight = in_flight.lock().unwrap();
+        match in_flight.get(cache_key) {
+            Some(fetch) => {
+                debug!("Awaiting in-flight validation of the same session");
+                fetch.clone()
+            }
+            None => {
+                let client = client.clone();
+                let session_url = session_url.to_string();
+                let session_token = session_token.to_string();
+                let fetch = async move {
+                    fetch_session(
+                        &client,
+                        &session_url,
+                        &session_token,
+                        &headers,
+                        log_invalid_sessions,
+                    )
+                    .await
+                }
+                .boxed()
+                .shared();
+                in_flight.insert(cache_key.to_string(), fetch.clone());
+                fetch
+            }
+        }
+    };
+
+    let result = fetch.clone().await;
+
+    // Later callers send a fresh request, unless one has already replaced this one
+    let mut in_flight = in_flight.lock().unwrap();
+    if in_flight
+        .get(cache_key)
+        .is_some_and(|current| current.ptr_eq(&fetch))
+    {
+        in_flight.remove(cache_key);
+    }
+
+    result
+}
+
+/// Report the outcome of a session fetch to the circuit breaker
+fn record_fetch(breaker: &CircuitBreaker, result: &Result<SessionResponse, FetchError>) {
+    // A rejected session still means the endpoint is up
+    match result {
+        Err(FetchError::Failed(_)) => breaker.record_failure(),
+        _ => breaker.record_success(),
+    }
+}
+
+/// How long a validated session stays fresh in the cache, capped at `max_ttl`
+fn session_ttl(session_token: &str, max_ttl: Option<Duration>) -> Duration {
+    // Use the JWT expiration if the token carries one
+    let ttl = extract_jwt_expiration(session_token).unwrap_or(DEFAULT_CACHE_TTL);
+    max_ttl.map_or(ttl, |max_ttl| ttl.min(max_ttl))
+}
+
+/// Cap a cache grace period at `MAX_CACHE_GRACE`
+fn bounded_cache_grace(grace: Duration) -> Duration {
+    if grace > MAX_CACHE_GRACE {
+        warn!(
+            "Cache grace period of {}s exceeds the maximum, using {}s",
+            grace.as_secs(),
+            MAX_CACHE_GRACE.as_secs()
+        );
+        return MAX_CACHE_GRACE;
+    }
+    grace
+}
+
+/// Prepare a session payload for logging by redacting secrets and truncating it
+fn scrub_session_payload(body: &str, session_token: &str) -> String {
+    let scrubbed = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact_sensitive_values(&mut value);
+            value.to_string()
+        }
+        Err(_) => SENSITIVE_VALUE_REGEX
+            .replace_all(body, r#"${1}"[REDACTED]""#)
+            .into_owned(),
+    };
+
+    // The token may be echoed back under an innocuous key
+    let scrubbed = if session_token.is_empty() {
+        scrubbed
+    } else {
+        scrubbed.replace(session_token, "[REDACTED]")
+    };
+
+    if scrubbed.chars().count() > MAX_LOGGED_PAYLOAD_CHARS {
+        let truncated: String = scrubbed.chars().take(MAX_LOGGED_PAYLOAD_CHARS).collect();
+        format!("{}... (truncated)", truncated)
+    } else {
+        scrubbed
+    }
+}
+
+/// Recursively replace the values of sensitive keys in a JSON value
+fn redact_sensitive_values(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SENSITIVE_KEYS
+                    .iter()
+                    .any(|sensitive| key.contains(sensitive))
+                {
+                    *value = serde_json::Value::String("[REDACTED]".to_string());
+                } else {
+                    redact_sensitive_values(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                redact_sensitive_values(item);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,536 @@
+use crate::expr::RequireExpr;
+use crate::types::{
+    AuthResult, CombineMode, RequireConfig, Route, Scope, ScopeRequirement, SessionResponse, Team,
+    TeamRequirement,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Requirements evaluated after the tenant check, which a route limited to a
+/// set of tenants can skip when it has none of them
+///
+/// These are also the requirements a route combining them with `any` needs
+/// just one of.
+const TENANT_FOLLOWUP_REQUIREMENTS: &[&str] = &[
+    "roles",
+    "permissions",
+    "email_domains",
+    "scopes",
+    "any_team",
+    "teams",
+    "expr",
+];
+
+/// Authorization engine evaluating a route's requirements against a session
+///
+/// Holds no HTTP client or cache, so it can be embedded in other servers that
+/// validate sessions themselves.
+#[derive(Debug, Clone, Default)]
+pub struct Authorizer {
+    default_combine: CombineMode,
+}
+
+impl Authorizer {
+    /// Create a new authorizer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how requirements combine on routes that don't set `combine`
+    pub fn with_default_combine(mut self, combine: CombineMode) -> Self {
+        self.default_combine = combine;
+        self
+    }
+
+    /// Authorize a session against the requirements of a route
+    pub fn authorize(&self, session: &SessionResponse, route: &Route) -> AuthResult {
+        // Requirements that don't parse, e.g. a corrupt stored value, would
+        // otherwise be partly skipped, so they can't be evaluated at all
+        if !route.require.is_null() {
+            if let Err(e) = RequireConfig::deserialize(&route.require) {
+                return AuthResult::Error(format!("Invalid route requirements: {}", e));
+            }
+        }
+
+        // Hierarchical groups such as /eng/backend also count as their parents
+        let nested = route.require.get("nested_groups").and_then(|v| v.as_bool()) == Some(true);
+
+        // Denied roles override every other requirement
+        if let Some(denied_roles) = route.require.get("deny_roles").and_then(|v| v.as_array()) {
+            if let Some(role) = denied_roles
+                .iter()
+                .filter_map(|v| v.as_str())
+                .find(|role| session.user.roles.iter().any(|r| grants(r, role, nested)))
+            {
+                return AuthResult::Unauthorized(format!("User has denied role: {}", role));
+            }
+        }
+
+        // Check if the session was authenticated with MFA
+        if route.require.get("require_mfa").and_then(|v| v.as_bool()) == Some(true)
+            && !session.is_mfa()
+        {
+            return AuthResult::Unauthorized(
+                "Route requires multi-factor authentication, step up authentication and retry"
+                    .to_string(),
+            );
+        }
+
+        // Tenants the session must belong to, checked before override permissions
+        // so that those never grant access across tenants
+        let allowed_tenants: Option<Vec<&str>> =
+            match route.require.get("tenant_id").and_then(|v| v.as_str()) {
+                Some(tenant_id) => Some(vec![tenant_id]),
+                None => route
+                    .require
+                    .get("tenants")
+                    .and_then(|v| v.as_array())
+                    .map(|tenants| tenants.iter().filter_map(|v| v.as_str()).collect()),
+            };
+        let wrong_tenant = allowed_tenants
+            .as_ref()
+            .is_some_and(|tenants| !tenants.contains(&session.tenant_id.as_str()));
+
+        // Override permissions grant access whatever the remaining requirements
+        // say, within the route's tenants
+        if let Some(overrides) = route
+            .require
+            .get("override_permissions")
+            .and_then(|v| v.as_array())
+            .filter(|_| !wrong_tenant)
+        {
+            if let Some(permission) =
+                overrides
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .find(|permission| {
+                        session
+                            .user
+                            .permissions
+                            .iter()
+                            .any(|p| grants(p, permission, nested))
+                    })
+            {
+                debug!("User has override permission: {}", permission);
+                return AuthResult::Authorized;
+            }
+        }
+
+        // Requirements below are all evaluated when the route asks for every
+        // failed one to be reported, or needs only one of them to pass
+        let combine = route
+            .require
+            .get("combine")
+            .and_then(|v| CombineMode::deserialize(v).ok())
+            .unwrap_or(self.default_combine);
+        let mut denials = Denials::new(
+            route.require.get("deny_reasons").and_then(|v| v.as_str()) == Some("all"),
+            match combine {
+                CombineMode::All => None,
+                CombineMode::Any => Some(combined_requirements(&route.require)),
+            },
+        );
+
+        // Check if the session belongs to one of the allowed tenants
+        if let Some(tenants) = allowed_tenants {
+            if wrong_tenant {
+                let reason = format!(
+                    "User tenant {} is not one of the allowed tenants: {:?}",
+                    session.tenant_id, tenants
+                );
+                // Other requirements never make up for the wrong tenant
+                if combine == CombineMode::Any {
+                    return AuthResult::Unauthorized(reason);
+                }
+                if let Some(denied) = denials.deny(reason) {
+                    return denied;
+                }
+            } else if !TENANT_FOLLOWUP_REQUIREMENTS
+                .iter()
+                .any(|key| route.require.get(key).is_some())
+            {
+                // Nothing else to evaluate for a plain tenant restriction
+                return AuthResult::Authorized;
+            }
+        }
+
+        // Check if the user has the required roles
+        if let Some(required_roles) = route.require.get("roles").and_then(|v| v.as_array()) {
+            let required_roles: Vec<String> = required_roles
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+            let match_all =
+                route.require.get("roles_match").and_then(|v| v.as_str()) == Some("all");
+            if match_all {
+                if !self.has_all_roles(&session.user.roles, &required_roles, nested) {
+                    if let Some(denied) = denials.deny(format!(
+                        "User does not have all of the required roles: {:?}",
+                        required_roles
+                    )) {
+                        return denied;
+                    }
+                }
+            } else if !self.has_any_role(&session.user.roles, &required_roles, nested) {
+                if let Some(denied) = denials.deny(format!(
+                    "User does not have any of the required roles: {:?}",
+                    required_roles
+                )) {
+                    return denied;
+                }
+            }
+        }
+
+        // Check if the user has the required permissions
+        if let Some(required_permissions) =
+            route.require.get("permissions").and_then(|v| v.as_array())
+        {
+            let required_permissions: Vec<String> = required_permissions
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+            if !self.has_any_permission(&session.user.permissions, &required_permissions, nested) {
+                if let Some(denied) = denials.deny(format!(
+                    "User does not have any of the required permissions: {:?}",
+                    required_permissions
+                )) {
+                    return denied;
+                }
+            }
+        }
+
+        // Check if the user's email is under one of the required domains
+        if let Some(required_domains) = route
+            .require
+            .get("email_domains")
+            .and_then(|v| v.as_array())
+        {
+            let required_domains: Vec<String> = required_domains
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+            if !self.has_email_domain(&session.user.email, &required_domains) {
+                if let Some(denied) = denials.deny(format!(
+                    "User email is not in any of the required domains: {:?}",
+                    required_domains
+                )) {
+                    return denied;
+                }
+            }
+        }
+
+        // Check if the user has the required scopes
+        if let Some(required_scopes_value) = route.require.get("scopes") {
+            if let Some(required_scopes_array) = required_scopes_value.as_array() {
+                // Convert JSON array to Vec<ScopeRequirement>
+                let mut required_scopes: Vec<ScopeRequirement> = Vec::new();
+                for scope_val in required_scopes_array {
+                    if let Ok(scope_req) =
+                        serde_json::from_value::<ScopeRequirement>(scope_val.clone())
+                    {
+                        required_scopes.push(scope_req);
+                    } else {
+                        return AuthResult::Error("Invalid scope requirement format".to_string());
+                    }
+                }
+
+                // Collect all scopes from all teams
+                let all_scopes: Vec<Scope> = session
+                    .user
+                    .teams
+                    .iter()
+                    .flat_map(|team| team.scopes.clone())
+                    .collect();
+
+                if !self.has_required_scopes(&all_scopes, &required_scopes) {
+                    if let Some(denied) = denials.deny(format!(
+                        "User does not have the required scopes: {:?}",
+                        required_scopes
+                    )) {
+                        return denied;
+                    }
+                }
+            }
+        }
+
+        // Check if the user belongs to any team at all
+        if route.require.get("any_team").and_then(|v| v.as_bool()) == Some(true)
+            && session.user.teams.is_empty()
+        {
+            if let Some(denied) = denials.deny("User is not a member of any team".to_string()) {
+                return denied;
+            }
+        }
+
+        // Check if the user is in any of the required teams with the required scopes
+        if let Some(required_teams_value) = route.require.get("teams") {
+            if let Some(required_teams_array) = required_teams_value.as_array() {
+                // Convert JSON array to Vec<TeamRequirement>
+                let mut required_teams: Vec<TeamRequirement> = Vec::new();
+                for team_val in required_teams_array {
+                    if let Ok(team_req) =
+                        serde_json::from_value::<TeamRequirement>(team_val.clone())
+                    {
+                        required_teams.push(team_req);
+                    } else {
+                        return AuthResult::Error("Invalid team requirement format".to_string());
+                    }
+                }
+
+                if !self.has_team_access(&session.user.teams, &required_teams) {
+                    // Distinguish a user without teams from a team mismatch, which
+                    // usually points at a misconfigured team id or name
+                    let reason = if session.user.teams.is_empty() {
+                        debug!(
+                            "User {} has no teams, required teams: {:?}",
+                            session.user.id, required_teams
+                        );
+                        format!(
+                            "User is not a member of any team, required teams: {:?}",
+                            required_teams
+                        )
+                    } else {
+                        let user_teams: Vec<&str> = session
+                            .user
+                            .teams
+                            .iter()
+                            .map(|team| team.id.as_str())
+                            .collect();
+                        debug!(
+                            "User {} has teams {:?} but none matched the required teams: {:?}",
+                            session.user.id, user_teams, required_teams
+                        );
+                        format!(
+                            "User does not have access through any of the required teams: {:?}",
+                            required_teams
+                        )
+                    };
+                    if let Some(denied) = denials.deny(reason) {
+                        return denied;
+                    }
+                }
+            }
+        }
+
+        // Check the boolean requirement expression, parsed when the configuration
+        // was loaded; routes that weren't loaded have it parsed here
+        if let Some(expr) = route.require.get("expr").and_then(|v| v.as_str()) {
+            let parsed = match &route.require_expr {
+                Some(parsed) => Ok(parsed.clone()),
+                None => RequireExpr::parse(expr).map(Arc::new),
+            };
+            match parsed {
+                Ok(parsed) => {
+                    if !parsed.evaluate(session, nested) {
+                        if let Some(denied) = denials.deny(format!(
+                            "User does not satisfy the required expression: {}",
+                            expr
+                        )) {
+                            return denied;
+                        }
+                    }
+                }
+                Err(e) => return AuthResult::Error(e.to_string()),
+            }
+        }
+
+        // If we've made it here, the user is authorized unless a failure was deferred
+        denials.finish()
+    }
+
+    /// Check if the user has any of the required roles
+    fn has_any_role(&self, user_roles: &[String], required_roles: &[String], nested: bool) -> bool {
+        for role in required_roles {
+            if user_roles.iter().any(|r| grants(r, role, nested)) {
+                debug!("User has required role: {}", role);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if the user has every one of the required roles
+    fn has_all_roles(
+        &self,
+        user_roles: &[String],
+        required_roles: &[String],
+        nested: bool,
+    ) -> bool {
+        for role in required_roles {
+            if !user_roles.iter().any(|r| grants(r, role, nested)) {
+                debug!("User is missing required role: {}", role);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Check if the user has any of the required permissions
+    fn has_any_permission(
+        &self,
+        user_permissions: &[String],
+        required_permissions: &[String],
+        nested: bool,
+    ) -> bool {
+        for permission in required_permissions {
+            if user_permissions
+                .iter()
+                .any(|p| grants(p, permission, nested))
+            {
+                debug!("User has required permission: {}", permission);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if the email is under any of the required domains
+    ///
+    /// Matching is case-insensitive. A domain written as `*.example.com` also
+    /// matches subdomains such as `eu.example.com`.
+    fn has_email_domain(&self, email: &str, required_domains: &[String]) -> bool {
+        let email_domain = match email.rsplit_once('@') {
+            Some((_, domain)) if !domain.is_empty() => domain.to_lowercase(),
+            _ => return false,
+        };
+
+        for domain in required_domains {
+            let domain = domain.trim_start_matches('@').to_lowercase();
+            let matched = match domain.strip_prefix("*.") {
+                Some(base) => email_domain == base || email_domain.ends_with(&format!(".{}", base)),
+                None => email_domain == domain,
+            };
+            if matched {
+                debug!("User email is in required domain: {}", domain);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if the user has the required scopes
+    fn has_required_scopes(
+        &self,
+        user_scopes: &[Scope],
+        required_scopes: &[ScopeRequirement],
+    ) -> bool {
+        // A `*` action or resource ID in a user scope covers any required one
+        required_scopes.iter().all(|required_scope| {
+            user_scopes
+                .iter()
+                .any(|user_scope| user_scope.satisfies(required_scope))
+        })
+    }
+
+    /// Check if the user has access through any of the required teams
+    fn has_team_access(&self, user_teams: &[Team], required_teams: &[TeamRequirement]) -> bool {
+        for team_req in required_teams {
+            for user_team in user_teams {
+                let id_match = team_req.id.as_ref().is_some_and(|id| id == &user_team.id);
+                let name_match = team_req
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name == &user_team.name);
+
+                // If either ID or name matches
+                if id_match || name_match {
+                    // Owner-only teams ignore plain members
+                    if team_req.owner == Some(true) && !user_team.is_owner {
+                        debug!("User is not an owner of team: {}", user_team.name);
+                        continue;
+                    }
+
+                    // If scopes are required, check them
+                    if let Some(required_scopes) = &team_req.scopes {
+                        if self.has_required_scopes(&user_team.scopes, required_scopes) {
+                            debug!("User has access through team: {}", user_team.name);
+                            return true;
+                        }
+                    } else {
+                        // No scopes required, team membership is enough
+                        debug!("User has access through team: {}", user_team.name);
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Failed requirements of a route, reported on the first failure or combined
+struct Denials {
+    report_all: bool,
+    /// Number of requirements evaluated when any one of them passing is enough
+    any_of: Option<usize>,
+    reasons: Vec<String>,
+}
+
+impl Denials {
+    fn new(report_all: bool, any_of: Option<usize>) -> Self {
+        Self {
+            report_all,
+            any_of,
+            reasons: Vec::new(),
+        }
+    }
+
+    /// Record a failed requirement, returning the denial unless every failure
+    /// is reported or another requirement may still pass
+    fn deny(&mut self, reason: String) -> Option<AuthResult> {
+        if self.report_all || self.any_of.is_some() {
+            self.reasons.push(reason);
+            None
+        } else {
+            Some(AuthResult::Unauthorized(reason))
+        }
+    }
+
+    /// Deny with every recorded reason, or authorize when none was recorded
+    /// or, combining with `any`, some requirement passed
+    fn finish(self) -> AuthResult {
+        let any_passed = self
+            .any_of
+            .is_some_and(|evaluated| self.reasons.len() < evaluated);
+        if self.reasons.is_empty() || any_passed {
+            AuthResult::Authorized
+        } else {
+            AuthResult::Unauthorized(self.reasons.join("; "))
+        }
+    }
+}
+
+/// Count the requirements of a require block that a route combining them with
+/// `any` needs just one of
+fn combined_requirements(require: &serde_json::Value) -> usize {
+    TENANT_FOLLOWUP_REQUIREMENTS
+        .iter()
+        .filter_map(|key| require.get(key))
+        .filter(|value| !value.is_null() && value.as_bool() != Some(false))
+        .count()
+}
+
+/// Check whether a role or permission the user holds grants a required one
+///
+/// With `nested`, `/`-separated groups also grant their ancestors, so
+/// `/eng/backend` grants `/eng` but `/engineering` and `/eng-ops` don't.
+pub(crate) fn grants(held: &str, required: &str, nested: bool) -> bool {
+    if held == required {
+        return true;
+    }
+    if !nested {
+        return false;
+    }
+    let parent = required.trim_end_matches('/');
+    !parent.is_empty()
+        && held
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Authorize a session against the requirements of a route
+pub fn authorize(session: &SessionResponse, route: &Route) -> AuthResult {
+    Authorizer::new().authorize(session, route)
+}
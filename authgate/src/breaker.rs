@@ -0,0 +1,189 @@
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Default window in which consecutive failures trip the breaker
+const DEFAULT_BREAKER_WINDOW_SECS: u64 = 30;
+
+/// Default time the breaker stays open before letting a request through
+const DEFAULT_BREAKER_COOLDOWN_SECS: u64 = 30;
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Requests reach the session endpoint
+    Closed,
+    /// Requests fail fast without reaching the session endpoint
+    Open,
+    /// The cool-down is over and a single request tests whether the session
+    /// endpoint recovered
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Name of the state, as used in metrics labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    /// Consecutive failures since the first one at `first_failure`
+    failures: u32,
+    first_failure: Option<Instant>,
+    /// When the breaker last tripped, while it is open or half-open
+    opened_at: Option<Instant>,
+    /// When the half-open test request was let through
+    probe_started: Option<Instant>,
+}
+
+/// Circuit breaker for the session endpoint
+///
+/// After `threshold` consecutive failures within `window`, the breaker trips
+/// open and requests fail fast for `cooldown` instead of each waiting for the
+/// endpoint to time out. It then half-opens, letting a single request through:
+/// a success closes it again, a failure reopens it for another cool-down.
+///
+/// Only failures to get an answer count; a session the endpoint rejects means
+/// it is up.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker tripping after `threshold` consecutive failures within
+    /// `window` and staying open for `cooldown`
+    pub fn new(threshold: u32, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            window,
+            cooldown,
+            inner: Mutex::new(BreakerInner {
+                failures: 0,
+                first_failure: None,
+                opened_at: None,
+                probe_started: None,
+            }),
+        }
+    }
+
+    /// Create the breaker configured by `AUTHGATE_BREAKER_FAILURES`,
+    /// `AUTHGATE_BREAKER_WINDOW_SECS` and `AUTHGATE_BREAKER_COOLDOWN_SECS`, if
+    /// enabled with a failure threshold above zero
+    pub fn from_env() -> Option<Self> {
+        let threshold = env::var("AUTHGATE_BREAKER_FAILURES")
+            .ok()
+            .and_then(|failures| failures.parse::<u32>().ok())
+            .filter(|failures| *failures > 0)?;
+        let window = env::var("AUTHGATE_BREAKER_WINDOW_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_BREAKER_WINDOW_SECS);
+        let cooldown = env::var("AUTHGATE_BREAKER_COOLDOWN_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_BREAKER_COOLDOWN_SECS);
+
+        Some(Self::new(
+            threshold,
+            Duration::from_secs(window),
+            Duration::from_secs(cooldown),
+        ))
+    }
+
+    /// Current state of the breaker
+    pub fn state(&self) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        self.state_of(&inner)
+    }
+
+    fn state_of(&self, inner: &BreakerInner) -> BreakerState {
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a request may call the session endpoint
+    ///
+    /// While half-open only one request is let through at a time. Should it
+    /// never report back, e.g. because the client went away, another is let
+    /// through after a further cool-down.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match self.state_of(&inner) {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => {
+                let probing = inner
+                    .probe_started
+                    .is_some_and(|started| started.elapsed() < self.cooldown);
+                if !probing {
+                    info!("Testing whether the session endpoint recovered");
+                    inner.probe_started = Some(Instant::now());
+                }
+                !probing
+            }
+        }
+    }
+
+    /// Record that the session endpoint answered, closing the breaker
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.opened_at.is_some() {
+            info!("Session endpoint recovered, closing the circuit breaker");
+        }
+        inner.failures = 0;
+        inner.first_failure = None;
+        inner.opened_at = None;
+        inner.probe_started = None;
+    }
+
+    /// Record that the session endpoint couldn't be reached or failed,
+    /// tripping the breaker once failures reach the threshold
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        match self.state_of(&inner) {
+            BreakerState::Closed => {
+                // Failures spread beyond the window start a new count
+                let within_window = inner
+                    .first_failure
+                    .is_some_and(|first| first.elapsed() <= self.window);
+                if !within_window {
+                    inner.failures = 0;
+                    inner.first_failure = Some(Instant::now());
+                }
+                inner.failures += 1;
+
+                if inner.failures >= self.threshold {
+                    warn!(
+                        "Session endpoint failed {} times in a row, opening the circuit breaker for {:?}",
+                        inner.failures, self.cooldown
+                    );
+                    inner.opened_at = Some(Instant::now());
+                }
+            }
+            BreakerState::HalfOpen => {
+                warn!(
+                    "Session endpoint still failing, reopening the circuit breaker for {:?}",
+                    self.cooldown
+                );
+                inner.opened_at = Some(Instant::now());
+                inner.probe_started = None;
+            }
+            // Requests let through before the breaker tripped
+            BreakerState::Open => {}
+        }
+    }
+}
@@ -1,12 +1,19 @@
 use crate::types::{AuthGateError, SessionResponse};
 use async_trait::async_trait;
 use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use lru::LruCache;
+use redis::aio::ConnectionManager;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
 use std::env;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::RwLock;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 /// Cache trait defining the interface for session caching
@@ -15,6 +22,21 @@ pub trait SessionCache: Send + Sync {
     /// Get a session from the cache
     async fn get(&self, token: &str) -> Option<SessionResponse>;
 
+    /// Get a session from the cache along with its remaining TTL, if the backend reports it
+    async fn get_with_ttl(&self, token: &str) -> Option<(SessionResponse, Option<Duration>)> {
+        self.get(token).await.map(|session| (session, None))
+    }
+
+    /// Like `get_with_ttl`, but reports backend failures instead of treating them as misses
+    ///
+    /// An entry the backend returns but that can't be read is still a miss.
+    async fn try_get_with_ttl(
+        &self,
+        token: &str,
+    ) -> Result<Option<(SessionResponse, Option<Duration>)>, AuthGateError> {
+        Ok(self.get_with_ttl(token).await)
+    }
+
     /// Set a session in the cache with TTL
     async fn set(
         &self,
@@ -25,6 +47,70 @@ pub trait SessionCache: Send + Sync {
 
     /// Remove a session from the cache
     async fn remove(&self, token: &str) -> Result<(), AuthGateError>;
+
+    /// Increment a counter that resets `window` after its first increment,
+    /// returning the new count
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, AuthGateError>;
+
+    /// Persist the cache contents, for backends that lose them on restart
+    async fn persist(&self) -> Result<(), AuthGateError> {
+        Ok(())
+    }
+
+    /// Close any pooled backend connections, for backends that hold them
+    async fn close(&self) -> Result<(), AuthGateError> {
+        Ok(())
+    }
+
+    /// Check that the cache's backend can be reached; in-process caches
+    /// always can
+    async fn ping(&self) -> Result<(), AuthGateError> {
+        Ok(())
+    }
+
+    /// Session lookup and store counts since the cache was created, for
+    /// backends that keep them
+    fn stats(&self) -> CacheStats {
+        CacheStats::default()
+    }
+}
+
+/// Session cache effectiveness counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that found no session
+    pub misses: u64,
+    /// Sessions stored
+    pub sets: u64,
+}
+
+/// Lock-free counters behind [`CacheStats`]
+#[derive(Debug, Default)]
+struct StatsCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    sets: AtomicU64,
+}
+
+impl StatsCounters {
+    fn record_lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_set(&self) {
+        self.sets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            sets: self.sets.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// JWT claims structure for extracting expiration time
@@ -34,27 +120,136 @@ struct Claims {
     // Other fields can be added as needed
 }
 
+/// Outcome of a session cache lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The session was served from the cache, with its remaining TTL if known
+    Hit(Option<Duration>),
+    /// The cached session has expired but is within the grace period, and is
+    /// being revalidated in the background
+    Stale,
+    /// The session was not cached and was validated against the session endpoint
+    Miss,
+    /// Caching is disabled
+    Bypass,
+}
+
+impl CacheStatus {
+    /// Value used for the `X-Auth-Cache` debug header
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit(_) => "hit",
+            CacheStatus::Stale => "stale",
+            CacheStatus::Miss => "miss",
+            CacheStatus::Bypass => "bypass",
+        }
+    }
+}
+
+/// Subsystem a cache is created for
+///
+/// Each purpose can use its own backend, configured with
+/// `AUTHGATE_<PURPOSE>_CACHE_BACKEND` and `AUTHGATE_<PURPOSE>_REDIS_URL`, e.g.
+/// `AUTHGATE_ADMIN_CACHE_BACKEND`. Unset, they fall back to
+/// `AUTHGATE_CACHE_BACKEND` and `AUTHGATE_REDIS_URL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePurpose {
+    /// Validated sessions and rate limit counters
+    Sessions,
+    /// Admin API state, such as used break-glass tokens
+    Admin,
+}
+
+impl CachePurpose {
+    /// Name used in the purpose's environment variables
+    fn env_name(&self) -> &'static str {
+        match self {
+            CachePurpose::Sessions => "SESSION",
+            CachePurpose::Admin => "ADMIN",
+        }
+    }
+
+    /// Read a per-purpose setting, falling back to the global one
+    fn env_var(&self, setting: &str) -> Option<String> {
+        env::var(format!("AUTHGATE_{}_{}", self.env_name(), setting))
+            .or_else(|_| env::var(format!("AUTHGATE_{}", setting)))
+            .ok()
+    }
+}
+
+/// Backend a cache is stored in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheBackend {
+    Memory,
+    Redis { url: String },
+}
+
 /// Cache factory for creating the appropriate cache implementation
 pub struct CacheFactory;
 
 impl CacheFactory {
-    /// Create a new cache instance based on environment configuration
+    /// Create the session cache based on environment configuration
     pub fn create() -> Arc<dyn SessionCache> {
-        let cache_backend =
-            env::var("AUTHGATE_CACHE_BACKEND").unwrap_or_else(|_| "memory".to_string());
-
-        match cache_backend.to_lowercase().as_str() {
-            "redis" => {
-                let redis_url = env::var("AUTHGATE_REDIS_URL")
-                    .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        Self::create_for(CachePurpose::Sessions)
+    }
 
-                info!("Using Redis cache backend at {}", redis_url);
-                Arc::new(RedisCache::new(&redis_url))
+    /// Create a cache for a subsystem, using the backend configured for it
+    ///
+    /// Only the session cache snapshots the in-memory backend.
+    pub fn create_for(purpose: CachePurpose) -> Arc<dyn SessionCache> {
+        match Self::backend_for(purpose) {
+            CacheBackend::Redis { url } => {
+                info!("Using Redis {:?} cache backend at {}", purpose, url);
+                Arc::new(RedisCache::new(&url))
             }
-            _ => {
+            CacheBackend::Memory if purpose == CachePurpose::Sessions => {
                 info!("Using in-memory cache backend");
-                Arc::new(InMemoryCache::new())
+                let max_entries = Self::max_entries_for(purpose);
+                match env::var("AUTHGATE_CACHE_SNAPSHOT") {
+                    Ok(path) => {
+                        let interval = env::var("AUTHGATE_CACHE_SNAPSHOT_INTERVAL")
+                            .ok()
+                            .and_then(|v| v.parse::<u64>().ok())
+                            .unwrap_or(60);
+
+                        let cache = Arc::new(
+                            InMemoryCache::with_snapshot(path).with_max_entries(max_entries),
+                        );
+                        cache.spawn_snapshot_task(Duration::from_secs(interval));
+                        cache
+                    }
+                    Err(_) => Arc::new(InMemoryCache::new().with_max_entries(max_entries)),
+                }
             }
+            CacheBackend::Memory => {
+                debug!("Using in-memory {:?} cache backend", purpose);
+                Arc::new(InMemoryCache::new().with_max_entries(Self::max_entries_for(purpose)))
+            }
+        }
+    }
+
+    /// Maximum number of entries of an in-memory cache for a subsystem, from
+    /// `AUTHGATE_<PURPOSE>_CACHE_MAX_ENTRIES` or `AUTHGATE_CACHE_MAX_ENTRIES`
+    pub fn max_entries_for(purpose: CachePurpose) -> usize {
+        purpose
+            .env_var("CACHE_MAX_ENTRIES")
+            .and_then(|max| max.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_ENTRIES)
+    }
+
+    /// Resolve the backend configured for a subsystem
+    pub fn backend_for(purpose: CachePurpose) -> CacheBackend {
+        let cache_backend = purpose
+            .env_var("CACHE_BACKEND")
+            .unwrap_or_else(|| "memory".to_string());
+
+        match cache_backend.to_lowercase().as_str() {
+            "redis" => CacheBackend::Redis {
+                url: purpose
+                    .env_var("REDIS_URL")
+                    .unwrap_or_else(|| "redis://127.0.0.1:6379".to_string()),
+            },
+            _ => CacheBackend::Memory,
         }
     }
 }
@@ -112,51 +307,336 @@ pub fn extract_jwt_expiration(token: &str) -> Option<Duration> {
     None
 }
 
+/// Key a session is cached under: the hex SHA-256 digest of its token
+///
+/// Keeps live session tokens out of Redis and cache snapshots, so read access
+/// to either doesn't hand out sessions.
+pub fn session_key(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Cached session as stored in a snapshot file
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEntry {
+    /// Cache key of the session, see [`session_key`]
+    key: String,
+    session: SessionResponse,
+    /// Expiry as seconds since the Unix epoch
+    expires_at: u64,
+}
+
+/// Default maximum number of sessions held by an in-memory cache
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+/// A session held by the in-memory cache
+struct CacheEntry {
+    session: SessionResponse,
+    expiry: SystemTime,
+}
+
+/// Sessions of the in-memory cache in least recently used order, indexed by
+/// expiry so expired ones are found without scanning them all
+struct SessionStore {
+    entries: LruCache<String, CacheEntry>,
+    expiries: BTreeSet<(SystemTime, String)>,
+}
+
+impl SessionStore {
+    fn new() -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            expiries: BTreeSet::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.contains(key)
+    }
+
+    /// Look a session up, marking it as the most recently used
+    fn get(&mut self, key: &str) -> Option<&CacheEntry> {
+        self.entries.get(key)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &CacheEntry)> {
+        self.entries.iter()
+    }
+
+    fn put(&mut self, key: String, entry: CacheEntry) {
+        self.expiries.insert((entry.expiry, key.clone()));
+        if let Some(replaced) = self.entries.put(key.clone(), entry) {
+            self.expiries.remove(&(replaced.expiry, key));
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        match self.entries.pop(key) {
+            Some(entry) => {
+                self.expiries.remove(&(entry.expiry, key.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop the session that expired first, if it has expired by `now`
+    fn pop_expired(&mut self, now: SystemTime) -> bool {
+        match self.expiries.first() {
+            Some((expiry, key)) if *expiry <= now => {
+                let key = key.clone();
+                self.remove(&key)
+            }
+            _ => false,
+        }
+    }
+
+    fn pop_lru(&mut self) -> bool {
+        match self.entries.pop_lru() {
+            Some((key, entry)) => {
+                self.expiries.remove(&(entry.expiry, key));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Default interval between sweeps of expired sessions from the in-memory cache
+const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of rate limit counters held before the first sweep of ended windows
+const MIN_COUNTER_SWEEP: usize = 1024;
+
 /// In-memory implementation of SessionCache
+///
+/// Holds at most `max_entries` sessions in least recently used order; once
+/// full, storing a new session evicts the least recently used one. Expired
+/// sessions are swept by a background task every `AUTHGATE_CACHE_CLEANUP_SECS`,
+/// which stops when the cache is dropped.
 pub struct InMemoryCache {
-    cache: Arc<RwLock<HashMap<String, (SessionResponse, SystemTime)>>>,
+    cache: Arc<Mutex<SessionStore>>,
+    counters: Arc<RwLock<HashMap<String, (u64, SystemTime)>>>,
+    /// Counter count at which counters whose window has ended are next swept
+    counter_sweep_at: AtomicUsize,
+    snapshot_path: Option<PathBuf>,
+    max_entries: usize,
+    stats: StatsCounters,
+    cleanup_task: Option<JoinHandle<()>>,
+}
+
+impl Default for InMemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InMemoryCache {
     /// Create a new in-memory cache
     pub fn new() -> Self {
+        Self::from_entries(HashMap::new(), None)
+    }
+
+    /// Build a cache holding `entries`
+    fn from_entries(
+        entries: HashMap<String, (SessionResponse, SystemTime)>,
+        snapshot_path: Option<PathBuf>,
+    ) -> Self {
+        let mut cache = SessionStore::new();
+        for (key, (session, expiry)) in entries {
+            cache.put(key, CacheEntry { session, expiry });
+        }
+        let cache = Arc::new(Mutex::new(cache));
+        let cleanup_task = spawn_cleanup_task(&cache, cleanup_interval());
+
         Self {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache,
+            counters: Arc::new(RwLock::new(HashMap::new())),
+            counter_sweep_at: AtomicUsize::new(MIN_COUNTER_SWEEP),
+            snapshot_path,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            stats: StatsCounters::default(),
+            cleanup_task,
         }
     }
 
-    /// Clean expired entries from the cache
-    async fn clean_expired(&self) {
-        let mut cache = self.cache.write().await;
-        let now = SystemTime::now();
+    /// Set how often expired sessions are swept, `Duration::ZERO` to only drop
+    /// them when the cache is full (`AUTHGATE_CACHE_CLEANUP_SECS`)
+    pub fn with_cleanup_interval(mut self, interval: Duration) -> Self {
+        if let Some(task) = self.cleanup_task.take() {
+            task.abort();
+        }
+        self.cleanup_task = spawn_cleanup_task(&self.cache, interval);
+        self
+    }
+
+    /// Number of sessions held, including expired ones not yet swept
+    pub async fn entry_count(&self) -> usize {
+        self.cache.lock().await.len()
+    }
+
+    /// Set the maximum number of sessions held, `0` for no limit
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Create an in-memory cache persisted to a snapshot file, restoring any
+    /// unexpired sessions from a previous snapshot
+    pub fn with_snapshot(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        let entries = match read_snapshot(&path) {
+            Ok(entries) => {
+                info!(
+                    "Restored {} cached sessions from {}",
+                    entries.len(),
+                    path.display()
+                );
+                entries
+            }
+            Err(AuthGateError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No cache snapshot found at {}", path.display());
+                HashMap::new()
+            }
+            Err(e) => {
+                warn!("Failed to load cache snapshot {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+
+        Self::from_entries(entries, Some(path))
+    }
 
-        // Remove expired entries
-        cache.retain(|_, (_, expiry)| {
-            match expiry.duration_since(now) {
-                Ok(_) => true,   // Not expired
-                Err(_) => false, // Expired
+    /// Periodically write the cache to its snapshot file until the cache is dropped
+    pub fn spawn_snapshot_task(self: &Arc<Self>, interval: Duration) {
+        if self.snapshot_path.is_none() {
+            return;
+        }
+        if tokio::runtime::Handle::try_current().is_err() {
+            warn!("No async runtime available, cache snapshots will only be written on shutdown");
+            return;
+        }
+
+        let cache: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                if let Err(e) = cache.persist().await {
+                    warn!("Failed to write cache snapshot: {}", e);
+                }
             }
         });
     }
+
+    /// Write all unexpired sessions to `path`, returning the number written
+    pub async fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<usize, AuthGateError> {
+        let path = path.as_ref();
+        let now = SystemTime::now();
+
+        let entries: Vec<SnapshotEntry> = {
+            let cache = self.cache.lock().await;
+            cache
+                .iter()
+                .filter(|(_, entry)| entry.expiry > now)
+                .map(|(key, entry)| SnapshotEntry {
+                    key: key.clone(),
+                    session: entry.session.clone(),
+                    expires_at: entry
+                        .expiry
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                })
+                .collect()
+        };
+
+        let json = serde_json::to_vec(&entries)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated snapshot
+        let tmp_path = path.with_extension("tmp");
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The snapshot contains user sessions, so keep it private to this user
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp_path).await?;
+        file.write_all(&json).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+
+        debug!(
+            "Wrote {} cached sessions to {}",
+            entries.len(),
+            path.display()
+        );
+        Ok(entries.len())
+    }
+
+    /// Load unexpired sessions from the snapshot at `path`, returning the number loaded
+    pub async fn load_snapshot(&self, path: impl AsRef<Path>) -> Result<usize, AuthGateError> {
+        let entries = read_snapshot(path.as_ref())?;
+        let count = entries.len();
+
+        let mut cache = self.cache.lock().await;
+        for (key, (session, expiry)) in entries {
+            cache.put(key, CacheEntry { session, expiry });
+        }
+
+        Ok(count)
+    }
+
+    /// Make room for a new session once the cache is full, dropping expired
+    /// sessions first and then the least recently used ones
+    fn evict_for_insert(&self, cache: &mut SessionStore, key: &str) {
+        if self.max_entries == 0 || cache.contains(key) {
+            return;
+        }
+
+        let now = SystemTime::now();
+        while cache.len() >= self.max_entries {
+            if cache.pop_expired(now) {
+                continue;
+            }
+            if !cache.pop_lru() {
+                break;
+            }
+            debug!("Session cache is full, evicting the least recently used session");
+        }
+    }
 }
 
 #[async_trait]
 impl SessionCache for InMemoryCache {
     async fn get(&self, token: &str) -> Option<SessionResponse> {
-        // Clean expired entries first
-        self.clean_expired().await;
+        self.get_with_ttl(token).await.map(|(session, _)| session)
+    }
 
-        // Try to get the session
-        let cache = self.cache.read().await;
-        if let Some((session, expiry)) = cache.get(token) {
+    async fn get_with_ttl(&self, token: &str) -> Option<(SessionResponse, Option<Duration>)> {
+        // Expired entries are left to the cleanup task; looking an entry up
+        // marks it as the most recently used
+        let mut cache = self.cache.lock().await;
+        if let Some(entry) = cache.get(&session_key(token)) {
             // Check if the session is still valid
-            if let Ok(_) = expiry.duration_since(SystemTime::now()) {
+            if let Ok(remaining) = entry.expiry.duration_since(SystemTime::now()) {
                 debug!("Cache hit for token");
-                return Some(session.clone());
+                self.stats.record_lookup(true);
+                return Some((entry.session.clone(), Some(remaining)));
             }
         }
 
         debug!("Cache miss for token");
+        self.stats.record_lookup(false);
         None
     }
 
@@ -167,26 +647,146 @@ impl SessionCache for InMemoryCache {
         ttl: Duration,
     ) -> Result<(), AuthGateError> {
         let expiry = SystemTime::now() + ttl;
+        let key = session_key(token);
 
-        let mut cache = self.cache.write().await;
-        cache.insert(token.to_string(), (session, expiry));
+        let mut cache = self.cache.lock().await;
+        self.evict_for_insert(&mut cache, &key);
+        cache.put(key, CacheEntry { session, expiry });
+        drop(cache);
+        self.stats.record_set();
 
         debug!("Cached session with TTL of {} seconds", ttl.as_secs());
         Ok(())
     }
 
     async fn remove(&self, token: &str) -> Result<(), AuthGateError> {
-        let mut cache = self.cache.write().await;
-        cache.remove(token);
+        let mut cache = self.cache.lock().await;
+        cache.remove(&session_key(token));
 
         debug!("Removed session from cache");
         Ok(())
     }
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, AuthGateError> {
+        let now = SystemTime::now();
+        let mut counters = self.counters.write().await;
+
+        // Drop counters whose window has ended once the map has doubled since
+        // the last sweep, so the sweep's cost is spread over many increments
+        if counters.len() >= self.counter_sweep_at.load(Ordering::Relaxed) {
+            counters.retain(|_, (_, reset_at)| *reset_at > now);
+            self.counter_sweep_at.store(
+                (counters.len() * 2).max(MIN_COUNTER_SWEEP),
+                Ordering::Relaxed,
+            );
+        }
+
+        let (count, reset_at) = counters.entry(key.to_string()).or_insert((0, now + window));
+        // A counter whose window has ended but wasn't swept yet starts over
+        if *reset_at <= now {
+            *count = 0;
+            *reset_at = now + window;
+        }
+        *count += 1;
+
+        Ok(*count)
+    }
+
+    async fn persist(&self) -> Result<(), AuthGateError> {
+        if let Some(path) = &self.snapshot_path {
+            self.save_snapshot(path).await?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.stats.stats()
+    }
+}
+
+impl Drop for InMemoryCache {
+    fn drop(&mut self) {
+        if let Some(task) = self.cleanup_task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Interval between expired session sweeps, from `AUTHGATE_CACHE_CLEANUP_SECS`
+fn cleanup_interval() -> Duration {
+    env::var("AUTHGATE_CACHE_CLEANUP_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CLEANUP_INTERVAL)
+}
+
+/// Periodically drop expired sessions until the cache is gone
+fn spawn_cleanup_task(
+    cache: &Arc<Mutex<SessionStore>>,
+    interval: Duration,
+) -> Option<JoinHandle<()>> {
+    if interval.is_zero() {
+        return None;
+    }
+    if tokio::runtime::Handle::try_current().is_err() {
+        debug!(
+            "No async runtime available, expired sessions are only dropped when the cache is full"
+        );
+        return None;
+    }
+
+    let cache = Arc::downgrade(cache);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            let Some(cache) = cache.upgrade() else {
+                break;
+            };
+
+            let mut cache = cache.lock().await;
+            let now = SystemTime::now();
+            let mut swept = 0;
+            while cache.pop_expired(now) {
+                swept += 1;
+            }
+            if swept > 0 {
+                debug!("Swept {} expired sessions from the cache", swept);
+            }
+        }
+    }))
+}
+
+/// Read a snapshot file, discarding sessions that have expired since it was written
+fn read_snapshot(
+    path: &Path,
+) -> Result<HashMap<String, (SessionResponse, SystemTime)>, AuthGateError> {
+    let data = std::fs::read(path)?;
+    let entries: Vec<SnapshotEntry> = serde_json::from_slice(&data)?;
+    let now = SystemTime::now();
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let expiry = UNIX_EPOCH + Duration::from_secs(entry.expires_at);
+            (expiry > now).then_some((entry.key, (entry.session, expiry)))
+        })
+        .collect())
 }
 
 /// Redis implementation of SessionCache
+///
+/// All operations share one multiplexed connection, which reconnects by itself
+/// when it drops.
 pub struct RedisCache {
     client: redis::Client,
+    connection: RwLock<Option<ConnectionManager>>,
+    closed: AtomicBool,
+    stats: StatsCounters,
 }
 
 impl RedisCache {
@@ -194,44 +794,110 @@ impl RedisCache {
     pub fn new(redis_url: &str) -> Self {
         Self {
             client: redis::Client::open(redis_url).expect("Failed to create Redis client"),
+            connection: RwLock::new(None),
+            closed: AtomicBool::new(false),
+            stats: StatsCounters::default(),
         }
     }
+
+    /// The shared connection, established on first use
+    ///
+    /// If Redis can't be reached for the first connection, the next call tries
+    /// again. Once established, dropped connections are re-established by the
+    /// connection manager in the background, until the cache is closed.
+    async fn connection(&self) -> redis::RedisResult<ConnectionManager> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(cache_closed());
+        }
+        if let Some(connection) = self.connection.read().await.as_ref() {
+            return Ok(connection.clone());
+        }
+
+        let mut slot = self.connection.write().await;
+        if self.closed.load(Ordering::Acquire) {
+            return Err(cache_closed());
+        }
+        if let Some(connection) = slot.as_ref() {
+            return Ok(connection.clone());
+        }
+        let connection = ConnectionManager::new(self.client.clone()).await?;
+        *slot = Some(connection.clone());
+        Ok(connection)
+    }
+}
+
+/// Error for calls made after the Redis cache was closed
+fn cache_closed() -> redis::RedisError {
+    redis::RedisError::from((redis::ErrorKind::IoError, "Redis cache is closed"))
 }
 
 #[async_trait]
 impl SessionCache for RedisCache {
     async fn get(&self, token: &str) -> Option<SessionResponse> {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
+        self.get_with_ttl(token).await.map(|(session, _)| session)
+    }
+
+    async fn get_with_ttl(&self, token: &str) -> Option<(SessionResponse, Option<Duration>)> {
+        match self.try_get_with_ttl(token).await {
+            Ok(result) => result,
             Err(e) => {
-                error!("Failed to connect to Redis: {}", e);
-                return None;
+                error!("{}", e);
+                None
             }
-        };
+        }
+    }
+
+    async fn try_get_with_ttl(
+        &self,
+        token: &str,
+    ) -> Result<Option<(SessionResponse, Option<Duration>)>, AuthGateError> {
+        let mut conn = self
+            .connection()
+            .await
+            .map_err(|e| AuthGateError::CacheError(format!("Failed to connect to Redis: {}", e)))?;
 
-        // Try to get the session from Redis
-        let key = format!("authgate:session:{}", token);
-        let result: redis::RedisResult<String> =
-            redis::cmd("GET").arg(&key).query_async(&mut conn).await;
+        // Fetch the session and its remaining TTL in a single round trip
+        let key = format!("authgate:session:{}", session_key(token));
+        let result: redis::RedisResult<(Option<String>, i64)> = redis::pipe()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("TTL")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await;
 
         match result {
-            Ok(json) => match serde_json::from_str(&json) {
+            Ok((Some(json), ttl)) => match serde_json::from_str(&json) {
                 Ok(session) => {
                     debug!("Cache hit for token in Redis");
-                    Some(session)
+                    self.stats.record_lookup(true);
+                    // TTL returns -1/-2 when the key has no expiry or is gone
+                    let ttl = (ttl > 0).then(|| Duration::from_secs(ttl as u64));
+                    Ok(Some((session, ttl)))
                 }
                 Err(e) => {
-                    error!("Failed to deserialize session from Redis: {}", e);
-                    None
+                    // An unreadable entry, e.g. written by another version, is
+                    // a miss rather than an outage, and is dropped so the
+                    // session is cached afresh
+                    warn!("Failed to deserialize session from Redis: {}", e);
+                    self.stats.record_lookup(false);
+                    let result: redis::RedisResult<()> =
+                        redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
+                    if let Err(e) = result {
+                        warn!("Failed to remove unreadable session from Redis: {}", e);
+                    }
+                    Ok(None)
                 }
             },
-            Err(e) => {
-                if e.kind() != redis::ErrorKind::TypeError {
-                    // Only log if it's not a type error (key not found)
-                    debug!("Cache miss for token in Redis: {}", e);
-                }
-                None
+            Ok((None, _)) => {
+                debug!("Cache miss for token in Redis");
+                self.stats.record_lookup(false);
+                Ok(None)
             }
+            Err(e) => Err(AuthGateError::CacheError(format!(
+                "Failed to read session from Redis: {}",
+                e
+            ))),
         }
     }
 
@@ -241,7 +907,7 @@ impl SessionCache for RedisCache {
         session: SessionResponse,
         ttl: Duration,
     ) -> Result<(), AuthGateError> {
-        let mut conn = match self.client.get_async_connection().await {
+        let mut conn = match self.connection().await {
             Ok(conn) => conn,
             Err(e) => {
                 return Err(AuthGateError::ConfigError(format!(
@@ -260,7 +926,7 @@ impl SessionCache for RedisCache {
         };
 
         // Store the session in Redis with expiration
-        let key = format!("authgate:session:{}", token);
+        let key = format!("authgate:session:{}", session_key(token));
         let result: redis::RedisResult<()> = redis::cmd("SETEX")
             .arg(&key)
             .arg(ttl.as_secs())
@@ -274,6 +940,7 @@ impl SessionCache for RedisCache {
                     "Cached session in Redis with TTL of {} seconds",
                     ttl.as_secs()
                 );
+                self.stats.record_set();
                 Ok(())
             }
             Err(e) => {
@@ -287,7 +954,7 @@ impl SessionCache for RedisCache {
     }
 
     async fn remove(&self, token: &str) -> Result<(), AuthGateError> {
-        let mut conn = match self.client.get_async_connection().await {
+        let mut conn = match self.connection().await {
             Ok(conn) => conn,
             Err(e) => {
                 return Err(AuthGateError::ConfigError(format!(
@@ -298,7 +965,7 @@ impl SessionCache for RedisCache {
         };
 
         // Remove the session from Redis
-        let key = format!("authgate:session:{}", token);
+        let key = format!("authgate:session:{}", session_key(token));
         let result: redis::RedisResult<()> =
             redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
 
@@ -316,4 +983,64 @@ impl SessionCache for RedisCache {
             }
         }
     }
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, AuthGateError> {
+        let mut conn = match self.connection().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                return Err(AuthGateError::CacheError(format!(
+                    "Failed to connect to Redis: {}",
+                    e
+                )));
+            }
+        };
+
+        let key = format!("authgate:counter:{}", key);
+        // Creating the counter with its expiry and incrementing it run as one
+        // transaction, so a failure between them can't leave a counter that
+        // never expires
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(window.as_secs().max(1))
+            .ignore()
+            .cmd("INCR")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to increment counter in Redis: {}", e);
+                AuthGateError::CacheError(format!("Failed to increment counter in Redis: {}", e))
+            })?;
+
+        Ok(count)
+    }
+
+    async fn close(&self) -> Result<(), AuthGateError> {
+        // Later calls fail instead of reconnecting; the connection closes once
+        // requests still holding a clone of it finish
+        self.closed.store(true, Ordering::Release);
+        self.connection.write().await.take();
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), AuthGateError> {
+        let mut conn = self
+            .connection()
+            .await
+            .map_err(|e| AuthGateError::CacheError(format!("Failed to connect to Redis: {}", e)))?;
+
+        let result: redis::RedisResult<String> = redis::cmd("PING").query_async(&mut conn).await;
+        result
+            .map(|_| ())
+            .map_err(|e| AuthGateError::CacheError(format!("Failed to ping Redis: {}", e)))
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.stats.stats()
+    }
 }
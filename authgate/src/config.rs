@@ -1,18 +1,53 @@
-use crate::config_provider::{ConfigProviderFactory, PostgresProvider};
-use crate::types::{AuthGateError, Config};
+use crate::config_provider::{
+    apply_default_require, parse_require_exprs, validate_config, ConfigProviderFactory,
+    PostgresProvider, RouteStore, RoutesVersion,
+};
+use crate::types::{AuthGateError, Config, Route};
 use anyhow::Result;
-use std::sync::Arc;
+use std::env;
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 /// Default cookie name if not specified in config
 pub const DEFAULT_COOKIE_NAME: &str = "session";
 
+/// Resolve the session cookie name from the configured `cookie_name`
+///
+/// `AUTHGATE_SESSION_COOKIE` overrides the configuration, so forward auth and
+/// the Admin API always read the same cookie.
+pub fn session_cookie_name(configured: Option<&str>) -> String {
+    env::var("AUTHGATE_SESSION_COOKIE")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .or_else(|| configured.map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_COOKIE_NAME.to_string())
+}
+
+/// A single route change made through the provider
+#[derive(Debug, Clone)]
+pub enum RouteChange {
+    /// A route was created, or updated in place when its id is already loaded
+    Upsert(Box<Route>),
+    /// The route with this id was deleted
+    Delete(i32),
+}
+
 /// ConfigManager handles loading and reloading of configuration
 pub struct ConfigManager {
     config: Arc<RwLock<Config>>,
     config_provider: Arc<dyn crate::config_provider::ConfigProvider>,
     provider_factory: Option<ConfigProviderFactory>,
+    /// Database the Admin API writes routes to
+    route_store: Option<Arc<dyn RouteStore>>,
+    /// Provider route version the loaded configuration corresponds to
+    routes_version: Mutex<Option<RoutesVersion>>,
+}
+
+impl Default for ConfigManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConfigManager {
@@ -20,38 +55,201 @@ impl ConfigManager {
     pub fn new() -> Self {
         // Create a config provider based on environment
         let (config_provider, provider_factory) = ConfigProviderFactory::create();
+        let route_store = provider_factory.get_route_store();
 
         Self {
-            config: Arc::new(RwLock::new(Config {
-                auth: crate::types::AuthConfig {
-                    session_url: String::new(),
-                    login_redirect: String::new(),
-                },
-                routes: Vec::new(),
-                cookie_name: None,
-            })),
+            config: Arc::new(RwLock::new(Config::default())),
             config_provider,
             provider_factory: Some(provider_factory),
+            route_store,
+            routes_version: Mutex::new(None),
         }
     }
 
+    /// Create a new ConfigManager backed by an explicit provider
+    pub fn with_provider(config_provider: Arc<dyn crate::config_provider::ConfigProvider>) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(Config::default())),
+            config_provider,
+            provider_factory: None,
+            route_store: None,
+            routes_version: Mutex::new(None),
+        }
+    }
+
+    /// Use `route_store` for routes written through the Admin API
+    pub fn with_route_store(mut self, route_store: Arc<dyn RouteStore>) -> Self {
+        self.route_store = Some(route_store);
+        self
+    }
+
     /// Load configuration from the provider
     pub async fn load_config(&self) -> Result<(), AuthGateError> {
-        let config = self.config_provider.load_config().await?;
+        // Taken before loading, so a change made meanwhile is picked up next time
+        let routes_version = self.current_routes_version().await;
+        let mut config = self.config_provider.load_config().await?;
+        parse_require_exprs(&mut config)?;
+
+        let mut writable_config = self.config.write().await;
 
         // Set default cookie name if not specified
         let config = Config {
             cookie_name: config.cookie_name.or(Some(DEFAULT_COOKIE_NAME.to_string())),
+            version: writable_config.version + 1,
             ..config
         };
 
-        let mut writable_config = self.config.write().await;
         *writable_config = config;
+        *self.routes_version.lock().unwrap() = routes_version;
 
         info!("Configuration loaded successfully");
         Ok(())
     }
 
+    /// Reload the configuration only if the provider's routes changed since the last load
+    ///
+    /// Providers that don't report a routes version are always reloaded. Returns
+    /// whether a reload happened.
+    pub async fn reload_if_changed(&self) -> Result<bool, AuthGateError> {
+        let current = self.current_routes_version().await;
+        if current.is_some() && current == *self.routes_version.lock().unwrap() {
+            debug!("Routes unchanged, skipping configuration reload");
+            return Ok(false);
+        }
+
+        self.load_config().await?;
+        Ok(true)
+    }
+
+    /// Apply a single route change to the loaded configuration without a full reload
+    ///
+    /// Falls back to a full reload when the provider's routes version shows changes
+    /// beyond this one, e.g. made by another replica, or when the change can't be
+    /// applied in place.
+    pub async fn apply_route_change(&self, change: RouteChange) -> Result<(), AuthGateError> {
+        let current = self.current_routes_version().await;
+        let previous = self.routes_version.lock().unwrap().clone();
+
+        if let (Some(previous), Some(current)) = (&previous, &current) {
+            let expected_count = match &change {
+                RouteChange::Upsert(route) => {
+                    if self.has_route(route).await {
+                        previous.count
+                    } else {
+                        previous.count + 1
+                    }
+                }
+                RouteChange::Delete(_) => previous.count.saturating_sub(1),
+            };
+            let upsert = matches!(change, RouteChange::Upsert(_));
+            if current.count != expected_count
+                || !self.only_write_since(previous, current, upsert).await
+            {
+                info!("Routes changed outside this instance, reloading the full configuration");
+                return self.load_config().await;
+            }
+        }
+
+        let mut writable_config = self.config.write().await;
+        let mut config = writable_config.clone();
+        if !apply_change(&mut config.routes, &change) {
+            drop(writable_config);
+            debug!("Route change can't be applied in place, reloading the full configuration");
+            return self.load_config().await;
+        }
+
+        if let Err(e) = apply_default_require(&mut config)
+            .and_then(|_| validate_config(&config))
+            .and_then(|_| parse_require_exprs(&mut config))
+        {
+            warn!("Route change leaves an invalid configuration: {}", e);
+            drop(writable_config);
+            return self.load_config().await;
+        }
+
+        config.version = writable_config.version + 1;
+        *writable_config = config;
+        *self.routes_version.lock().unwrap() = current;
+
+        info!("Applied route change without a full reload");
+        Ok(())
+    }
+
+    /// Whether `current` differs from `previous` by a single route write when
+    /// `upsert` is set, or by no write at all for a deletion
+    async fn only_write_since(
+        &self,
+        previous: &RoutesVersion,
+        current: &RoutesVersion,
+        upsert: bool,
+    ) -> bool {
+        // A write moves the latest update forward and a deletion never does
+        let tracked = previous.updated_at.is_some() || current.updated_at.is_some();
+        if tracked && (current.updated_at > previous.updated_at) != upsert {
+            return false;
+        }
+
+        // A write made elsewhere in between can hide behind this one's timestamp
+        let Some(since) = &previous.updated_at else {
+            return true;
+        };
+        match self.config_provider.routes_updated_since(since).await {
+            Ok(Some(updated)) => updated == usize::from(upsert),
+            Ok(None) => true,
+            Err(e) => {
+                warn!(
+                    "Failed to read the routes updated since the last load: {}",
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Whether a route with the same id is loaded
+    async fn has_route(&self, route: &Route) -> bool {
+        self.config
+            .read()
+            .await
+            .routes
+            .iter()
+            .any(|existing| existing.id.is_some() && existing.id == route.id)
+    }
+
+    /// The provider's current routes version, if it reports one
+    async fn current_routes_version(&self) -> Option<RoutesVersion> {
+        match self.config_provider.routes_version().await {
+            Ok(version) => version,
+            Err(e) => {
+                warn!("Failed to read the routes version: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Check that the loaded configuration has routes, as a startup safety check
+    ///
+    /// Requests matching no route are allowed, so a configuration without any
+    /// routes lets every request through. That is logged as an error, or
+    /// refused when `refuse` is set (see [`refuse_empty_config`]).
+    pub async fn check_not_empty(&self, refuse: bool) -> Result<(), AuthGateError> {
+        if !self.config.read().await.routes.is_empty() {
+            return Ok(());
+        }
+
+        if refuse {
+            return Err(AuthGateError::ConfigError(
+                "The configuration has no routes, so every request would be allowed".to_string(),
+            ));
+        }
+
+        error!(
+            "The configuration has no routes, so every request is allowed. \
+             Set AUTHGATE_REFUSE_EMPTY_CONFIG=true to refuse to start instead"
+        );
+        Ok(())
+    }
+
     /// Get a clone of the current configuration
     pub async fn get_config(&self) -> Config {
         self.config.read().await.clone()
@@ -65,14 +263,31 @@ impl ConfigManager {
         config.clone()
     }
 
-    /// Get the cookie name from configuration
+    /// Get the session cookie name, see [`session_cookie_name`]
     pub async fn get_cookie_name(&self) -> String {
-        self.config
-            .read()
-            .await
-            .cookie_name
-            .clone()
-            .unwrap_or_else(|| DEFAULT_COOKIE_NAME.to_string())
+        session_cookie_name(self.config.read().await.cookie_name.as_deref())
+    }
+
+    /// Warn when `AUTHGATE_SESSION_COOKIE` overrides a different configured
+    /// cookie name, as a startup check
+    ///
+    /// Returns whether the two conflict.
+    pub async fn check_cookie_name(&self) -> bool {
+        let configured = self.config.read().await.cookie_name.clone();
+        let Ok(overridden) = env::var("AUTHGATE_SESSION_COOKIE") else {
+            return false;
+        };
+        match configured {
+            Some(configured) if !overridden.is_empty() && overridden != configured => {
+                warn!(
+                    "AUTHGATE_SESSION_COOKIE={} overrides the configured cookie_name {} \
+                     for both forward auth and the Admin API",
+                    overridden, configured
+                );
+                true
+            }
+            _ => false,
+        }
     }
 
     /// Get the PostgreSQL provider if available
@@ -88,31 +303,173 @@ impl ConfigManager {
         None
     }
 
+    /// Get the database holding routes managed by the Admin API, if available
+    pub fn get_route_store(&self) -> Option<Arc<dyn RouteStore>> {
+        let route_store = self.route_store.clone();
+
+        if route_store.is_none() {
+            debug!("Route store not available");
+        }
+        route_store
+    }
+
+    /// Close the config provider's database connections, which the route store shares
+    pub async fn close(&self) {
+        self.config_provider.close().await;
+    }
+
     /// Get a reference to the config for sharing
     pub fn get_config_ref(&self) -> Arc<RwLock<Config>> {
         self.config.clone()
     }
 }
 
+/// JSON Schema of the configuration file, for validating files before deploying them
+pub fn config_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Config)).expect("Config schema is valid JSON")
+}
+
+/// Whether to refuse to start with a configuration that has no routes, from
+/// `AUTHGATE_REFUSE_EMPTY_CONFIG` (default: `false`, only log an error)
+pub fn refuse_empty_config() -> bool {
+    std::env::var("AUTHGATE_REFUSE_EMPTY_CONFIG")
+        .unwrap_or_else(|_| "false".to_string())
+        .to_lowercase()
+        == "true"
+}
+
+/// Apply a route change to a route list, returning false if it doesn't fit
+fn apply_change(routes: &mut Vec<Route>, change: &RouteChange) -> bool {
+    match change {
+        RouteChange::Upsert(route) => {
+            if let Some(existing) = routes
+                .iter_mut()
+                .find(|existing| existing.id.is_some() && existing.id == route.id)
+            {
+                *existing = Route::clone(route);
+                return true;
+            }
+
+            // A route shadowed by another provider's route is dropped on full loads
+            let duplicate = routes.iter().any(|existing| {
+                existing.host == route.host
+                    && existing.path == route.path
+                    && existing.port == route.port
+            });
+            if duplicate || route.id.is_none() {
+                return false;
+            }
+
+            routes.push(Route::clone(route));
+            true
+        }
+        RouteChange::Delete(id) => {
+            let len = routes.len();
+            routes.retain(|route| route.id != Some(*id));
+            routes.len() < len
+        }
+    }
+}
+
+/// How often providers that can't be watched are polled for changes
+#[cfg(feature = "config_reload")]
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long a config file must stay unchanged before it is reloaded, since
+/// editors often write a file several times in quick succession
+#[cfg(feature = "config_reload")]
+const CONFIG_WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
+
 /// Setup config watcher for reloading
+///
+/// File providers are reloaded as soon as their file changes; other providers,
+/// such as PostgreSQL, are polled. A configuration that fails to load or
+/// validate is logged and the previous one is kept.
 #[cfg(feature = "config_reload")]
 pub async fn setup_config_watcher(config_manager: Arc<ConfigManager>) -> Result<(), AuthGateError> {
-    use std::time::Duration;
-
-    // For now, we'll just periodically reload the config
-    // In the future, this could be enhanced to watch for changes in different ways
-    // depending on the config provider type
+    match config_manager.config_provider.watch_path() {
+        Some(path) => watch_config_file(config_manager, path),
+        None => {
+            poll_config(config_manager);
+            Ok(())
+        }
+    }
+}
 
+/// Periodically reload the configuration if the provider reports changes
+#[cfg(feature = "config_reload")]
+fn poll_config(config_manager: Arc<ConfigManager>) {
     tokio::spawn(async move {
         loop {
             // Sleep for a while before checking for changes
-            tokio::time::sleep(Duration::from_secs(60)).await;
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
 
             info!("Checking for configuration changes...");
-            if let Err(e) = config_manager.load_config().await {
-                error!("Failed to reload config: {}", e);
-            } else {
-                info!("Config reloaded successfully");
+            match config_manager.reload_if_changed().await {
+                Ok(true) => info!("Config reloaded successfully"),
+                Ok(false) => {}
+                Err(e) => error!("Failed to reload config: {}", e),
+            }
+        }
+    });
+}
+
+/// Reload the configuration whenever its file changes
+#[cfg(feature = "config_reload")]
+fn watch_config_file(
+    config_manager: Arc<ConfigManager>,
+    path: std::path::PathBuf,
+) -> Result<(), AuthGateError> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::path::Path;
+
+    // Watch the directory rather than the file itself, since editors often
+    // replace a file by renaming a new one over it
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let changed = !matches!(event.kind, EventKind::Access(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|changed| changed.file_name() == file_name.as_deref());
+                if changed {
+                    let _ = tx.send(());
+                }
+            }
+            Err(e) => warn!("Config file watch error: {}", e),
+        })
+        .map_err(|e| AuthGateError::ConfigError(format!("Failed to watch config file: {}", e)))?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| {
+            AuthGateError::ConfigError(format!(
+                "Failed to watch config directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+    info!("Watching {} for configuration changes", path.display());
+
+    tokio::spawn(async move {
+        // The watcher stops when dropped, so it lives as long as this task
+        let _watcher = watcher;
+
+        while rx.recv().await.is_some() {
+            // Wait for the writes to settle
+            while let Ok(Some(())) = tokio::time::timeout(CONFIG_WATCH_DEBOUNCE, rx.recv()).await {}
+
+            info!("Configuration file changed, reloading");
+            match config_manager.load_config().await {
+                Ok(()) => info!("Config reloaded successfully"),
+                Err(e) => error!("Failed to reload config, keeping the previous one: {}", e),
             }
         }
     });
@@ -1,6 +1,5 @@
 use crate::types::{AuthGateError, Config, Route};
 use async_trait::async_trait;
-use std::sync::Arc;
 
 /// Mock implementation of PostgresProvider for testing
 #[derive(Clone)]
@@ -8,6 +7,12 @@ pub struct MockPostgresProvider {
     routes: Vec<Route>,
 }
 
+impl Default for MockPostgresProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MockPostgresProvider {
     /// Create a new mock provider
     pub fn new() -> Self {
@@ -79,9 +84,11 @@ impl crate::config_provider::ConfigProvider for MockPostgresProvider {
             auth: crate::types::AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: self.routes.clone(),
             cookie_name: Some("session".to_string()),
+            ..Default::default()
         })
     }
 }
@@ -0,0 +1,272 @@
+use crate::authorizer::grants;
+use crate::types::{AuthGateError, SessionResponse};
+
+/// Deepest nesting of parentheses and `NOT`s an expression may have
+pub const MAX_EXPR_DEPTH: usize = 32;
+
+/// Most predicates, operators and parentheses an expression may have, which
+/// also bounds how deep chains of `AND` and `OR` nest
+pub const MAX_EXPR_TOKENS: usize = 256;
+
+/// Boolean requirement expression over session predicates
+///
+/// Written in a route's `require.expr`, for example
+/// `(role:admin OR team:ops) AND NOT role:contractor`. Supported predicates:
+///
+/// - `role:<role>`: the user has the role
+/// - `permission:<permission>`: the user has the permission
+/// - `team:<id or name>`: the user is a member of the team
+/// - `scope:<resource_type>:<action>[:<resource_id>]`: one of the user's teams
+///   grants the scope
+///
+/// `NOT` binds tighter than `AND`, which binds tighter than `OR`. Operators are
+/// case-insensitive and parentheses group subexpressions. Expressions are
+/// limited to [`MAX_EXPR_TOKENS`] tokens nested at most [`MAX_EXPR_DEPTH`] deep,
+/// so a runaway expression can't exhaust the stack while it's evaluated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequireExpr {
+    Role(String),
+    Permission(String),
+    Team(String),
+    Scope {
+        resource_type: String,
+        action: String,
+        resource_id: Option<String>,
+    },
+    Not(Box<RequireExpr>),
+    And(Box<RequireExpr>, Box<RequireExpr>),
+    Or(Box<RequireExpr>, Box<RequireExpr>),
+}
+
+impl RequireExpr {
+    /// Parse an expression, reporting the first syntax error
+    pub fn parse(input: &str) -> Result<Self, AuthGateError> {
+        let tokens = tokenize(input);
+        if tokens.is_empty() {
+            return Err(expr_error(input, "expression is empty"));
+        }
+        if tokens.len() > MAX_EXPR_TOKENS {
+            return Err(expr_error(
+                input,
+                &format!("expression has more than {} tokens", MAX_EXPR_TOKENS),
+            ));
+        }
+
+        let mut parser = Parser {
+            input,
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(expr_error(input, &format!("unexpected `{}`", token))),
+        }
+    }
+
+    /// Evaluate the expression against a session
+    ///
+    /// With `nested`, the route's `nested_groups`, roles and permissions match
+    /// like in the rest of the route's requirements: holding a group also
+    /// grants its ancestors.
+    pub fn evaluate(&self, session: &SessionResponse, nested: bool) -> bool {
+        let user = &session.user;
+        match self {
+            RequireExpr::Role(role) => user.roles.iter().any(|held| grants(held, role, nested)),
+            RequireExpr::Permission(permission) => user
+                .permissions
+                .iter()
+                .any(|held| grants(held, permission, nested)),
+            RequireExpr::Team(team) => user
+                .teams
+                .iter()
+                .any(|user_team| &user_team.id == team || &user_team.name == team),
+            RequireExpr::Scope {
+                resource_type,
+                action,
+                resource_id,
+            } => user
+                .teams
+                .iter()
+                .flat_map(|team| team.scopes.iter())
+                .any(|scope| {
+                    &scope.resource_type == resource_type
+                        && scope.covers_action(action)
+                        && resource_id
+                            .as_ref()
+                            .is_none_or(|id| scope.covers_resource(id))
+                }),
+            RequireExpr::Not(expr) => !expr.evaluate(session, nested),
+            RequireExpr::And(left, right) => {
+                left.evaluate(session, nested) && right.evaluate(session, nested)
+            }
+            RequireExpr::Or(left, right) => {
+                left.evaluate(session, nested) || right.evaluate(session, nested)
+            }
+        }
+    }
+}
+
+/// Split an expression into parentheses and whitespace-separated words
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+
+    for c in input.chars() {
+        if c == '(' || c == ')' || c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    tokens
+}
+
+/// Recursive descent parser over expression tokens
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<String>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<RequireExpr, AuthGateError> {
+        let mut expr = self.parse_and()?;
+        while self.peek_keyword("OR") {
+            self.pos += 1;
+            expr = RequireExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<RequireExpr, AuthGateError> {
+        let mut expr = self.parse_not()?;
+        while self.peek_keyword("AND") {
+            self.pos += 1;
+            expr = RequireExpr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    /// Enter a nested subexpression, failing once nested too deep
+    fn descend(&mut self) -> Result<(), AuthGateError> {
+        self.depth += 1;
+        if self.depth > MAX_EXPR_DEPTH {
+            return Err(expr_error(
+                self.input,
+                &format!(
+                    "expression is nested more than {} levels deep",
+                    MAX_EXPR_DEPTH
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_not(&mut self) -> Result<RequireExpr, AuthGateError> {
+        if self.peek_keyword("NOT") {
+            self.pos += 1;
+            self.descend()?;
+            let expr = RequireExpr::Not(Box::new(self.parse_not()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<RequireExpr, AuthGateError> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(expr_error(self.input, "unexpected end of expression")),
+        };
+
+        if token == "(" {
+            self.descend()?;
+            let expr = self.parse_or()?;
+            self.depth -= 1;
+            return match self.next() {
+                Some(token) if token == ")" => Ok(expr),
+                _ => Err(expr_error(self.input, "missing `)`")),
+            };
+        }
+
+        parse_predicate(self.input, &token)
+    }
+}
+
+/// Parse a `kind:value` predicate
+fn parse_predicate(input: &str, token: &str) -> Result<RequireExpr, AuthGateError> {
+    let (kind, value) = match token.split_once(':') {
+        Some((kind, value)) if !value.is_empty() => (kind, value),
+        _ => {
+            return Err(expr_error(
+                input,
+                &format!("expected a predicate, found `{}`", token),
+            ))
+        }
+    };
+
+    match kind {
+        "role" => Ok(RequireExpr::Role(value.to_string())),
+        "permission" => Ok(RequireExpr::Permission(value.to_string())),
+        "team" => Ok(RequireExpr::Team(value.to_string())),
+        "scope" => {
+            let parts: Vec<&str> = value.split(':').collect();
+            match parts.as_slice() {
+                [resource_type, action] | [resource_type, action, _]
+                    if parts.iter().all(|part| !part.is_empty()) =>
+                {
+                    Ok(RequireExpr::Scope {
+                        resource_type: resource_type.to_string(),
+                        action: action.to_string(),
+                        resource_id: parts.get(2).map(|id| id.to_string()),
+                    })
+                }
+                _ => Err(expr_error(
+                    input,
+                    &format!(
+                        "scope predicate `{}` must be scope:<resource_type>:<action>[:<resource_id>]",
+                        token
+                    ),
+                )),
+            }
+        }
+        _ => Err(expr_error(
+            input,
+            &format!("unknown predicate `{}` in `{}`", kind, token),
+        )),
+    }
+}
+
+fn expr_error(input: &str, message: &str) -> AuthGateError {
+    AuthGateError::ConfigError(format!(
+        "Invalid require expression `{}`: {}",
+        input, message
+    ))
+}
@@ -0,0 +1,239 @@
+use crate::proxy::AppState;
+use crate::types::AuthGateError;
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::Response,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Default timeout for the session endpoint readiness probe
+const DEFAULT_READY_CHECK_TIMEOUT_MS: u64 = 2000;
+
+/// Default time a backend probe result is reused for
+const DEFAULT_READY_CHECK_INTERVAL_MS: u64 = 5000;
+
+/// Last probe result of a backend
+struct HealthEntry {
+    healthy: bool,
+    checked_at: Instant,
+    refreshing: bool,
+}
+
+/// Cache of backend probe results shared by readiness checks
+///
+/// Within the interval (`AUTHGATE_READY_CHECK_INTERVAL_MS`), frequent readiness
+/// probes reuse the last result instead of each opening a connection. Once it
+/// is stale, the last result is still served while a single background probe
+/// refreshes it. An interval of zero disables caching.
+pub struct HealthCache {
+    interval: Duration,
+    entries: Mutex<HashMap<String, HealthEntry>>,
+}
+
+impl Default for HealthCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthCache {
+    /// Create a health cache with the interval from the environment
+    pub fn new() -> Self {
+        let interval = env::var("AUTHGATE_READY_CHECK_INTERVAL_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_READY_CHECK_INTERVAL_MS);
+        Self::with_interval(Duration::from_millis(interval))
+    }
+
+    /// Create a health cache reusing probe results for `interval`
+    pub fn with_interval(interval: Duration) -> Self {
+        Self {
+            interval,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a backend, probing it only when no fresh result is cached
+    pub async fn check<F, Fut>(self: &Arc<Self>, backend: &str, probe: F) -> bool
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        if self.interval.is_zero() {
+            return probe().await;
+        }
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(entry) = entries.get_mut(backend) {
+                if entry.checked_at.elapsed() < self.interval {
+                    return entry.healthy;
+                }
+
+                // Serve the stale result while a single probe refreshes it
+                if !entry.refreshing {
+                    entry.refreshing = true;
+                    debug!("Refreshing health of {} in the background", backend);
+                    let cache = Arc::clone(self);
+                    let backend = backend.to_string();
+                    let probe = probe();
+                    tokio::spawn(async move {
+                        let healthy = probe.await;
+                        cache.store(&backend, healthy);
+                    });
+                }
+                return entry.healthy;
+            }
+        }
+
+        let healthy = probe().await;
+        self.store(backend, healthy);
+        healthy
+    }
+
+    fn store(&self, backend: &str, healthy: bool) {
+        self.entries.lock().unwrap().insert(
+            backend.to_string(),
+            HealthEntry {
+                healthy,
+                checked_at: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+}
+
+/// Readiness check: ready once a configuration with routes is loaded and the
+/// database and cache backends can be reached
+///
+/// With `AUTHGATE_READY_CHECK_SESSION` enabled, the session endpoint must be
+/// reachable too.
+pub async fn handle_readyz(State(state): State<AppState>) -> Response {
+    let config = state.config_manager.get_config().await;
+    if config.version == 0 {
+        return ready_response(false, json!({ "config": "not loaded" }));
+    }
+    // Requests matching no route are let through, so without routes nothing is protected
+    if config.routes.is_empty() {
+        return ready_response(false, json!({ "config": "no routes" }));
+    }
+
+    let timeout = Duration::from_millis(
+        env::var("AUTHGATE_READY_CHECK_TIMEOUT_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_READY_CHECK_TIMEOUT_MS),
+    );
+    let mut checks = serde_json::Map::new();
+    checks.insert("config".to_string(), json!("ok"));
+
+    if let Some(route_store) = state.config_manager.get_route_store() {
+        let healthy = state
+            .health
+            .check("database", move || {
+                probe("Database", timeout, async move { route_store.ping().await })
+            })
+            .await;
+        checks.insert("database".to_string(), reachability(healthy));
+    }
+
+    let auth_service = Arc::clone(&state.auth_service);
+    let healthy = state
+        .health
+        .check("cache", move || {
+            probe(
+                "Cache",
+                timeout,
+                async move { auth_service.ping_cache().await },
+            )
+        })
+        .await;
+    checks.insert("cache".to_string(), reachability(healthy));
+
+    let check_session = env::var("AUTHGATE_READY_CHECK_SESSION")
+        .unwrap_or_else(|_| "false".to_string())
+        .to_lowercase()
+        == "true";
+    if check_session {
+        let session_url = state
+            .auth_service
+            .peek_session_url(&config.auth)
+            .to_string();
+        let auth_service = Arc::clone(&state.auth_service);
+        let healthy = state
+            .health
+            .check(&format!("session:{}", session_url), move || async move {
+                match auth_service.probe_session_url(&session_url, timeout).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        warn!("Readiness check failed: {}", e);
+                        false
+                    }
+                }
+            })
+            .await;
+        checks.insert("session".to_string(), reachability(healthy));
+    }
+
+    let ready = checks.values().all(|status| status == "ok");
+    ready_response(ready, serde_json::Value::Object(checks))
+}
+
+/// Liveness check: the process is up and serving requests
+///
+/// Unlike `/readyz`, it doesn't depend on the configuration or any backend, so
+/// an outage of a dependency doesn't get AuthGate restarted.
+pub async fn handle_livez() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Run a backend check, failing it when it doesn't finish within `timeout`
+async fn probe<F>(backend: &'static str, timeout: Duration, check: F) -> bool
+where
+    F: Future<Output = Result<(), AuthGateError>>,
+{
+    match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!("Readiness check failed: {}", e);
+            false
+        }
+        Err(_) => {
+            warn!("Readiness check failed: {} didn't answer in time", backend);
+            false
+        }
+    }
+}
+
+/// Status of a backend in the readiness response
+fn reachability(healthy: bool) -> serde_json::Value {
+    json!(if healthy { "ok" } else { "unreachable" })
+}
+
+/// Build a readiness response with the status of each check
+fn ready_response(ready: bool, checks: serde_json::Value) -> Response {
+    let (status, label) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    };
+
+    let body = json!({
+        "status": label,
+        "checks": checks
+    });
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.to_string().into())
+        .unwrap()
+}
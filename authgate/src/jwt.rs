@@ -0,0 +1,368 @@
+use crate::types::{AuthGateError, SessionResponse, Team, User};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use std::env;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Default time a fetched JWKS is reused for
+const DEFAULT_JWKS_CACHE_SECS: u64 = 300;
+
+/// Minimum time between JWKS fetches triggered by unknown key IDs
+const JWKS_MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Session fields that claims can't be passed through as
+const RESERVED_CLAIMS: &[&str] = &["user", "tenant_id", "authority", "redirect_url"];
+
+/// Claim names a verified session JWT is mapped to a session with
+///
+/// Each can be overridden with `AUTHGATE_JWT_CLAIM_<FIELD>`, e.g.
+/// `AUTHGATE_JWT_CLAIM_ROLES=realm_roles`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimNames {
+    pub id: String,
+    pub email: String,
+    pub roles: String,
+    pub permissions: String,
+    pub teams: String,
+    pub tenant_id: String,
+}
+
+impl Default for ClaimNames {
+    fn default() -> Self {
+        Self {
+            id: "sub".to_string(),
+            email: "email".to_string(),
+            roles: "roles".to_string(),
+            permissions: "permissions".to_string(),
+            teams: "teams".to_string(),
+            tenant_id: "tenant_id".to_string(),
+        }
+    }
+}
+
+impl ClaimNames {
+    /// Read claim names from the environment, defaulting the rest
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let claim = |field: &str, default: String| {
+            env::var(format!("AUTHGATE_JWT_CLAIM_{}", field)).unwrap_or(default)
+        };
+
+        Self {
+            id: claim("ID", defaults.id),
+            email: claim("EMAIL", defaults.email),
+            roles: claim("ROLES", defaults.roles),
+            permissions: claim("PERMISSIONS", defaults.permissions),
+            teams: claim("TEAMS", defaults.teams),
+            tenant_id: claim("TENANT_ID", defaults.tenant_id),
+        }
+    }
+}
+
+/// Where session JWT verification keys come from
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// Shared secret for HMAC-signed (`HS256`, `HS384`, `HS512`) tokens
+    Secret(Vec<u8>),
+    /// URL of a JSON Web Key Set with public keys for asymmetrically signed tokens
+    Jwks(String),
+}
+
+/// Fetched JWKS and when it was fetched
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Verifies session JWTs locally instead of calling the session endpoint
+///
+/// Used when `AUTHGATE_SESSION_MODE=jwt`. The token's signature and expiry are
+/// checked against a shared secret or a JWKS, and the session is built from its
+/// claims. A JWKS is cached for `AUTHGATE_JWKS_CACHE_SECS` and refetched early
+/// when a token is signed with an unknown key, so key rotation is picked up.
+pub struct JwtVerifier {
+    source: KeySource,
+    claims: ClaimNames,
+    client: reqwest::Client,
+    jwks_ttl: Duration,
+    jwks: RwLock<Option<CachedJwks>>,
+}
+
+impl JwtVerifier {
+    /// Create a verifier for the given keys and claim names
+    pub fn new(source: KeySource, claims: ClaimNames) -> Self {
+        Self {
+            source,
+            claims,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to create HTTP client"),
+            jwks_ttl: Duration::from_secs(DEFAULT_JWKS_CACHE_SECS),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    /// Set how long a fetched JWKS is reused for
+    pub fn with_jwks_ttl(mut self, ttl: Duration) -> Self {
+        self.jwks_ttl = ttl;
+        self
+    }
+
+    /// Create a verifier from the environment if `AUTHGATE_SESSION_MODE` is `jwt`
+    ///
+    /// Keys come from `AUTHGATE_JWKS_URL` or, without it, `AUTHGATE_JWT_SECRET`.
+    /// Returns `None` in the default `remote` mode.
+    pub fn from_env() -> Result<Option<Self>, AuthGateError> {
+        let mode = env::var("AUTHGATE_SESSION_MODE")
+            .unwrap_or_else(|_| "remote".to_string())
+            .to_lowercase();
+
+        match mode.trim() {
+            "" | "remote" => Ok(None),
+            "jwt" => {
+                let jwks_url = env::var("AUTHGATE_JWKS_URL").unwrap_or_default();
+                let secret = env::var("AUTHGATE_JWT_SECRET").unwrap_or_default();
+                let source = if !jwks_url.is_empty() {
+                    info!("Verifying session JWTs against the JWKS at {}", jwks_url);
+                    KeySource::Jwks(jwks_url)
+                } else if !secret.is_empty() {
+                    info!("Verifying session JWTs with a shared secret");
+                    KeySource::Secret(secret.into_bytes())
+                } else {
+                    return Err(AuthGateError::ConfigError(
+                        "AUTHGATE_SESSION_MODE=jwt requires AUTHGATE_JWKS_URL or AUTHGATE_JWT_SECRET"
+                            .to_string(),
+                    ));
+                };
+
+                let jwks_ttl = env::var("AUTHGATE_JWKS_CACHE_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse::<u64>().ok())
+                    .unwrap_or(DEFAULT_JWKS_CACHE_SECS);
+
+                Ok(Some(
+                    Self::new(source, ClaimNames::from_env())
+                        .with_jwks_ttl(Duration::from_secs(jwks_ttl)),
+                ))
+            }
+            other => Err(AuthGateError::ConfigError(format!(
+                "AUTHGATE_SESSION_MODE must be remote or jwt, got {}",
+                other
+            ))),
+        }
+    }
+
+    /// Verify a session JWT and build the session from its claims
+    pub async fn verify(&self, token: &str) -> Result<SessionResponse, AuthGateError> {
+        let header = decode_header(token).map_err(invalid_token)?;
+        let hmac = matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+
+        // Only accept the algorithm family the keys are for, so a token can't
+        // pick how its signature is checked
+        let key = match &self.source {
+            KeySource::Secret(secret) if hmac => DecodingKey::from_secret(secret),
+            KeySource::Jwks(url) if !hmac => self.jwks_key(url, header.kid.as_deref()).await?,
+            _ => {
+                return Err(AuthGateError::AuthError(format!(
+                    "Invalid session token: unexpected signing algorithm {:?}",
+                    header.alg
+                )))
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.validate_aud = false;
+        let data = decode::<Map<String, Value>>(token, &key, &validation).map_err(invalid_token)?;
+
+        Ok(self.session_from_claims(data.claims))
+    }
+
+    /// Find the key a token was signed with, fetching the JWKS when needed
+    async fn jwks_key(&self, url: &str, kid: Option<&str>) -> Result<DecodingKey, AuthGateError> {
+        {
+            let jwks = self.jwks.read().await;
+            if let Some(cached) = jwks.as_ref() {
+                let age = cached.fetched_at.elapsed();
+                if age < self.jwks_ttl {
+                    if let Some(key) = find_key(&cached.keys, kid)? {
+                        return Ok(key);
+                    }
+                    // An unknown key may have just been rotated in, but don't let
+                    // such tokens make us fetch the JWKS on every request
+                    if age < JWKS_MIN_REFETCH_INTERVAL {
+                        return Err(unknown_key(kid));
+                    }
+                }
+            }
+        }
+
+        match self.fetch_jwks(url).await {
+            Ok(keys) => {
+                let key = find_key(&keys, kid)?;
+                *self.jwks.write().await = Some(CachedJwks {
+                    keys,
+                    fetched_at: Instant::now(),
+                });
+                key.ok_or_else(|| unknown_key(kid))
+            }
+            Err(e) => {
+                // Keep verifying with the last known keys while the JWKS is unavailable
+                let jwks = self.jwks.read().await;
+                match jwks.as_ref() {
+                    Some(cached) => {
+                        warn!("{}, using the previously fetched keys", e);
+                        find_key(&cached.keys, kid)?.ok_or_else(|| unknown_key(kid))
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Fetch the JWKS
+    async fn fetch_jwks(&self, url: &str) -> Result<JwkSet, AuthGateError> {
+        debug!("Fetching JWKS from {}", url);
+        let fetch_error =
+            |e: reqwest::Error| AuthGateError::AuthError(format!("Failed to fetch JWKS: {}", e));
+
+        self.client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(fetch_error)?
+            .json::<JwkSet>()
+            .await
+            .map_err(fetch_error)
+    }
+
+    /// Build a session from verified claims
+    ///
+    /// Roles and permissions may be arrays or space- or comma-separated strings.
+    /// Teams may be team IDs or objects with at least an `id`. All claims not
+    /// mapped to a session field are kept, e.g. `amr` for MFA checks.
+    fn session_from_claims(&self, mut claims: Map<String, Value>) -> SessionResponse {
+        let names = &self.claims;
+        let user = User {
+            id: string_claim(&claims, &names.id),
+            email: string_claim(&claims, &names.email),
+            roles: list_claim(&claims, &names.roles),
+            permissions: list_claim(&claims, &names.permissions),
+            teams: claims
+                .get(&names.teams)
+                .and_then(|teams| teams.as_array())
+                .map(|teams| teams.iter().filter_map(team_from_claim).collect())
+                .unwrap_or_default(),
+        };
+        let tenant_id = string_claim(&claims, &names.tenant_id);
+        let authority = string_claim(&claims, "iss");
+
+        for name in [
+            &names.id,
+            &names.email,
+            &names.roles,
+            &names.permissions,
+            &names.teams,
+            &names.tenant_id,
+        ] {
+            claims.remove(name.as_str());
+        }
+        claims.retain(|name, _| !RESERVED_CLAIMS.contains(&name.as_str()));
+
+        SessionResponse {
+            user,
+            tenant_id,
+            authority,
+            redirect_url: None,
+            claims,
+        }
+    }
+}
+
+/// Find the key with the given ID, or the only key if the token names none
+fn find_key(keys: &JwkSet, kid: Option<&str>) -> Result<Option<DecodingKey>, AuthGateError> {
+    let jwk = match kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    };
+
+    jwk.map(|jwk| {
+        DecodingKey::from_jwk(jwk)
+            .map_err(|e| AuthGateError::AuthError(format!("Unusable key in JWKS: {}", e)))
+    })
+    .transpose()
+}
+
+fn unknown_key(kid: Option<&str>) -> AuthGateError {
+    AuthGateError::AuthError(format!(
+        "Invalid session token: no signing key found for key ID {}",
+        kid.unwrap_or("(none)")
+    ))
+}
+
+fn invalid_token(e: jsonwebtoken::errors::Error) -> AuthGateError {
+    AuthGateError::AuthError(format!("Invalid session token: {}", e))
+}
+
+fn string_claim(claims: &Map<String, Value>, name: &str) -> String {
+    claims
+        .get(name)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn list_claim(claims: &Map<String, Value>, name: &str) -> Vec<String> {
+    match claims.get(name) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|item| item.to_string())
+            .collect(),
+        Some(Value::String(items)) => items
+            .split([' ', ','])
+            .filter(|item| !item.is_empty())
+            .map(|item| item.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn team_from_claim(value: &Value) -> Option<Team> {
+    match value {
+        Value::String(id) => Some(Team {
+            id: id.clone(),
+            name: id.clone(),
+            is_owner: false,
+            scopes: Vec::new(),
+        }),
+        Value::Object(team) => {
+            let id = team.get("id")?.as_str()?.to_string();
+            Some(Team {
+                name: team
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or(&id)
+                    .to_string(),
+                is_owner: team
+                    .get("is_owner")
+                    .and_then(|is_owner| is_owner.as_bool())
+                    .unwrap_or(false),
+                scopes: team
+                    .get("scopes")
+                    .and_then(|scopes| serde_json::from_value(scopes.clone()).ok())
+                    .unwrap_or_default(),
+                id,
+            })
+        }
+        _ => None,
+    }
+}
@@ -1,10 +1,19 @@
 pub mod admin;
 pub mod auth;
+pub mod authorizer;
+pub mod breaker;
 pub mod cache;
 pub mod config;
 pub mod config_provider;
 #[cfg(test)]
 pub mod config_provider_mock;
+pub mod expr;
+pub mod health;
+pub mod jwt;
 pub mod matcher;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod proxy;
+pub mod redact;
 pub mod types;
+pub mod webhook;
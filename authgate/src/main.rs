@@ -1,12 +1,18 @@
 use authgate::admin::{
-    create_admin_router, create_route, delete_route, get_route, is_admin_api_enabled, list_routes,
-    update_route,
+    create_admin_router, create_route, delete_route, delete_routes, export_config, get_route,
+    is_admin_api_enabled, list_routes, reload_config, test_session, update_route, AdminState,
 };
 use authgate::auth::AuthService;
-use authgate::config::ConfigManager;
+use authgate::config::{config_schema, refuse_empty_config, ConfigManager};
+use authgate::health::{handle_livez, handle_readyz, HealthCache};
+use authgate::jwt::JwtVerifier;
 use authgate::matcher::RouteMatcher;
-use authgate::proxy::{handle_forward_auth, AppState};
-use axum::{routing::get, Router};
+use authgate::proxy::{handle_forward_auth, handle_logout, unauth_status, AppState};
+use authgate::redact::redact_headers;
+use axum::{
+    routing::{get, post},
+    Router,
+};
 use std::env;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -17,7 +23,7 @@ use tracing_subscriber::prelude::*;
 #[cfg(feature = "postgres")]
 async fn run_migrations_if_postgres() -> anyhow::Result<()> {
     let backend = std::env::var("AUTHGATE_CONFIG_BACKEND").unwrap_or_else(|_| "json".into());
-    if backend == "postgres" {
+    if backend == "postgres" || backend == "composite" {
         let database_url = std::env::var("DATABASE_URL")
             .expect("DATABASE_URL must be set when using Postgres backend");
         let pool = sqlx::PgPool::connect(&database_url).await?;
@@ -25,6 +31,9 @@ async fn run_migrations_if_postgres() -> anyhow::Result<()> {
         tracing::info!("Postgres migrations applied successfully.");
 
         bootstrap_seeds_if_needed(&pool).await?;
+
+        // Close the migration pool rather than dropping its connections
+        pool.close().await;
     }
     Ok(())
 }
@@ -79,8 +88,84 @@ async fn bootstrap_seeds_if_needed(pool: &sqlx::PgPool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "sqlite")]
+async fn run_migrations_if_sqlite() -> anyhow::Result<()> {
+    let backend = std::env::var("AUTHGATE_CONFIG_BACKEND").unwrap_or_else(|_| "json".into());
+    if backend == "sqlite" {
+        let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "authgate.db".into());
+        let provider = authgate::config_provider::SqliteProvider::new(&database_url)?;
+        provider.migrate().await?;
+        tracing::info!("SQLite migrations applied successfully.");
+
+        bootstrap_sqlite_seeds_if_needed(provider.pool()).await?;
+
+        provider.pool().close().await;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn bootstrap_sqlite_seeds_if_needed(pool: &sqlx::SqlitePool) -> anyhow::Result<()> {
+    use serde_json::json;
+
+    // Seed auth_config
+    let session_url = std::env::var("AUTHGATE_BOOTSTRAP_SESSION_URL").ok();
+    let login_redirect = std::env::var("AUTHGATE_BOOTSTRAP_LOGIN_REDIRECT").ok();
+    let cookie_name =
+        std::env::var("AUTHGATE_BOOTSTRAP_COOKIE_NAME").unwrap_or_else(|_| "session".into());
+
+    if let (Some(session_url), Some(login_redirect)) = (session_url, login_redirect) {
+        let exists: Option<(i32,)> = sqlx::query_as("SELECT id FROM auth_config LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            sqlx::query("INSERT INTO auth_config (session_url, login_redirect, cookie_name) VALUES (?, ?, ?)")
+                .bind(&session_url)
+                .bind(&login_redirect)
+                .bind(&cookie_name)
+                .execute(pool)
+                .await?;
+            tracing::info!("✅ Seeded auth_config.");
+        }
+    }
+
+    // Seed routes
+    let host = std::env::var("AUTHGATE_BOOTSTRAP_ROUTE_HOST").ok();
+    let path = std::env::var("AUTHGATE_BOOTSTRAP_ROUTE_PATH").ok();
+    let require_roles =
+        std::env::var("AUTHGATE_BOOTSTRAP_ROUTE_ROLES").unwrap_or_else(|_| "admin".into());
+
+    if let (Some(host), Some(path)) = (host, path) {
+        let exists: Option<(i32,)> =
+            sqlx::query_as("SELECT id FROM routes WHERE host = ? AND path = ?")
+                .bind(&host)
+                .bind(&path)
+                .fetch_optional(pool)
+                .await?;
+        if exists.is_none() {
+            // SQLite stores the require block as JSON text
+            let require = json!({ "roles": [require_roles] }).to_string();
+            sqlx::query("INSERT INTO routes (host, path, require) VALUES (?, ?, ?)")
+                .bind(&host)
+                .bind(&path)
+                .bind(&require)
+                .execute(pool)
+                .await?;
+            tracing::info!("✅ Seeded route: {}{}", host, path);
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // Print the configuration file schema for tooling instead of serving
+    if std::env::args().skip(1).any(|arg| arg == "--schema") {
+        println!("{}", serde_json::to_string_pretty(&config_schema())?);
+        return Ok(());
+    }
+
     // Initialize logging
     tracing_subscriber::registry()
         .with(
@@ -98,10 +183,24 @@ async fn main() -> anyhow::Result<()> {
 
     #[cfg(feature = "postgres")]
     run_migrations_if_postgres().await?;
+    #[cfg(feature = "sqlite")]
+    run_migrations_if_sqlite().await?;
+
+    // Fail fast on an unsupported unauthenticated status or session mode
+    unauth_status()?;
+    JwtVerifier::from_env()?;
 
     // Initialize configuration manager
     let config_manager = Arc::new(ConfigManager::new());
     config_manager.load_config().await?;
+    config_manager
+        .check_not_empty(refuse_empty_config())
+        .await?;
+    config_manager.check_cookie_name().await;
+
+    // Reload the configuration when it changes
+    #[cfg(feature = "config_reload")]
+    authgate::config::setup_config_watcher(config_manager.clone()).await?;
 
     // Initialize route matcher
     let route_matcher = Arc::new(RouteMatcher::new(config_manager.get_config_ref()));
@@ -114,32 +213,84 @@ async fn main() -> anyhow::Result<()> {
         config_manager: config_manager.clone(),
         route_matcher: route_matcher.clone(),
         auth_service: auth_service.clone(),
+        health: Arc::new(HealthCache::new()),
+    };
+
+    let admin_state = AdminState {
+        config_manager: config_manager.clone(),
+        auth_service: auth_service.clone(),
     };
 
-    // Create the admin router
-    let mut admin_router = create_admin_router::<AppState>();
+    // Create the admin router, validating admin sessions with the app's auth service
+    let mut admin_router = create_admin_router::<AppState>(admin_state.clone());
 
     // Add routes API endpoints if the Admin API is enabled
-    #[cfg(feature = "postgres")]
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
     if is_admin_api_enabled() {
         // Create a separate router for routes API
         let routes_router = Router::new()
-            .route("/", get(list_routes).post(create_route))
+            .route(
+                "/",
+                get(list_routes).post(create_route).delete(delete_routes),
+            )
             .route(
                 "/:id",
                 get(get_route).put(update_route).delete(delete_route),
             )
-            .with_state(Arc::clone(&config_manager));
+            .with_state(admin_state.clone());
 
         // Nest the routes router under /routes
         admin_router = admin_router.nest("/routes", routes_router);
+
+        // Configuration export for backups and backend migration
+        let config_router = Router::new()
+            .route("/export", get(export_config))
+            .with_state(admin_state.clone());
+
+        admin_router = admin_router.nest("/config", config_router);
+
+        // End-to-end session token check for support
+        let test_session_router = Router::new()
+            .route("/", post(test_session))
+            .with_state(admin_state.clone());
+
+        admin_router = admin_router.nest("/test-session", test_session_router);
+
+        // On-demand reload, for routes changed outside the Admin API
+        let reload_router = Router::new()
+            .route("/", post(reload_config))
+            .with_state(admin_state.clone());
+
+        admin_router = admin_router.nest("/reload", reload_router);
     }
 
     // Build the application
     let app = Router::new()
         .route("/auth", get(handle_forward_auth))
+        .route("/auth/logout", post(handle_logout))
+        .route("/readyz", get(handle_readyz))
+        .route("/livez", get(handle_livez));
+
+    // Request, decision, latency and cache metrics for Prometheus
+    #[cfg(feature = "metrics")]
+    let app = {
+        authgate::metrics::spawn_upkeep();
+        app.route("/metrics", get(authgate::metrics::handle_metrics))
+    };
+
+    let app = app
         .nest("/admin", admin_router)
-        .layer(TraceLayer::new_for_http())
+        .layer(
+            // Log request headers with credentials and identity headers redacted
+            TraceLayer::new_for_http().make_span_with(|request: &axum::extract::Request| {
+                tracing::debug_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    headers = ?redact_headers(request.headers()),
+                )
+            }),
+        )
         .with_state(app_state);
 
     // Get the port from environment or use default
@@ -155,7 +306,41 @@ async fn main() -> anyhow::Result<()> {
         tokio::net::TcpListener::bind(addr).await?,
         app.into_make_service(),
     )
+    .with_graceful_shutdown(shutdown_signal())
     .await?;
 
+    // Persist the session cache and close its connections
+    auth_service.shutdown().await;
+
+    // Close the config database pool, shared by the route store
+    config_manager.close().await;
+
     Ok(())
 }
+
+/// Resolve once the process is asked to shut down
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutting down");
+}
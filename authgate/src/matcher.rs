@@ -1,72 +1,770 @@
 use crate::types::{Config, Route};
+use lru::LruCache;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::sync::Arc;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 use tracing::{debug, trace};
 
-/// Regex for matching wildcard hostnames
+/// Regex for matching wildcard hostnames, `*.` for one label or `**.` for any number
 static WILDCARD_HOST_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^\*\.(.+)$").expect("Failed to compile wildcard host regex"));
+    Lazy::new(|| Regex::new(r"^(\*\*?)\.(.+)$").expect("Failed to compile wildcard host regex"));
+
+/// How specifically a route's host pattern selects hosts, most specific first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HostSpecificity {
+    /// A literal host such as `app.example.com`
+    Exact,
+    /// A single-label wildcard such as `*.example.com`
+    Wildcard,
+    /// A multi-level wildcard such as `**.example.com`
+    MultiWildcard,
+}
+
+/// How specifically a route's path pattern selects paths, most specific first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathSpecificity {
+    /// A literal path such as `/admin`
+    Exact,
+    /// A pattern with named segments such as `/users/{id}/profile`
+    Template,
+    /// A prefix pattern such as `/admin/*`
+    Prefix,
+}
+
+/// A route host pattern compiled once per configuration load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostMatcher {
+    /// A literal host such as `app.example.com`
+    Exact(String),
+    /// A wildcard such as `*.example.com` matching exactly one more label,
+    /// holding the suffix after `*.`
+    Wildcard(String),
+    /// A wildcard such as `**.example.com` matching one or more labels,
+    /// holding the suffix after `**.`
+    MultiWildcard(String),
+}
+
+impl HostMatcher {
+    /// Compile a route host pattern
+    ///
+    /// Hostnames are case-insensitive and may be written fully qualified, so
+    /// the pattern is lowercased and a trailing dot dropped, like request hosts.
+    pub fn compile(pattern: &str) -> Self {
+        let pattern = canonical_hostname(pattern);
+        match WILDCARD_HOST_REGEX.captures(&pattern) {
+            Some(captures) if &captures[1] == "**" => {
+                HostMatcher::MultiWildcard(captures[2].to_string())
+            }
+            Some(captures) => HostMatcher::Wildcard(captures[2].to_string()),
+            None => HostMatcher::Exact(pattern),
+        }
+    }
+
+    /// Match a request host against the pattern
+    pub fn matches(&self, request_host: &str) -> bool {
+        match self {
+            HostMatcher::Exact(host) => {
+                let matched = request_host == host;
+                if matched {
+                    trace!("Exact host match: {}", request_host);
+                }
+                matched
+            }
+            HostMatcher::Wildcard(suffix) => {
+                let matched = wildcard_labels(request_host, suffix)
+                    .is_some_and(|labels| !labels.contains('.'));
+                if matched {
+                    trace!(
+                        "Wildcard host match: {} matches pattern *.{}",
+                        request_host,
+                        suffix
+                    );
+                }
+                matched
+            }
+            HostMatcher::MultiWildcard(suffix) => {
+                let matched = wildcard_labels(request_host, suffix).is_some();
+                if matched {
+                    trace!(
+                        "Multi-level wildcard host match: {} matches pattern **.{}",
+                        request_host,
+                        suffix
+                    );
+                }
+                matched
+            }
+        }
+    }
+
+    /// Whether some host matches both patterns
+    pub fn overlaps(&self, other: &HostMatcher) -> bool {
+        match (self, other) {
+            (HostMatcher::Exact(a), HostMatcher::Exact(b)) => a == b,
+            (HostMatcher::Exact(host), pattern) | (pattern, HostMatcher::Exact(host)) => {
+                pattern.matches(host)
+            }
+            (HostMatcher::Wildcard(a), HostMatcher::Wildcard(b)) => a == b,
+            // The single-label side only matches hosts one label above its suffix
+            (HostMatcher::Wildcard(single), HostMatcher::MultiWildcard(multi))
+            | (HostMatcher::MultiWildcard(multi), HostMatcher::Wildcard(single)) => {
+                single == multi || single.ends_with(&format!(".{}", multi))
+            }
+            (HostMatcher::MultiWildcard(a), HostMatcher::MultiWildcard(b)) => {
+                a == b || a.ends_with(&format!(".{}", b)) || b.ends_with(&format!(".{}", a))
+            }
+        }
+    }
+}
+
+/// The labels a request host has in front of a wildcard suffix, if any
+fn wildcard_labels<'a>(request_host: &'a str, suffix: &str) -> Option<&'a str> {
+    request_host
+        .strip_suffix(suffix)
+        .and_then(|prefix| prefix.strip_suffix('.'))
+        .filter(|labels| !labels.is_empty())
+}
+
+/// Sort key ranking routes by specificity, where a smaller key is more specific
+///
+/// When several routes match a request, the one with the smallest key wins.
+/// Keys compare field by field in this order:
+///
+/// 1. Methods: routes listing `methods`, then routes accepting any method
+/// 2. Host kind: exact host, then single-label wildcard, then multi-level wildcard
+/// 3. Port: routes requiring a port, then routes accepting any port
+/// 4. Literal path length: longer literal path (or prefix) first
+/// 5. Path kind: exact path, then template, then prefix
+/// 6. Literal host length: longer host pattern first
+///
+/// Routes with equal keys fall back to configuration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpecificityKey {
+    any_method: bool,
+    host: HostSpecificity,
+    any_port: bool,
+    path_len: Reverse<usize>,
+    path: PathSpecificity,
+    host_len: Reverse<usize>,
+}
+
+/// Order route indices from most to least specific
+///
+/// Equally specific routes keep their configuration order, so matching routes
+/// in this order and stopping at the first match picks the same route as
+/// comparing every match's [`route_specificity`].
+pub fn specificity_order(routes: &[Route]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..routes.len()).collect();
+    order.sort_by_cached_key(|&index| route_specificity(&routes[index]));
+    order
+}
+
+/// Compute the specificity key of a route
+pub fn route_specificity(route: &Route) -> SpecificityKey {
+    let host = match HostMatcher::compile(&route.host) {
+        HostMatcher::Exact(_) => HostSpecificity::Exact,
+        HostMatcher::Wildcard(_) => HostSpecificity::Wildcard,
+        HostMatcher::MultiWildcard(_) => HostSpecificity::MultiWildcard,
+    };
+
+    let (path, literal_path) = match route.path.find('{') {
+        Some(start) => (PathSpecificity::Template, &route.path[..start]),
+        None => match route.path.strip_suffix('*') {
+            Some(prefix) => (PathSpecificity::Prefix, prefix),
+            None => (PathSpecificity::Exact, route.path.as_str()),
+        },
+    };
+
+    SpecificityKey {
+        any_method: route.methods.is_none(),
+        host,
+        any_port: route.port.is_none(),
+        path_len: Reverse(literal_path.len()),
+        path,
+        host_len: Reverse(
+            route
+                .host
+                .trim_start_matches('*')
+                .trim_start_matches('.')
+                .len(),
+        ),
+    }
+}
+
+/// Two routes that can match the same request
+///
+/// Only overlaps between different host patterns (a wildcard host covering
+/// another route's host) and exact duplicates are reported, since nesting paths
+/// on a single host is the usual way to refine requirements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteOverlap {
+    /// Index of the route chosen for requests matching both
+    pub winner: usize,
+    /// Index of the route that is shadowed for those requests
+    pub shadowed: usize,
+}
+
+/// Find routes that overlap ambiguously, along with which one wins
+///
+/// The winner is the route with the more specific [`SpecificityKey`], then the
+/// one first in configuration order, as in [`RouteMatcher::match_route`].
+pub fn find_route_overlaps(routes: &[Route]) -> Vec<RouteOverlap> {
+    let mut overlaps = Vec::new();
+
+    for (i, a) in routes.iter().enumerate() {
+        for (j, b) in routes.iter().enumerate().skip(i + 1) {
+            let ports_overlap = match (a.port, b.port) {
+                (Some(a_port), Some(b_port)) => a_port == b_port,
+                _ => true,
+            };
+            let methods_overlap = methods_overlap(a.methods.as_deref(), b.methods.as_deref());
+            let duplicate =
+                a.host == b.host && a.path == b.path && a.port == b.port && methods_overlap;
+            let ambiguous = a.host != b.host
+                && hosts_overlap(&a.host, &b.host)
+                && paths_overlap(&a.path, &b.path)
+                && ports_overlap
+                && methods_overlap;
+            if !duplicate && !ambiguous {
+                continue;
+            }
+
+            let (winner, shadowed) = if (route_specificity(b), j) < (route_specificity(a), i) {
+                (j, i)
+            } else {
+                (i, j)
+            };
+            overlaps.push(RouteOverlap { winner, shadowed });
+        }
+    }
+
+    overlaps
+}
+
+/// Whether some method is accepted by both method lists, where `None` accepts any
+fn methods_overlap(a: Option<&[String]>, b: Option<&[String]>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a
+            .iter()
+            .any(|method| b.iter().any(|other| method.eq_ignore_ascii_case(other))),
+        _ => true,
+    }
+}
+
+/// Whether some host matches both host patterns
+fn hosts_overlap(a: &str, b: &str) -> bool {
+    HostMatcher::compile(a).overlaps(&HostMatcher::compile(b))
+}
+
+/// Whether some path matches both path patterns
+///
+/// Templates are treated as prefix patterns up to their first named segment.
+fn paths_overlap(a: &str, b: &str) -> bool {
+    match (pattern_prefix(a), pattern_prefix(b)) {
+        (None, None) => a == b,
+        (Some(prefix), None) => b.starts_with(prefix),
+        (None, Some(prefix)) => a.starts_with(prefix),
+        (Some(a_prefix), Some(b_prefix)) => {
+            a_prefix.starts_with(b_prefix) || b_prefix.starts_with(a_prefix)
+        }
+    }
+}
+
+/// Literal prefix of every path a pattern matches, or `None` for a literal path
+fn pattern_prefix(path: &str) -> Option<&str> {
+    match path.find('{') {
+        Some(start) => Some(&path[..start]),
+        None => path.strip_suffix('*'),
+    }
+}
+
+/// The path of a request URI, without its query string or fragment
+fn path_without_query(path: &str) -> &str {
+    path.split(['?', '#']).next().unwrap_or_default()
+}
+
+/// Match a path against a pattern with named segments, returning the captures
+///
+/// Each `{name}` segment matches one non-empty path segment, and a trailing `*`
+/// segment matches the rest of the path. The query string is ignored.
+pub fn match_path_template(route_path: &str, request_path: &str) -> Option<Vec<(String, String)>> {
+    let request_path = path_without_query(request_path);
+    let mut request_segments = request_path.split('/');
+    let mut pattern_segments = route_path.split('/').peekable();
+    let mut captures = Vec::new();
+
+    while let Some(pattern) = pattern_segments.next() {
+        let segment = request_segments.next()?;
+        if pattern == "*" && pattern_segments.peek().is_none() {
+            return Some(captures);
+        }
+
+        match pattern.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+            Some(name) if !segment.is_empty() => {
+                captures.push((name.to_string(), segment.to_string()));
+            }
+            Some(_) => return None,
+            None if pattern == segment => {}
+            None => return None,
+        }
+    }
+
+    match request_segments.next() {
+        None => Some(captures),
+        Some(_) => None,
+    }
+}
+
+/// Values captured by a route's named path segments for a request path
+pub fn path_captures(route: &Route, request_path: &str) -> Vec<(String, String)> {
+    if !route.path.contains('{') {
+        return Vec::new();
+    }
+    match_path_template(&route.path, request_path).unwrap_or_default()
+}
+
+/// Canonicalize a request path before matching
+///
+/// Percent-encoded unreserved characters (letters, digits, `-`, `.`, `_` and
+/// `~`) are decoded, as RFC 3986 normalization does, so `/%61dmin` becomes
+/// `/admin`. Backslashes and encoded slashes and backslashes (`%2f`, `%5c`)
+/// separate segments, as backends may decode them. Then `.` and `..` segments
+/// are resolved and repeated slashes collapsed, so `/admin/../secret` and
+/// `/admin%2f%2e%2e%2fsecret` become `/secret` and `/admin//users` becomes
+/// `/admin/users`. `..` never climbs above the root. Other encoded characters
+/// and the query string are kept as is.
+pub fn canonicalize_path(path: &str) -> String {
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    if !path.starts_with('/') {
+        return match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path.to_string(),
+        };
+    }
+
+    let path = decode_path(path);
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/').skip(1) {
+        trailing_slash = true;
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        if segment == ".." {
+            segments.pop();
+            continue;
+        }
+        segments.push(segment);
+        trailing_slash = false;
+    }
+
+    let mut canonical = format!("/{}", segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        canonical.push('/');
+    }
+    if let Some(query) = query {
+        canonical.push('?');
+        canonical.push_str(query);
+    }
+    canonical
+}
+
+/// Decode percent-encoded unreserved characters, and turn backslashes and
+/// encoded slashes and backslashes into `/`
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let encoded = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match encoded {
+            Some(b'/' | b'\\') => {
+                decoded.push(b'/');
+                i += 3;
+            }
+            Some(c) if c.is_ascii_alphanumeric() || matches!(c, b'-' | b'.' | b'_' | b'~') => {
+                decoded.push(c);
+                i += 3;
+            }
+            _ => {
+                decoded.push(if bytes[i] == b'\\' { b'/' } else { bytes[i] });
+                i += 1;
+            }
+        }
+    }
+    // Only ASCII characters replace ASCII escapes, so the path stays UTF-8
+    String::from_utf8(decoded).unwrap_or_else(|_| path.to_string())
+}
+
+/// Default number of (host, path, method) match results remembered by the matcher
+const DEFAULT_ROUTE_CACHE_SIZE: usize = 1024;
+
+/// Cache key for a match result
+type RouteCacheKey = (String, String, String);
+
+/// Bounded LRU cache of match results, keyed by request host, path and method
+struct RouteCache {
+    /// Config version the cached results were computed against
+    version: u64,
+    /// Index of the matched route (or `None` for no match), unless disabled
+    entries: Option<LruCache<RouteCacheKey, Option<usize>>>,
+}
+
+impl RouteCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            version: 0,
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+        }
+    }
+
+    /// Drop all cached results if the config has been reloaded since they were stored
+    fn sync_version(&mut self, version: u64) {
+        if self.version != version {
+            if let Some(entries) = self.entries.as_mut() {
+                entries.clear();
+            }
+            self.version = version;
+        }
+    }
+
+    fn get(&mut self, version: u64, key: &RouteCacheKey) -> Option<Option<usize>> {
+        self.sync_version(version);
+        self.entries.as_mut()?.get(key).copied()
+    }
+
+    fn insert(&mut self, version: u64, key: RouteCacheKey, index: Option<usize>) {
+        self.sync_version(version);
+        if let Some(entries) = self.entries.as_mut() {
+            entries.put(key, index);
+        }
+    }
+}
+
+/// Default number of request hosts whose normalized form is remembered by the matcher
+const DEFAULT_HOST_CACHE_SIZE: usize = 1024;
+
+/// Canonical form of a request host: its hostname and the port it carries, if any
+type NormalizedHost = (String, Option<u16>);
+
+/// Bounded cache of normalized request hosts, cleared when the config is reloaded
+///
+/// Once full it is emptied rather than evicting entries one by one, as the
+/// hosts seen by a deployment rarely outnumber its capacity.
+struct HostCache {
+    version: u64,
+    capacity: usize,
+    entries: HashMap<String, NormalizedHost>,
+}
+
+impl HostCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            version: 0,
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Drop all entries if the config has been reloaded since they were stored
+    fn sync_version(&mut self, version: u64) {
+        if self.version != version {
+            self.entries.clear();
+            self.version = version;
+        }
+    }
+
+    fn get(&mut self, version: u64, host: &str) -> Option<NormalizedHost> {
+        self.sync_version(version);
+        self.entries.get(host).cloned()
+    }
+
+    fn insert(&mut self, version: u64, host: String, normalized: NormalizedHost) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.sync_version(version);
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&host) {
+            self.entries.clear();
+        }
+        self.entries.insert(host, normalized);
+    }
+}
+
+/// Route table compiled for one config version
+struct CompiledRoutes {
+    version: u64,
+    /// Host matchers, indexed like the config's routes
+    matchers: Arc<Vec<HostMatcher>>,
+    /// Route indices from most to least specific, see [`specificity_order`]
+    order: Arc<Vec<usize>>,
+}
 
 /// RouteMatcher handles matching incoming requests to configured routes
 pub struct RouteMatcher {
     config: Arc<RwLock<Config>>,
+    cache: Mutex<RouteCache>,
+    hosts: Mutex<Option<CompiledRoutes>>,
+    host_cache: Mutex<HostCache>,
+    scan_count: AtomicUsize,
+    compile_count: AtomicUsize,
+    normalize_count: AtomicUsize,
+    /// Whether `/admin/*` also matches `/admin`
+    wildcard_bare_prefix: bool,
+    /// Whether request paths are canonicalized before matching
+    canonicalize_paths: bool,
 }
 
 impl RouteMatcher {
     /// Create a new RouteMatcher with the given configuration
     pub fn new(config: Arc<RwLock<Config>>) -> Self {
-        Self { config }
+        let cache_size = env::var("AUTHGATE_ROUTE_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_ROUTE_CACHE_SIZE);
+
+        let host_cache_size = env::var("AUTHGATE_HOST_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_HOST_CACHE_SIZE);
+
+        let wildcard_bare_prefix = env::var("AUTHGATE_WILDCARD_MATCH_BARE_PREFIX")
+            .unwrap_or_else(|_| "true".to_string())
+            .to_lowercase()
+            == "true";
+
+        let canonicalize_paths = env::var("AUTHGATE_CANONICALIZE_PATHS")
+            .unwrap_or_else(|_| "true".to_string())
+            .to_lowercase()
+            == "true";
+
+        Self {
+            config,
+            cache: Mutex::new(RouteCache::new(cache_size)),
+            hosts: Mutex::new(None),
+            host_cache: Mutex::new(HostCache::new(host_cache_size)),
+            scan_count: AtomicUsize::new(0),
+            compile_count: AtomicUsize::new(0),
+            normalize_count: AtomicUsize::new(0),
+            wildcard_bare_prefix,
+            canonicalize_paths,
+        }
+    }
+
+    /// Set whether a trailing `/*` pattern also matches the bare prefix, e.g.
+    /// `/admin/*` matching `/admin`
+    pub fn with_wildcard_bare_prefix(mut self, enabled: bool) -> Self {
+        self.wildcard_bare_prefix = enabled;
+        self
+    }
+
+    /// Set how many match results are remembered, `0` to scan the route table
+    /// on every request (`AUTHGATE_ROUTE_CACHE_SIZE`)
+    pub fn with_route_cache_size(mut self, size: usize) -> Self {
+        self.cache = Mutex::new(RouteCache::new(size));
+        self
+    }
+
+    /// Set how many normalized request hosts are remembered, `0` to normalize
+    /// every request (`AUTHGATE_HOST_CACHE_SIZE`)
+    pub fn with_host_cache_size(mut self, size: usize) -> Self {
+        self.host_cache = Mutex::new(HostCache::new(size));
+        self
     }
 
-    /// Match a request to a route based on host and path
-    pub async fn match_route(&self, host: &str, path: &str) -> Option<Route> {
+    /// Set whether request paths are canonicalized before matching
+    pub fn with_path_canonicalization(mut self, enabled: bool) -> Self {
+        self.canonicalize_paths = enabled;
+        self
+    }
+
+    /// The path a request is matched and authorized with
+    ///
+    /// Canonicalized with [`canonicalize_path`] unless disabled, for apps that
+    /// use literal dot segments or repeated slashes.
+    pub fn request_path(&self, path: &str) -> String {
+        if !self.canonicalize_paths {
+            return path.to_string();
+        }
+        let canonical = canonicalize_path(path);
+        if canonical != path {
+            debug!("Canonicalized request path {} to {}", path, canonical);
+        }
+        canonical
+    }
+
+    /// Match a request to a route based on host, path and method
+    ///
+    /// The host may carry a port (`example.com:8080`), which is checked against
+    /// routes that require a specific `port`. Routes listing `methods` only match
+    /// requests with one of those methods, compared case-insensitively.
+    pub async fn match_route(&self, host: &str, path: &str, method: &str) -> Option<Route> {
+        self.match_route_with_port(host, None, path, method).await
+    }
+
+    /// Match a request to a route based on host, forwarded port, path and method
+    ///
+    /// A port included in `host` takes precedence over `port`, which is typically
+    /// taken from `X-Forwarded-Port`. The query string and fragment of `path` are
+    /// ignored. `path` is matched as given, so callers canonicalize it first with
+    /// [`Self::request_path`].
+    pub async fn match_route_with_port(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        path: &str,
+        method: &str,
+    ) -> Option<Route> {
+        let path = path_without_query(path);
+        let method = method.to_uppercase();
+
         let config = self.config.read().await;
+        let (hostname, host_port) = self.normalized_host(config.version, host);
+        let hostname_with_port = host_port.map(|port| format!("{}:{}", hostname, port));
+        let port = host_port.or(port);
+        let key_host = match port {
+            Some(port) => format!("{}:{}", hostname, port),
+            None => hostname.clone(),
+        };
+        let key = (key_host, path.to_string(), method.clone());
+
+        let cached = self.cache.lock().unwrap().get(config.version, &key);
+        if let Some(index) = cached {
+            trace!(
+                "Route cache hit for host={}, path={}, method={}",
+                host,
+                path,
+                method
+            );
+            return index.and_then(|index| config.routes.get(index).cloned());
+        }
+
+        self.scan_count.fetch_add(1, Ordering::Relaxed);
+        let (host_matchers, order) = self.compiled_routes(&config);
+        // Routes are tried from most to least specific, so the first match wins
+        let index = order.iter().copied().find(|&index| {
+            let route = &config.routes[index];
+            let host_matcher = &host_matchers[index];
+            // Routes may still spell the port as part of their host
+            (host_matcher.matches(&hostname)
+                || hostname_with_port
+                    .as_deref()
+                    .is_some_and(|host| host_matcher.matches(host)))
+                && route.port.is_none_or(|route_port| port == Some(route_port))
+                && self.match_path(path, &route.path)
+                && route
+                    .methods
+                    .as_ref()
+                    .is_none_or(|methods| methods.iter().any(|m| m.eq_ignore_ascii_case(&method)))
+        });
 
-        for route in &config.routes {
-            if self.match_host(host, &route.host) && self.match_path(path, &route.path) {
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(config.version, key, index);
+
+        match index {
+            Some(index) => {
+                let route = &config.routes[index];
                 debug!("Matched route: host={}, path={}", route.host, route.path);
-                return Some(route.clone());
+                Some(route.clone())
+            }
+            None => {
+                debug!(
+                    "No matching route found for host={}, path={}, method={}",
+                    host, path, method
+                );
+                None
             }
         }
+    }
 
-        debug!("No matching route found for host={}, path={}", host, path);
-        None
+    /// Number of times the route table has been scanned, i.e. match cache misses
+    pub fn scan_count(&self) -> usize {
+        self.scan_count.load(Ordering::Relaxed)
     }
 
-    /// Match a host against a route host pattern
-    fn match_host(&self, request_host: &str, route_host: &str) -> bool {
-        // Exact match
-        if request_host == route_host {
-            trace!("Exact host match: {}", request_host);
-            return true;
+    /// Number of times the route host patterns have been compiled, i.e. once per
+    /// loaded configuration that has been matched against
+    pub fn compile_count(&self) -> usize {
+        self.compile_count.load(Ordering::Relaxed)
+    }
+
+    /// Number of times a request host has been normalized, i.e. host cache misses
+    pub fn normalize_count(&self) -> usize {
+        self.normalize_count.load(Ordering::Relaxed)
+    }
+
+    /// Normalize a request host, reusing the result for hosts seen before
+    fn normalized_host(&self, version: u64, host: &str) -> NormalizedHost {
+        if let Some(normalized) = self.host_cache.lock().unwrap().get(version, host) {
+            return normalized;
         }
 
-        // Wildcard match (*.example.com)
-        if let Some(captures) = WILDCARD_HOST_REGEX.captures(route_host) {
-            if let Some(domain_suffix) = captures.get(1) {
-                let domain_suffix = domain_suffix.as_str();
-                if request_host.ends_with(domain_suffix) && request_host.len() > domain_suffix.len()
-                {
-                    let prefix = &request_host[0..request_host.len() - domain_suffix.len()];
-                    if prefix.ends_with('.') {
-                        trace!(
-                            "Wildcard host match: {} matches pattern {}",
-                            request_host,
-                            route_host
-                        );
-                        return true;
-                    }
-                }
+        self.normalize_count.fetch_add(1, Ordering::Relaxed);
+        let normalized = normalize_host(host);
+        self.host_cache
+            .lock()
+            .unwrap()
+            .insert(version, host.to_string(), normalized.clone());
+        normalized
+    }
+
+    /// Get the compiled host matchers and specificity order for the config,
+    /// compiling them if it has been reloaded since they were built
+    fn compiled_routes(&self, config: &Config) -> (Arc<Vec<HostMatcher>>, Arc<Vec<usize>>) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(compiled) = hosts.as_ref() {
+            if compiled.version == config.version {
+                return (compiled.matchers.clone(), compiled.order.clone());
             }
         }
 
-        false
+        self.compile_count.fetch_add(1, Ordering::Relaxed);
+        let matchers = Arc::new(
+            config
+                .routes
+                .iter()
+                .map(|route| HostMatcher::compile(&route.host))
+                .collect::<Vec<_>>(),
+        );
+        let order = Arc::new(specificity_order(&config.routes));
+        *hosts = Some(CompiledRoutes {
+            version: config.version,
+            matchers: matchers.clone(),
+            order: order.clone(),
+        });
+        (matchers, order)
     }
 
     /// Match a path against a route path pattern
     fn match_path(&self, request_path: &str, route_path: &str) -> bool {
+        // Named segments, e.g. /users/{id}/profile
+        if route_path.contains('{') {
+            let matched = match_path_template(route_path, request_path).is_some();
+            if matched {
+                trace!(
+                    "Template path match: {} matches pattern {}",
+                    request_path,
+                    route_path
+                );
+            }
+            return matched;
+        }
+
         // Exact match
         if request_path == route_path {
             trace!("Exact path match: {}", request_path);
@@ -86,6 +784,48 @@ impl RouteMatcher {
             }
         }
 
+        // Bare prefix match, e.g. /admin for /admin/* but not /administrator
+        if self.wildcard_bare_prefix {
+            if let Some(prefix) = route_path.strip_suffix("/*") {
+                if !prefix.is_empty() && request_path == prefix {
+                    trace!(
+                        "Bare prefix path match: {} matches pattern {}",
+                        request_path,
+                        route_path
+                    );
+                    return true;
+                }
+            }
+        }
+
         false
     }
 }
+
+/// Normalize a request host into the form routes are matched against
+///
+/// The port is split off and the hostname lowercased, without a trailing dot,
+/// so `App.Example.com.:8080` matches routes for `app.example.com`.
+fn normalize_host(host: &str) -> NormalizedHost {
+    let (hostname, port) = split_host_port(host);
+    (canonical_hostname(hostname), port)
+}
+
+/// Lowercase a hostname and drop the trailing dot of a fully qualified name
+fn canonical_hostname(hostname: &str) -> String {
+    let hostname = hostname.strip_suffix('.').unwrap_or(hostname);
+    hostname.to_ascii_lowercase()
+}
+
+/// Split an optional port off a host, e.g. `example.com:8080` or `[::1]:8080`
+fn split_host_port(host: &str) -> (&str, Option<u16>) {
+    if let Some((hostname, port)) = host.rsplit_once(':') {
+        // A bare IPv6 address has colons but no port
+        if !hostname.contains(':') || hostname.ends_with(']') {
+            if let Ok(port) = port.parse::<u16>() {
+                return (hostname, Some(port));
+            }
+        }
+    }
+    (host, None)
+}
@@ -0,0 +1,238 @@
+use crate::breaker::BreakerState;
+use crate::cache::CacheStats;
+use crate::proxy::AppState;
+use crate::types::Route;
+use ::metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::Response,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Default maximum number of distinct routes labeled in metrics
+const DEFAULT_MAX_ROUTES: usize = 500;
+
+/// Label used for routes beyond the cardinality cap
+pub const OTHER_LABEL: &str = "other";
+
+/// Upper bounds in seconds of the session validation latency buckets
+const SESSION_VALIDATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// How often recorded latencies are folded into the histogram between scrapes
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+// Metric names
+const AUTH_REQUESTS: &str = "authgate_auth_requests_total";
+const ROUTE_DECISIONS: &str = "authgate_route_decisions_total";
+const SESSION_VALIDATION_SECONDS: &str = "authgate_session_validation_duration_seconds";
+const CACHE_HITS: &str = "authgate_session_cache_hits_total";
+const CACHE_MISSES: &str = "authgate_session_cache_misses_total";
+const CACHE_SETS: &str = "authgate_session_cache_sets_total";
+const CACHE_HIT_RATIO: &str = "authgate_session_cache_hit_ratio";
+const BREAKER_STATE: &str = "authgate_session_breaker_state";
+
+/// Prometheus recorder, installed as the global `metrics` recorder on first use
+static PROMETHEUS: Lazy<PrometheusHandle> = Lazy::new(|| {
+    let recorder = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(SESSION_VALIDATION_SECONDS.to_string()),
+            SESSION_VALIDATION_BUCKETS,
+        )
+        .expect("Session validation buckets are not empty")
+        .build_recorder();
+    let handle = recorder.handle();
+    if let Err(e) = ::metrics::set_global_recorder(recorder) {
+        warn!("Failed to install the Prometheus metrics recorder: {}", e);
+    }
+
+    describe_counter!(AUTH_REQUESTS, "Forward auth requests received");
+    describe_counter!(ROUTE_DECISIONS, "Forward auth decisions by matched route");
+    describe_histogram!(
+        SESSION_VALIDATION_SECONDS,
+        "Latency of session endpoint calls"
+    );
+    describe_counter!(CACHE_HITS, "Session lookups answered from the cache");
+    describe_counter!(CACHE_MISSES, "Session lookups that missed the cache");
+    describe_counter!(CACHE_SETS, "Sessions stored in the cache");
+    describe_gauge!(
+        CACHE_HIT_RATIO,
+        "Share of session lookups answered from the cache"
+    );
+    describe_gauge!(
+        BREAKER_STATE,
+        "State of the session endpoint circuit breaker"
+    );
+    handle
+});
+
+/// Per-route decision counters shared by all requests
+pub static ROUTE_METRICS: Lazy<RouteMetrics> = Lazy::new(RouteMetrics::new);
+
+/// Install the Prometheus recorder if it isn't yet, returning its handle
+///
+/// Metrics recorded before the recorder is installed are dropped, so every
+/// recording function here installs it first.
+pub fn install() -> &'static PrometheusHandle {
+    &PROMETHEUS
+}
+
+/// Periodically fold recorded latencies into the histogram, so they don't pile
+/// up in memory when nothing scrapes `/metrics`
+pub fn spawn_upkeep() -> JoinHandle<()> {
+    let handle = install();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            handle.run_upkeep();
+        }
+    })
+}
+
+/// Forward auth decisions counted per route, labeled by host and path
+///
+/// At most `max_routes` (`AUTHGATE_METRICS_MAX_ROUTES`) distinct routes get their
+/// own labels; decisions for any further routes are counted under `other`, so
+/// a large or churning route table can't blow up the number of series.
+pub struct RouteMetrics {
+    max_routes: usize,
+    routes: Mutex<HashSet<(String, String)>>,
+}
+
+impl Default for RouteMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RouteMetrics {
+    /// Create a registry with the route cap from the environment
+    pub fn new() -> Self {
+        let max_routes = env::var("AUTHGATE_METRICS_MAX_ROUTES")
+            .ok()
+            .and_then(|max| max.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_ROUTES);
+        Self::with_max_routes(max_routes)
+    }
+
+    /// Create a registry labeling at most `max_routes` distinct routes
+    pub fn with_max_routes(max_routes: usize) -> Self {
+        Self {
+            max_routes,
+            routes: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Get the host and path labels a route's decisions are counted under
+    pub fn labels(&self, route: &Route) -> (String, String) {
+        let mut routes = self.routes.lock().unwrap();
+
+        let key = (route.host.clone(), route.path.clone());
+        if routes.contains(&key) {
+            key
+        } else if routes.len() < self.max_routes {
+            routes.insert(key.clone());
+            key
+        } else {
+            (OTHER_LABEL.to_string(), OTHER_LABEL.to_string())
+        }
+    }
+
+    /// Count a decision for a route
+    pub fn record(&self, route: &Route, decision: &str) {
+        install();
+        let (host, path) = self.labels(route);
+        counter!(
+            ROUTE_DECISIONS,
+            "host" => host,
+            "path" => path,
+            "decision" => decision.to_string()
+        )
+        .increment(1);
+    }
+}
+
+/// Count a forward auth request
+pub fn record_request() {
+    install();
+    counter!(AUTH_REQUESTS).increment(1);
+}
+
+/// Record the latency of a session endpoint call
+pub fn record_session_validation(duration: Duration) {
+    install();
+    histogram!(SESSION_VALIDATION_SECONDS).record(duration.as_secs_f64());
+}
+
+/// Record the session cache counters and hit ratio
+pub fn record_cache_stats(stats: &CacheStats) {
+    install();
+    counter!(CACHE_HITS).absolute(stats.hits);
+    counter!(CACHE_MISSES).absolute(stats.misses);
+    counter!(CACHE_SETS).absolute(stats.sets);
+
+    // Share of lookups answered from the cache, 0 before the first lookup
+    let lookups = stats.hits + stats.misses;
+    let hit_ratio = if lookups == 0 {
+        0.0
+    } else {
+        stats.hits as f64 / lookups as f64
+    };
+    gauge!(CACHE_HIT_RATIO).set(hit_ratio);
+}
+
+/// Record the session endpoint circuit breaker state, as a gauge set to 1 for
+/// the current state
+pub fn record_breaker_state(state: BreakerState) {
+    install();
+    for candidate in [
+        BreakerState::Closed,
+        BreakerState::Open,
+        BreakerState::HalfOpen,
+    ] {
+        gauge!(BREAKER_STATE, "state" => candidate.as_str())
+            .set(f64::from(u8::from(candidate == state)));
+    }
+}
+
+/// Whether `/metrics` is served, from `AUTHGATE_METRICS_ENABLED` (default: `true`)
+pub fn metrics_enabled() -> bool {
+    env::var("AUTHGATE_METRICS_ENABLED")
+        .map(|v| v.to_lowercase() != "false")
+        .unwrap_or(true)
+}
+
+/// Serve the metrics in the Prometheus text format
+///
+/// Answers `404` when turned off with `AUTHGATE_METRICS_ENABLED=false`.
+pub async fn handle_metrics(State(state): State<AppState>) -> Response<axum::body::Body> {
+    if !metrics_enabled() {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(axum::body::Body::empty())
+            .unwrap();
+    }
+
+    // Cache and breaker state belong to the app's auth service, so they are
+    // read when scraped
+    record_cache_stats(&state.auth_service.cache_stats());
+    if let Some(breaker_state) = state.auth_service.breaker_state() {
+        record_breaker_state(breaker_state);
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(axum::body::Body::from(install().render()))
+        .unwrap()
+}
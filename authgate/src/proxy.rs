@@ -1,14 +1,21 @@
-use crate::auth::AuthService;
+use crate::auth::{AuthHeaderNames, AuthService, TeamsHeader};
+use crate::cache::CacheStatus;
 use crate::config::ConfigManager;
-use crate::matcher::RouteMatcher;
-use crate::types::{AuthResult, RequestContext};
+use crate::health::HealthCache;
+use crate::matcher::{path_captures, RouteMatcher};
+use crate::redact::redact_headers;
+use crate::types::{
+    AuthGateError, AuthResult, RateLimitKey, RequestContext, Route, Scope, SessionResponse,
+};
+use crate::webhook::DenialKind;
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, Response, StatusCode},
+    http::{HeaderMap, HeaderName, HeaderValue, Response, StatusCode, Uri},
     response::{IntoResponse, Redirect},
 };
 use http::header;
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tracing::{debug, error, warn};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
@@ -19,6 +26,7 @@ pub struct AppState {
     pub config_manager: Arc<ConfigManager>,
     pub route_matcher: Arc<RouteMatcher>,
     pub auth_service: Arc<AuthService>,
+    pub health: Arc<HealthCache>,
 }
 
 /// Query parameters for the forward auth endpoint
@@ -30,6 +38,10 @@ pub struct ForwardAuthQuery {
     pub forwarded_uri: Option<String>,
     #[serde(rename = "X-Forwarded-Proto")]
     pub forwarded_proto: Option<String>,
+    #[serde(rename = "X-Forwarded-Query")]
+    pub forwarded_query: Option<String>,
+    #[serde(rename = "X-Forwarded-Method")]
+    pub forwarded_method: Option<String>,
 }
 
 /// Handle the forward auth request
@@ -38,14 +50,17 @@ pub async fn handle_forward_auth(
     headers: HeaderMap,
     query: Query<ForwardAuthQuery>,
 ) -> impl IntoResponse {
-    // Extract request information
-    let host = query.forwarded_host.clone().unwrap_or_else(|| {
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_request();
+
+    // Extract request information, taking the client-facing (leftmost) value
+    // when a proxy chain forwards a list
+    let host = first_forwarded_value(query.forwarded_host.as_deref().unwrap_or_else(|| {
         headers
             .get("X-Forwarded-Host")
             .and_then(|h| h.to_str().ok())
             .unwrap_or("unknown-host")
-            .to_string()
-    });
+    }));
 
     let path = query.forwarded_uri.clone().unwrap_or_else(|| {
         headers
@@ -55,41 +70,80 @@ pub async fn handle_forward_auth(
             .to_string()
     });
 
-    let proto = query.forwarded_proto.clone().unwrap_or_else(|| {
+    let proto = first_forwarded_value(query.forwarded_proto.as_deref().unwrap_or_else(|| {
         headers
             .get("X-Forwarded-Proto")
             .and_then(|h| h.to_str().ok())
             .unwrap_or("http")
-            .to_string()
+    }));
+
+    // Some ingresses forward the query string separately from the path
+    let forwarded_query = query.forwarded_query.clone().or_else(|| {
+        headers
+            .get("X-Forwarded-Query")
+            .and_then(|h| h.to_str().ok())
+            .map(|q| q.to_string())
     });
+    let method = authorization_method(query.forwarded_method.as_deref().unwrap_or_else(|| {
+        headers
+            .get("X-Forwarded-Method")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("GET")
+    }));
 
-    let original_url = format!("{}://{}{}", proto, host, path);
+    let request_uri = with_forwarded_query(&path, forwarded_query.as_deref());
+
+    let original_url = format!("{}://{}{}", proto, host, request_uri);
     debug!("Processing forward auth request for: {}", original_url);
+    debug!(
+        "Forward auth request headers: {:?}",
+        redact_headers(&headers)
+    );
 
     let callback_domain = std::env::var("AUTHGATE_CALLBACK_DOMAIN").ok();
+    let debug_headers = std::env::var("AUTHGATE_DEBUG_HEADERS")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false);
 
     /// Encodes a string as base64 URL-safe (without padding)
     fn base64_url_encode(input: &str) -> String {
         URL_SAFE_NO_PAD.encode(input)
     }
 
-    let effective_original_url = if let Some(callback_domain) = callback_domain {
-        let encoded = base64_url_encode(&original_url);
-        format!("{}/auth/callback?next={}", callback_domain, encoded)
-    } else {
-        original_url.clone()
+    // Overly long URLs are truncated or left out of login redirects
+    let bounded_url = state.auth_service.bounded_original_url(&original_url);
+    let effective_original_url = bounded_url.map(|url| match &callback_domain {
+        Some(callback_domain) => {
+            let encoded = base64_url_encode(&url);
+            format!("{}/auth/callback?next={}", callback_domain, encoded)
+        }
+        None => url,
+    });
+    let login_redirect = |login_url: &str| match &effective_original_url {
+        Some(url) => state.auth_service.create_login_redirect(login_url, url),
+        None => login_url.to_string(),
     };
 
     // Match route
-    let matched_route = state.route_matcher.match_route(&host, &path).await;
+    let forwarded_port = headers
+        .get("X-Forwarded-Port")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|p| first_forwarded_value(p).parse::<u16>().ok());
+    // Dot segments and repeated slashes are resolved so that e.g.
+    // `/public/../admin` can't slip past a route for `/admin/*`
+    let path = state.route_matcher.request_path(&path);
+    let matched_route = state
+        .route_matcher
+        .match_route_with_port(&host, forwarded_port, &path, &method)
+        .await;
 
     // Get cookie name from config
     let cookie_name = state.config_manager.get_cookie_name().await;
 
-    // Extract session token from cookies
+    // Extract the session token from the configured sources
     let session_token = state
         .auth_service
-        .extract_session_token(&headers, &cookie_name);
+        .find_session_token(&headers, &cookie_name, &request_uri);
 
     // Create request context
     let mut ctx = RequestContext {
@@ -99,6 +153,11 @@ pub async fn handle_forward_auth(
         session_token: session_token.clone(),
         session: None,
         matched_route: matched_route.clone(),
+        method,
+        path_captures: matched_route
+            .as_ref()
+            .map(|route| path_captures(route, &path))
+            .unwrap_or_default(),
     };
 
     // If no matching route, allow the request (no protection needed)
@@ -110,29 +169,43 @@ pub async fn handle_forward_auth(
             .unwrap();
     }
 
-    // If no session token, redirect to login
+    let route = matched_route.as_ref().unwrap();
+
+    // Public routes are allowed without checking for a session
+    if route.public {
+        debug!("Matched public route, allowing request");
+        record_decision(route, "public");
+        return Response::builder()
+            .status(StatusCode::OK)
+            .body(axum::body::Body::empty())
+            .unwrap();
+    }
+
+    // If no session token, redirect to login. This is an ordinary anonymous
+    // visit, so it isn't reported to the denial webhook
     if ctx.session_token.is_none() {
         debug!("No session token found, redirecting to login");
+        record_decision(route, "unauthenticated");
         let config = state.config_manager.get_config().await;
-        let redirect_url = state
-            .auth_service
-            .create_login_redirect(&config.auth.login_redirect, &effective_original_url);
+        let redirect_url = login_redirect(&config.auth.login_redirect);
 
-        return Redirect::to(&redirect_url).into_response();
+        return login_response(&headers, &redirect_url);
     }
 
     // Validate session
     let config = state.config_manager.get_config().await;
     let session_result = state
         .auth_service
-        .validate_session(
-            &config.auth.session_url,
+        .validate_route_session_with_headers(
+            &config.auth,
+            route,
             &ctx.session_token.clone().unwrap(),
+            &headers,
         )
         .await;
 
     match session_result {
-        Ok(session) => {
+        Ok((session, cache_status)) => {
             ctx.session = Some(session);
 
             // Authorize the request
@@ -141,23 +214,75 @@ pub async fn handle_forward_auth(
                     debug!("Request authorized for {}", original_url);
                     let user = &ctx.session.as_ref().unwrap().user;
 
+                    // Enforce the route's rate limit, if any
+                    if let Some(rate_limit) = &route.rate_limit {
+                        let subject = match rate_limit.key {
+                            RateLimitKey::User => user.id.clone(),
+                            RateLimitKey::Ip => client_ip(&headers),
+                        };
+                        if !state
+                            .auth_service
+                            .check_rate_limit(route, rate_limit, &subject)
+                            .await
+                        {
+                            warn!("Rate limit exceeded for {} on {}", subject, original_url);
+                            record_decision(route, "rate_limited");
+                            return Response::builder()
+                                .status(StatusCode::TOO_MANY_REQUESTS)
+                                .header(header::RETRY_AFTER, rate_limit.window_secs)
+                                .header(header::CONTENT_TYPE, "text/plain")
+                                .body(axum::body::Body::from("Too many requests"))
+                                .unwrap();
+                        }
+                    }
+
+                    record_decision(route, "authorized");
+
                     // Build response with user information headers
                     let mut response = Response::builder().status(StatusCode::OK);
 
                     // Add user ID and email headers
+                    let names = state.auth_service.header_names();
                     response = response
-                        .header("X-Auth-User-Id", &user.id)
-                        .header("X-Auth-User-Email", &user.email);
+                        .header(&names.user_id, &user.id)
+                        .header(&names.email, &user.email);
 
                     // Add roles as a comma-separated list
                     if !user.roles.is_empty() {
-                        response = response.header("X-Auth-User-Roles", user.roles.join(","));
+                        response = response.header(&names.roles, user.roles.join(","));
                     }
 
                     // Add permissions as a comma-separated list
                     if !user.permissions.is_empty() {
-                        response =
-                            response.header("X-Auth-User-Permissions", user.permissions.join(","));
+                        response = response.header(&names.permissions, user.permissions.join(","));
+                    }
+
+                    // Add the tenant, teams and scopes for tenant routing and
+                    // fine-grained authorization downstream
+                    response = add_tenant_headers(response, names, ctx.session.as_ref().unwrap());
+
+                    // Tell a shared backend which app the request was routed to
+                    if let Some(audience) = route.audience.as_deref() {
+                        match HeaderValue::from_str(audience) {
+                            Ok(value) => response = response.header(&names.audience, value),
+                            Err(_) => warn!("Not forwarding invalid audience {:?}", audience),
+                        }
+                    }
+
+                    // Forward named path segments, e.g. X-Auth-Path-Id for /users/{id}
+                    for (name, value) in &ctx.path_captures {
+                        match HeaderValue::from_str(value) {
+                            Ok(value) => {
+                                response = response.header(format!("X-Auth-Path-{}", name), value)
+                            }
+                            Err(_) => {
+                                warn!("Not forwarding invalid value of path segment {}", name)
+                            }
+                        }
+                    }
+
+                    if debug_headers {
+                        response = add_cache_debug_headers(response, cache_status);
                     }
 
                     // Return the response with headers
@@ -165,40 +290,452 @@ pub async fn handle_forward_auth(
                 }
                 AuthResult::Unauthorized(reason) => {
                     warn!("Request unauthorized: {}", reason);
-                    Response::builder()
-                        .status(StatusCode::FORBIDDEN)
+                    record_decision(route, "forbidden");
+                    state.auth_service.report_denial(
+                        DenialKind::Forbidden,
+                        &ctx,
+                        &client_ip(&headers),
+                        &reason,
+                    );
+
+                    // Send forbidden users to the route's request access page, if any
+                    if let Some(forbidden_redirect) = route.forbidden_redirect.as_deref() {
+                        return Redirect::to(forbidden_redirect).into_response();
+                    }
+
+                    let mut response = Response::builder().status(StatusCode::FORBIDDEN);
+                    if debug_headers {
+                        response = add_cache_debug_headers(response, cache_status);
+                    }
+                    response
                         .header(header::CONTENT_TYPE, "text/plain")
                         .body(axum::body::Body::from(format!("Forbidden: {}", reason)))
                         .unwrap()
                 }
                 AuthResult::Unauthenticated => {
                     debug!("Session invalid, redirecting to login");
-                    let redirect_url = state
-                        .auth_service
-                        .create_login_redirect(&config.auth.login_redirect, &effective_original_url);
+                    record_decision(route, "unauthenticated");
+                    state.auth_service.report_denial(
+                        DenialKind::Unauthenticated,
+                        &ctx,
+                        &client_ip(&headers),
+                        "Session invalid",
+                    );
+                    let redirect_url = login_redirect(&config.auth.login_redirect);
 
-                    Redirect::to(&redirect_url).into_response()
+                    login_response(&headers, &redirect_url)
                 }
                 AuthResult::Error(err) => {
-                    error!("Authorization error: {}", err);
-                    Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .header(header::CONTENT_TYPE, "text/plain")
-                        .body(axum::body::Body::from(format!(
-                            "Internal server error: {}",
-                            err
-                        )))
-                        .unwrap()
+                    // The route's requirements couldn't be evaluated, usually
+                    // because a stored `require` is corrupt
+                    let policy = invalid_require_policy();
+                    error!(
+                        "Route {} ({}{}) has invalid requirements, answering {:?}: {}",
+                        route
+                            .id
+                            .map_or_else(|| "-".to_string(), |id| id.to_string()),
+                        route.host,
+                        route.path,
+                        policy,
+                        err
+                    );
+                    record_decision(route, "error");
+                    match policy {
+                        InvalidRequirePolicy::Forbidden => Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .header(header::CONTENT_TYPE, "text/plain")
+                            .body(axum::body::Body::from("Forbidden"))
+                            .unwrap(),
+                        InvalidRequirePolicy::Login => {
+                            let redirect_url = login_redirect(&config.auth.login_redirect);
+                            login_response(&headers, &redirect_url)
+                        }
+                        InvalidRequirePolicy::Error => Response::builder()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .header(header::CONTENT_TYPE, "text/plain")
+                            .body(axum::body::Body::from(format!(
+                                "Internal server error: {}",
+                                err
+                            )))
+                            .unwrap(),
+                    }
                 }
             }
         }
+        Err(AuthGateError::CacheError(e)) => {
+            // Only surfaced in strict cache mode
+            error!("Session cache unavailable: {}", e);
+            record_decision(route, "unavailable");
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(axum::body::Body::from("Session cache unavailable"))
+                .unwrap()
+        }
         Err(e) => {
             warn!("Session validation failed: {}", e);
-            let redirect_url = state
-                .auth_service
-                .create_login_redirect(&config.auth.login_redirect, &effective_original_url);
+            record_decision(route, "unauthenticated");
+            state.auth_service.report_denial(
+                DenialKind::Unauthenticated,
+                &ctx,
+                &client_ip(&headers),
+                &e.to_string(),
+            );
+            let redirect_url = login_redirect(&config.auth.login_redirect);
 
-            Redirect::to(&redirect_url).into_response()
+            login_response(&headers, &redirect_url)
         }
     }
 }
+
+/// Handle a logout, removing the caller's session from the session cache
+///
+/// The token is read from the same sources as forward auth requests. Answers
+/// `200` whether or not the session was cached, so it can be called whenever a
+/// user logs out upstream; the session service must still reject the token, or
+/// the next request caches it again.
+pub async fn handle_logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    let cookie_name = state.config_manager.get_cookie_name().await;
+    let request_uri = uri.path_and_query().map_or("/", |path| path.as_str());
+    let Some(session_token) =
+        state
+            .auth_service
+            .find_session_token(&headers, &cookie_name, request_uri)
+    else {
+        return (StatusCode::BAD_REQUEST, "No session token").into_response();
+    };
+
+    let config = state.config_manager.get_config().await;
+    match state
+        .auth_service
+        .invalidate_session_with_headers(&config.routes, &session_token, &headers)
+        .await
+    {
+        Ok(()) => (StatusCode::OK, "Logged out").into_response(),
+        Err(e) => {
+            error!("Failed to remove session from the cache: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, "Session cache unavailable").into_response()
+        }
+    }
+}
+
+/// Count a forward auth decision for the matched route
+fn record_decision(route: &Route, decision: &str) {
+    #[cfg(feature = "metrics")]
+    crate::metrics::ROUTE_METRICS.record(route, decision);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (route, decision);
+}
+
+/// Response to a request whose route has requirements that can't be evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InvalidRequirePolicy {
+    /// Deny the request with a `403`
+    Forbidden,
+    /// Send the request to the login page
+    Login,
+    /// Answer with a `500`
+    Error,
+}
+
+/// Policy for routes with invalid requirements, from `AUTHGATE_INVALID_REQUIRE`
+///
+/// Either `forbidden` (the default), `login` or `error`. Unknown values are
+/// logged and fail closed with `forbidden`.
+fn invalid_require_policy() -> InvalidRequirePolicy {
+    match std::env::var("AUTHGATE_INVALID_REQUIRE") {
+        Err(_) => InvalidRequirePolicy::Forbidden,
+        Ok(policy) => match policy.trim().to_lowercase().as_str() {
+            "" | "forbidden" => InvalidRequirePolicy::Forbidden,
+            "login" => InvalidRequirePolicy::Login,
+            "error" => InvalidRequirePolicy::Error,
+            other => {
+                warn!(
+                    "AUTHGATE_INVALID_REQUIRE must be forbidden, login or error, got {}",
+                    other
+                );
+                InvalidRequirePolicy::Forbidden
+            }
+        },
+    }
+}
+
+/// Status returned when a request needs authentication, from `AUTHGATE_UNAUTH_STATUS`
+///
+/// Either `303` (the default), redirecting to the login page, or `511`, returning
+/// the login URL in the body for captive-portal-style integrations.
+pub fn unauth_status() -> Result<StatusCode, AuthGateError> {
+    match std::env::var("AUTHGATE_UNAUTH_STATUS") {
+        Err(_) => Ok(StatusCode::SEE_OTHER),
+        Ok(status) => match status.trim() {
+            "" | "303" => Ok(StatusCode::SEE_OTHER),
+            "511" => Ok(StatusCode::NETWORK_AUTHENTICATION_REQUIRED),
+            other => Err(AuthGateError::ConfigError(format!(
+                "AUTHGATE_UNAUTH_STATUS must be 303 or 511, got {}",
+                other
+            ))),
+        },
+    }
+}
+
+/// Send an unauthenticated request to the login page
+///
+/// With `AUTHGATE_UNAUTH_JSON` set, API clients preferring JSON get a `401` with
+/// the login URL in a JSON body and the `Location` header instead, so they can
+/// handle authentication themselves.
+fn login_response(headers: &HeaderMap, redirect_url: &str) -> Response<axum::body::Body> {
+    let unauth_json = std::env::var("AUTHGATE_UNAUTH_JSON")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false);
+    if unauth_json && prefers_json(headers) {
+        let body = serde_json::json!({
+            "error": "unauthenticated",
+            "login_url": redirect_url,
+        });
+        return Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::LOCATION, redirect_url)
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+    }
+
+    match unauth_status() {
+        Ok(StatusCode::NETWORK_AUTHENTICATION_REQUIRED) => Response::builder()
+            .status(StatusCode::NETWORK_AUTHENTICATION_REQUIRED)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(axum::body::Body::from(redirect_url.to_string()))
+            .unwrap(),
+        Ok(_) => Redirect::to(redirect_url).into_response(),
+        Err(e) => {
+            // Rejected at startup, so only reachable if the variable changed since
+            warn!("{}, redirecting instead", e);
+            Redirect::to(redirect_url).into_response()
+        }
+    }
+}
+
+/// Whether the original request came from an API client rather than a browser
+///
+/// True for `X-Requested-With: XMLHttpRequest`, or when the `Accept` header
+/// ranks JSON above HTML, e.g. `application/json` but not `text/html, */*`.
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let xhr = headers
+        .get("X-Requested-With")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("XMLHttpRequest"));
+    if xhr {
+        return true;
+    }
+
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let (mut json_quality, mut html_quality) = (0.0_f32, 0.0_f32);
+    for media_range in accept.split(',') {
+        let mut params = media_range.split(';');
+        let media_type = params.next().unwrap_or_default().trim().to_lowercase();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if media_type == "application/json" || media_type.ends_with("+json") {
+            json_quality = json_quality.max(quality);
+        } else if media_type == "text/html" {
+            html_quality = html_quality.max(quality);
+        }
+    }
+    json_quality > 0.0 && json_quality > html_quality
+}
+
+/// Append a separately forwarded query string to a path that doesn't carry one
+fn with_forwarded_query(path: &str, query: Option<&str>) -> String {
+    match query.map(|q| q.trim_start_matches('?')) {
+        Some(query) if !query.is_empty() && !path.contains('?') => format!("{}?{}", path, query),
+        _ => path.to_string(),
+    }
+}
+
+/// First value of a possibly comma-separated forwarded header, such as
+/// `X-Forwarded-Proto: https, http`
+fn first_forwarded_value(value: &str) -> String {
+    value
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Method a request is authorized as
+///
+/// `HEAD` is authorized with `GET` semantics, so a `HEAD` request can't reach
+/// anything its `GET` counterpart can't. Set `AUTHGATE_HEAD_AS_GET=false` to
+/// authorize `HEAD` requests as themselves.
+fn authorization_method(method: &str) -> String {
+    let method = method.trim().to_uppercase();
+    let head_as_get = std::env::var("AUTHGATE_HEAD_AS_GET")
+        .map(|v| v.to_lowercase() != "false")
+        .unwrap_or(true);
+
+    if method == "HEAD" && head_as_get {
+        "GET".to_string()
+    } else {
+        method
+    }
+}
+
+/// Default number of trusted proxies in front of AuthGate that append to `X-Forwarded-For`
+const DEFAULT_TRUSTED_PROXY_HOPS: usize = 1;
+
+/// Get the client IP address from the forwarded headers
+///
+/// Each trusted proxy appends the address it received the request from to
+/// `X-Forwarded-For`, so the client is `AUTHGATE_TRUSTED_PROXY_HOPS` entries
+/// from the right. Entries further left were sent by the client itself and
+/// could be anything.
+pub(crate) fn client_ip(headers: &HeaderMap) -> String {
+    let hops = std::env::var("AUTHGATE_TRUSTED_PROXY_HOPS")
+        .ok()
+        .and_then(|hops| hops.parse::<usize>().ok())
+        .filter(|hops| *hops > 0)
+        .unwrap_or(DEFAULT_TRUSTED_PROXY_HOPS);
+
+    let forwarded: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .collect();
+
+    // Fewer entries than trusted proxies leaves the leftmost, added by a trusted proxy
+    forwarded
+        .get(forwarded.len().saturating_sub(hops))
+        .copied()
+        .or_else(|| headers.get("X-Real-IP").and_then(|h| h.to_str().ok()))
+        .map(|ip| ip.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Encode scopes for the `X-Auth-Scopes` header, returning whether any were left out
+///
+/// Each scope is written as `resource_type:action:resource_id` and scopes are
+/// comma-separated, with duplicates removed. `%`, `:`, `,` and any bytes that
+/// aren't visible ASCII are percent-encoded, so the value can always be split
+/// unambiguously. Scopes that would take the value past `max_len` bytes are
+/// left out whole.
+fn encode_scopes<'a>(scopes: impl Iterator<Item = &'a Scope>, max_len: usize) -> (String, bool) {
+    let mut encoded = String::new();
+    let mut seen = HashSet::new();
+    let mut truncated = false;
+    for scope in scopes {
+        let scope = format!(
+            "{}:{}:{}",
+            encode_scope_part(&scope.resource_type),
+            encode_scope_part(&scope.action),
+            encode_scope_part(&scope.resource_id)
+        );
+        if !seen.insert(scope.clone()) {
+            continue;
+        }
+        let separator = if encoded.is_empty() { 0 } else { 1 };
+        if encoded.len() + separator + scope.len() > max_len {
+            truncated = true;
+            continue;
+        }
+        if separator > 0 {
+            encoded.push(',');
+        }
+        encoded.push_str(&scope);
+    }
+    (encoded, truncated)
+}
+
+/// Percent-encode a part of a scope for the `X-Auth-Scopes` header
+fn encode_scope_part(part: &str) -> String {
+    let mut encoded = String::with_capacity(part.len());
+    for byte in part.bytes() {
+        if byte.is_ascii_graphic() && !matches!(byte, b'%' | b':' | b',') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Add the `X-Auth-Tenant-Id`, `X-Auth-User-Teams` and `X-Auth-Scopes` headers,
+/// or their configured names
+///
+/// The tenant and teams headers are on by default and the scopes header, which
+/// can grow large, off. Values that aren't valid header values are skipped.
+fn add_tenant_headers(
+    mut response: http::response::Builder,
+    names: &AuthHeaderNames,
+    session: &SessionResponse,
+) -> http::response::Builder {
+    let mut headers = Vec::new();
+
+    if names.tenant_header && !session.tenant_id.is_empty() {
+        headers.push((names.tenant_id.clone(), session.tenant_id.clone()));
+    }
+
+    let teams = &session.user.teams;
+    let teams_value = match names.teams_header {
+        TeamsHeader::Ids => teams.iter().map(|team| team.id.clone()).collect(),
+        TeamsHeader::IdNames => teams
+            .iter()
+            .map(|team| format!("{}:{}", team.id, team.name))
+            .collect(),
+        TeamsHeader::Off => Vec::new(),
+    };
+    if !teams_value.is_empty() {
+        headers.push((names.teams.clone(), teams_value.join(",")));
+    }
+
+    if names.scopes_header {
+        let max_len = names.scopes_header_max_bytes;
+        let (scopes, truncated) =
+            encode_scopes(teams.iter().flat_map(|team| &team.scopes), max_len);
+        if truncated {
+            warn!(
+                "Scopes of user {} exceed {} bytes, truncating {}",
+                session.user.id, max_len, names.scopes
+            );
+            // Appending to a valid header name keeps it valid
+            let truncated_name = format!("{}-truncated", names.scopes);
+            if let Ok(truncated_name) = HeaderName::from_bytes(truncated_name.as_bytes()) {
+                headers.push((truncated_name, "true".to_string()));
+            }
+        }
+        if !scopes.is_empty() {
+            headers.push((names.scopes.clone(), scopes));
+        }
+    }
+
+    for (name, value) in headers {
+        match HeaderValue::from_str(&value) {
+            Ok(value) => response = response.header(name, value),
+            Err(_) => warn!("Not forwarding invalid value of {}", name),
+        }
+    }
+    response
+}
+
+/// Add the `X-Auth-Cache` debug headers describing how the session was resolved
+fn add_cache_debug_headers(
+    response: http::response::Builder,
+    cache_status: CacheStatus,
+) -> http::response::Builder {
+    let response = response.header("X-Auth-Cache", cache_status.as_str());
+    match cache_status {
+        CacheStatus::Hit(Some(ttl)) => response.header("X-Auth-Cache-TTL", ttl.as_secs()),
+        _ => response,
+    }
+}
@@ -0,0 +1,61 @@
+use http::HeaderMap;
+use once_cell::sync::Lazy;
+use std::env;
+use std::fmt;
+
+/// Placeholder logged in place of a sensitive header value
+pub const REDACTED: &str = "[redacted]";
+
+/// Headers whose values are never logged
+const SENSITIVE_HEADERS: &[&str] = &[
+    "cookie",
+    "set-cookie",
+    "authorization",
+    "proxy-authorization",
+];
+
+/// Header name prefixes whose values are never logged
+const SENSITIVE_HEADER_PREFIXES: &[&str] = &["x-auth-"];
+
+/// Additional headers to redact, from `AUTHGATE_LOG_REDACT_HEADERS`
+static EXTRA_SENSITIVE_HEADERS: Lazy<Vec<String>> = Lazy::new(|| {
+    env::var("AUTHGATE_LOG_REDACT_HEADERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+});
+
+/// Check whether a header's value must be kept out of the logs
+pub fn is_sensitive_header(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SENSITIVE_HEADERS.contains(&name.as_str())
+        || SENSITIVE_HEADER_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        || EXTRA_SENSITIVE_HEADERS.contains(&name)
+}
+
+/// Headers formatted for logging with sensitive values redacted
+pub struct RedactedHeaders<'a>(&'a HeaderMap);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value = if is_sensitive_header(name.as_str()) {
+                    REDACTED
+                } else {
+                    value.to_str().unwrap_or("[binary]")
+                };
+                (name.as_str(), value)
+            }))
+            .finish()
+    }
+}
+
+/// Wrap headers so they can be logged without leaking credentials
+pub fn redact_headers(headers: &HeaderMap) -> RedactedHeaders<'_> {
+    RedactedHeaders(headers)
+}
@@ -1,57 +1,235 @@
+use crate::expr::RequireExpr;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgTypeInfo, Decode, Postgres, Type};
+use std::sync::Arc;
 
 /// Main configuration structure for authgate
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     pub auth: AuthConfig,
     pub routes: Vec<Route>,
     #[serde(default)]
     pub cookie_name: Option<String>,
+    /// Requirement applied to routes that don't specify one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<RequireConfig>")]
+    pub default_require: Option<serde_json::Value>,
+    /// Generation of the loaded configuration, bumped on every reload
+    #[serde(skip)]
+    pub version: u64,
 }
 
 /// Authentication configuration
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct AuthConfig {
     pub session_url: String,
     pub login_redirect: String,
+    /// Weighted session endpoints; when set, validation requests are spread across them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub session_urls: Vec<WeightedSessionUrl>,
+}
+
+/// A session endpoint with its share of validation traffic
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct WeightedSessionUrl {
+    pub url: String,
+    #[serde(default = "default_session_url_weight")]
+    pub weight: u32,
+}
+
+fn default_session_url_weight() -> u32 {
+    1
 }
 
 /// Route definition with matching criteria and requirements
-#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct Route {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub id: Option<i32>,
     pub host: String,
     pub path: String,
+    #[serde(default)]
+    #[schemars(with = "RequireConfig")]
     pub require: serde_json::Value,
+    /// Optional rate limit enforced after the request is authorized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    /// Session endpoint used for this route instead of `auth.session_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_url: Option<String>,
+    /// Forwarded port the request must arrive on; any port when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Page that forbidden users are redirected to instead of receiving a 403
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forbidden_redirect: Option<String>,
+    /// Request methods the route applies to, e.g. `["POST", "PUT"]`; all methods when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+    /// Whether requests are allowed without a session, skipping `require`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
+    /// Longest time in seconds a session validated for this route is cached,
+    /// capping the default or JWT-derived TTL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_cache_ttl: Option<u64>,
+    /// Logical app the route belongs to, forwarded as `X-Auth-Audience` on
+    /// authorized requests so a shared backend can tell traffic sources apart
+    #[serde(default, alias = "app", skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    /// `require.expr`, parsed when the configuration is loaded
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub require_expr: Option<Arc<RequireExpr>>,
+}
+
+/// Rate limit for requests to a route
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RateLimit {
+    /// Maximum number of requests allowed per window
+    pub requests: u64,
+    /// Length of the window in seconds
+    pub window_secs: u64,
+    /// What requests are counted by
+    #[serde(default)]
+    pub key: RateLimitKey,
+}
+
+/// Identity that rate limit counters are kept for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    /// Count requests per authenticated user id
+    #[default]
+    User,
+    /// Count requests per client IP address
+    Ip,
 }
 
 /// Authorization requirements for a route
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct RequireConfig {
     #[serde(default)]
     pub roles: Option<Vec<String>>,
+    /// Whether the user needs any (the default) or all of `roles`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles_match: Option<RoleMatch>,
+    /// Roles that are denied access even if every other requirement passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny_roles: Option<Vec<String>>,
+    /// When true, `/`-separated roles and permissions also satisfy requirements
+    /// for their parent groups, e.g. `/eng/backend` satisfies `/eng`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nested_groups: Option<bool>,
     #[serde(default)]
     pub permissions: Option<Vec<String>>,
+    /// Permissions any one of which grants access whatever the other
+    /// requirements say, except `deny_roles` and `require_mfa`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_permissions: Option<Vec<String>>,
     #[serde(default)]
     pub scopes: Option<Vec<ScopeRequirement>>,
     #[serde(default)]
     pub teams: Option<Vec<TeamRequirement>>,
+    /// Email domains the user must belong to, e.g. `example.com` or `*.example.com`
+    /// to also allow subdomains
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_domains: Option<Vec<String>>,
+    /// Tenants the session must belong to, any valid session from one of them
+    /// passing unless other requirements are set too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenants: Option<Vec<String>>,
+    /// Single tenant the session must belong to, a shorthand for a `tenants`
+    /// list of one that can't be combined with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    /// When true, the user must belong to at least one team
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub any_team: Option<bool>,
+    /// When true, any user with a valid session is allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authenticated: Option<bool>,
+    /// When true, the session must have been authenticated with MFA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_mfa: Option<bool>,
+    /// Boolean expression over role, permission, team and scope predicates,
+    /// e.g. `(role:admin OR team:ops) AND NOT role:contractor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expr: Option<String>,
+    /// Whether a denial reports the first failed requirement (the default) or all of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny_reasons: Option<DenyReasons>,
+    /// Whether the user must pass every requirement (the default) or any one of
+    /// them, falling back to `AUTHGATE_DEFAULT_COMBINE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub combine: Option<CombineMode>,
+}
+
+/// How the roles of a require block are matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleMatch {
+    /// The user needs at least one of the roles
+    #[default]
+    Any,
+    /// The user needs every one of the roles
+    All,
+}
+
+/// How the requirements of a require block combine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CombineMode {
+    /// The user must pass every requirement
+    #[default]
+    #[serde(alias = "and")]
+    All,
+    /// The user must pass at least one requirement
+    #[serde(alias = "or")]
+    Any,
+}
+
+/// Which failed requirements a denial reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DenyReasons {
+    /// Stop at the first failed requirement
+    #[default]
+    First,
+    /// Evaluate every requirement and list each one that failed
+    All,
 }
 
 /// Scope requirement definition
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ScopeRequirement {
     pub resource_type: String,
     pub action: String,
     #[serde(default)]
     pub resource_id: Option<String>,
+    /// Resource IDs any one of which grants the scope, instead of a single `resource_id`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resource_ids: Vec<String>,
+}
+
+impl ScopeRequirement {
+    /// Check whether a user scope for the given resource ID satisfies this requirement
+    ///
+    /// Any resource ID is accepted when neither `resource_id` nor `resource_ids` is set.
+    pub fn allows_resource(&self, resource_id: &str) -> bool {
+        match &self.resource_id {
+            Some(required_resource_id) => required_resource_id == resource_id,
+            None => {
+                self.resource_ids.is_empty() || self.resource_ids.iter().any(|id| id == resource_id)
+            }
+        }
+    }
 }
 
 impl<'r> Decode<'r, sqlx::Postgres> for RequireConfig {
     fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
-        let json: serde_json::Value = Decode::decode(value)?;
+        let json = <serde_json::Value as Decode<Postgres>>::decode(value)?;
         let cfg = serde_json::from_value(json)?;
         Ok(cfg)
     }
@@ -64,7 +242,7 @@ impl Type<Postgres> for RequireConfig {
 }
 
 /// Team requirement definition
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct TeamRequirement {
     #[serde(default)]
     pub id: Option<String>,
@@ -72,20 +250,42 @@ pub struct TeamRequirement {
     pub name: Option<String>,
     #[serde(default)]
     pub scopes: Option<Vec<ScopeRequirement>>,
+    /// Only grant access to owners of the team when `true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<bool>,
 }
 
 /// Session response from the authentication service
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct SessionResponse {
     pub user: User,
     pub tenant_id: String,
     pub authority: String,
     #[serde(default)]
     pub redirect_url: Option<String>,
+    /// Additional claims passed through from the session service, e.g. `acr`, `amr` or `mfa`
+    #[serde(flatten, default)]
+    pub claims: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SessionResponse {
+    /// Check whether the session was authenticated with multiple factors
+    ///
+    /// Recognises an `mfa: true` claim and an `amr` claim containing `mfa` (RFC 8176).
+    pub fn is_mfa(&self) -> bool {
+        if self.claims.get("mfa").and_then(|v| v.as_bool()) == Some(true) {
+            return true;
+        }
+
+        self.claims
+            .get("amr")
+            .and_then(|v| v.as_array())
+            .is_some_and(|amr| amr.iter().any(|m| m.as_str() == Some("mfa")))
+    }
 }
 
 /// User information in the session
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct User {
     pub id: String,
     pub email: String,
@@ -111,6 +311,25 @@ pub struct Scope {
     pub action: String,
 }
 
+impl Scope {
+    /// Whether the scope covers an action, `*` covering every action
+    pub fn covers_action(&self, action: &str) -> bool {
+        self.action == "*" || self.action == action
+    }
+
+    /// Whether the scope covers a resource, `*` covering every resource of its type
+    pub fn covers_resource(&self, resource_id: &str) -> bool {
+        self.resource_id == "*" || self.resource_id == resource_id
+    }
+
+    /// Whether the scope satisfies a scope requirement
+    pub fn satisfies(&self, required: &ScopeRequirement) -> bool {
+        self.resource_type == required.resource_type
+            && self.covers_action(&required.action)
+            && (self.resource_id == "*" || required.allows_resource(&self.resource_id))
+    }
+}
+
 /// Result of an authorization check
 #[derive(Debug, Clone)]
 pub enum AuthResult {
@@ -129,6 +348,13 @@ pub struct RequestContext {
     pub session_token: Option<String>,
     pub session: Option<SessionResponse>,
     pub matched_route: Option<Route>,
+    /// Request method used for authorization, from `X-Forwarded-Method`
+    ///
+    /// `HEAD` is reported as `GET` unless `AUTHGATE_HEAD_AS_GET` is `false`.
+    pub method: String,
+    /// Values of the matched route's named path segments, e.g. `id` for
+    /// `/users/{id}/profile`
+    pub path_captures: Vec<(String, String)>,
 }
 
 /// Error types for the application
@@ -146,6 +372,9 @@ pub enum AuthGateError {
     #[error("Database error: {0}")]
     DatabaseError(String),
 
+    #[error("Database unavailable: {0}")]
+    DatabaseUnavailable(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
@@ -157,4 +386,7 @@ pub enum AuthGateError {
 
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("Cache error: {0}")]
+    CacheError(String),
 }
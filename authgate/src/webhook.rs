@@ -0,0 +1,152 @@
+use crate::types::RequestContext;
+use serde::Serialize;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Default number of denial events waiting to be sent before new ones are dropped
+const DEFAULT_WEBHOOK_QUEUE_SIZE: usize = 1024;
+
+/// Timeout for delivering a single denial event
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a denied forward auth request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DenialKind {
+    /// The session token was rejected by the session endpoint
+    Unauthenticated,
+    /// The session doesn't meet the route's requirements
+    Forbidden,
+}
+
+/// Event posted to the denial webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct DenialEvent {
+    pub kind: DenialKind,
+    pub host: String,
+    /// Request path, without its query string
+    pub path: String,
+    pub method: String,
+    pub client_ip: String,
+    pub reason: String,
+    /// Id of the user, when the session could be validated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// Id of the matched route, for database-backed routes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_id: Option<i32>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl DenialEvent {
+    /// Create an event for a denied request, scrubbing anything that could
+    /// carry its session token
+    ///
+    /// The query string is dropped from the path, as tokens may be passed in
+    /// it, and any occurrence of the token in the reason is redacted.
+    pub fn new(kind: DenialKind, ctx: &RequestContext, client_ip: &str, reason: &str) -> Self {
+        let path = ctx.path.split(['?', '#']).next().unwrap_or_default();
+        let reason = match ctx.session_token.as_deref() {
+            Some(token) if !token.is_empty() => reason.replace(token, "[REDACTED]"),
+            _ => reason.to_string(),
+        };
+
+        Self {
+            kind,
+            host: ctx.host.clone(),
+            path: path.to_string(),
+            method: ctx.method.clone(),
+            client_ip: client_ip.to_string(),
+            reason,
+            user_id: ctx.session.as_ref().map(|session| session.user.id.clone()),
+            route_id: ctx.matched_route.as_ref().and_then(|route| route.id),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// Sends denial events to a webhook without holding up requests
+///
+/// Events are queued and posted as JSON by a background task, one at a time.
+/// When the queue is full, e.g. because the webhook is slow or down, new events
+/// are dropped and counted rather than waiting for room.
+pub struct DenialWebhook {
+    sender: mpsc::Sender<DenialEvent>,
+    dropped: AtomicU64,
+}
+
+impl DenialWebhook {
+    /// Create a webhook posting to `url`, holding at most `queue_size` pending events
+    ///
+    /// Must be called within a Tokio runtime, which runs the delivery task.
+    pub fn new(url: &str, queue_size: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<DenialEvent>(queue_size.max(1));
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .expect("Failed to create HTTP client");
+        let url = url.to_string();
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                match client.post(&url).json(&event).send().await {
+                    Ok(response) if response.status().is_success() => {
+                        debug!("Sent denial event for {}{}", event.host, event.path);
+                    }
+                    Ok(response) => {
+                        warn!("Denial webhook answered with status {}", response.status());
+                    }
+                    Err(e) => warn!("Failed to send denial event: {}", e),
+                }
+            }
+        });
+
+        Self {
+            sender,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Create the webhook configured by `AUTHGATE_DENIAL_WEBHOOK_URL` and
+    /// `AUTHGATE_DENIAL_WEBHOOK_QUEUE_SIZE`, if any
+    ///
+    /// Without a Tokio runtime to deliver events, no webhook is created.
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("AUTHGATE_DENIAL_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())?;
+        if tokio::runtime::Handle::try_current().is_err() {
+            warn!("No async runtime available, denial events will not be sent");
+            return None;
+        }
+
+        let queue_size = env::var("AUTHGATE_DENIAL_WEBHOOK_QUEUE_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_WEBHOOK_QUEUE_SIZE);
+        Some(Self::new(&url, queue_size))
+    }
+
+    /// Queue an event for delivery, dropping it if the queue is full
+    pub fn emit(&self, event: DenialEvent) {
+        if self.sender.try_send(event).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                "Denial webhook queue is full, dropped {} events so far",
+                dropped
+            );
+        }
+    }
+
+    /// Number of events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
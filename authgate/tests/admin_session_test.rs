@@ -1,16 +1,39 @@
 #[cfg(test)]
 mod tests {
-    use authgate::admin::create_admin_router_with_enabled;
+    use async_trait::async_trait;
+    use authgate::admin::{create_admin_router_with_enabled, AdminState};
+    use authgate::auth::AuthService;
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::ConfigProvider;
+    use authgate::types::{AuthGateError, Config, SessionResponse, User};
     use axum::{
         body::Body,
         extract::Request,
         http::{header, StatusCode},
+        routing::get,
+        Json, Router,
     };
     use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use tower::util::ServiceExt;
 
+    /// Serializes tests, which all configure admin authentication through the environment
+    static ADMIN_ENV: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Admin state around `auth_service`, with the default session cookie
+    fn admin_state(auth_service: Arc<AuthService>) -> AdminState {
+        AdminState {
+            config_manager: Arc::new(ConfigManager::with_provider(Arc::new(CookieNameProvider(
+                "session",
+            )))),
+            auth_service,
+        }
+    }
+
     #[tokio::test]
     async fn test_admin_api_token_auth() {
+        let _env = ADMIN_ENV.lock().await;
         // Clear any existing environment variables
         env::remove_var("AUTHGATE_ENABLE_ADMIN_API");
         env::remove_var("AUTHGATE_CONFIG_BACKEND");
@@ -25,7 +48,8 @@ mod tests {
         env::set_var("AUTHGATE_ADMIN_TOKEN", "test-token");
 
         // Create the admin router with explicit enabled flag
-        let app = create_admin_router_with_enabled::<()>(true);
+        let app =
+            create_admin_router_with_enabled::<()>(true, admin_state(Arc::new(AuthService::new())));
 
         // Create a request with a valid token
         let request = Request::builder()
@@ -43,6 +67,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_admin_api_invalid_token() {
+        let _env = ADMIN_ENV.lock().await;
         // Clear any existing environment variables
         env::remove_var("AUTHGATE_ENABLE_ADMIN_API");
         env::remove_var("AUTHGATE_CONFIG_BACKEND");
@@ -57,7 +82,8 @@ mod tests {
         env::set_var("AUTHGATE_ADMIN_TOKEN", "test-token");
 
         // Create the admin router with explicit enabled flag
-        let app = create_admin_router_with_enabled::<()>(true);
+        let app =
+            create_admin_router_with_enabled::<()>(true, admin_state(Arc::new(AuthService::new())));
 
         // Create a request with an invalid token
         let request = Request::builder()
@@ -81,6 +107,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_admin_api_session_auth() {
+        let _env = ADMIN_ENV.lock().await;
         // Clear any existing environment variables
         env::remove_var("AUTHGATE_ENABLE_ADMIN_API");
         env::remove_var("AUTHGATE_CONFIG_BACKEND");
@@ -102,7 +129,8 @@ mod tests {
         // In a real integration test, we would set up a mock server to handle these requests.
 
         // For now, we'll just test that the endpoint returns 401 when no authentication is provided
-        let app = create_admin_router_with_enabled::<()>(true);
+        let app =
+            create_admin_router_with_enabled::<()>(true, admin_state(Arc::new(AuthService::new())));
 
         // Create a request with no authentication
         let request = Request::builder()
@@ -116,4 +144,129 @@ mod tests {
         // Check that the response is 401 Unauthorized
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
+
+    /// Spawn a session endpoint counting its calls, for sessions with the admin role
+    async fn spawn_counting_session_server(calls: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/session",
+            get(move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Json(SessionResponse {
+                    user: User {
+                        id: "admin-1".to_string(),
+                        email: "admin@example.com".to_string(),
+                        roles: vec!["admin".to_string()],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/session", addr)
+    }
+
+    #[tokio::test]
+    async fn test_admin_session_auth_reuses_auth_service() {
+        let _env = ADMIN_ENV.lock().await;
+        env::remove_var("AUTHGATE_ADMIN_TOKEN");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let session_url = spawn_counting_session_server(calls.clone()).await;
+        env::set_var("AUTHGATE_SESSION_URL", &session_url);
+        env::set_var("AUTHGATE_SESSION_COOKIE", "session");
+        env::set_var("AUTHGATE_ADMIN_SESSION_ROLES", "admin");
+
+        let auth_service = Arc::new(AuthService::new());
+        let app = create_admin_router_with_enabled::<()>(true, admin_state(auth_service.clone()));
+        for _ in 0..3 {
+            let request = Request::builder()
+                .uri("/health")
+                .header(header::COOKIE, "session=reused-admin-session")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // A service built per request would start with an empty cache every time
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        // And the sessions were validated by the app's own service
+        assert_eq!(auth_service.cache_stats().sets, 1);
+
+        env::remove_var("AUTHGATE_SESSION_URL");
+        env::remove_var("AUTHGATE_SESSION_COOKIE");
+        env::remove_var("AUTHGATE_ADMIN_SESSION_ROLES");
+    }
+
+    /// Config provider serving a configuration with a custom session cookie name
+    struct CookieNameProvider(&'static str);
+
+    #[async_trait]
+    impl ConfigProvider for CookieNameProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            Ok(Config {
+                cookie_name: Some(self.0.to_string()),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_and_proxy_share_cookie_name() {
+        let _env = ADMIN_ENV.lock().await;
+        env::remove_var("AUTHGATE_ADMIN_TOKEN");
+        env::remove_var("AUTHGATE_SESSION_COOKIE");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let session_url = spawn_counting_session_server(calls.clone()).await;
+        env::set_var("AUTHGATE_SESSION_URL", &session_url);
+        env::set_var("AUTHGATE_ADMIN_SESSION_ROLES", "admin");
+
+        let config_manager =
+            ConfigManager::with_provider(Arc::new(CookieNameProvider("app_session")));
+        config_manager.load_config().await.unwrap();
+        assert_eq!(config_manager.get_cookie_name().await, "app_session");
+        assert!(!config_manager.check_cookie_name().await);
+
+        let config_manager = Arc::new(config_manager);
+        let app = create_admin_router_with_enabled::<()>(
+            true,
+            AdminState {
+                config_manager: config_manager.clone(),
+                auth_service: Arc::new(AuthService::new()),
+            },
+        );
+        let health = |cookie: &str| {
+            let request = Request::builder()
+                .uri("/health")
+                .header(header::COOKIE, cookie.to_string())
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        // The Admin API reads the cookie named by the configuration, like the proxy
+        let response = health("app_session=shared-cookie-session").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = health("session=default-cookie-session").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // The environment overrides it for both, and the conflict is reported
+        env::set_var("AUTHGATE_SESSION_COOKIE", "override_session");
+        assert_eq!(config_manager.get_cookie_name().await, "override_session");
+        assert!(config_manager.check_cookie_name().await);
+        let response = health("override_session=overridden-cookie-session")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = health("app_session=shared-cookie-session").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        env::remove_var("AUTHGATE_SESSION_URL");
+        env::remove_var("AUTHGATE_SESSION_COOKIE");
+        env::remove_var("AUTHGATE_ADMIN_SESSION_ROLES");
+    }
 }
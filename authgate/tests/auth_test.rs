@@ -1,10 +1,25 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
-    use authgate::auth::AuthService;
+    use crate::common::capture_logs;
+    use async_trait::async_trait;
+    use authgate::auth::{
+        AuthService, LongUrlPolicy, RevalidationSampler, SamplingMode, TokenSource,
+    };
+    use authgate::breaker::{BreakerState, CircuitBreaker};
+    use authgate::cache::{CacheStatus, InMemoryCache, SessionCache};
     use authgate::types::{
-        AuthResult, RequestContext, RequireConfig, Route, Scope, ScopeRequirement, SessionResponse,
-        Team, TeamRequirement, User,
+        AuthConfig, AuthGateError, AuthResult, RateLimit, RateLimitKey, RequestContext, Route,
+        Scope, SessionResponse, Team, User, WeightedSessionUrl,
     };
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     #[test]
     fn test_role_authorization() {
@@ -25,6 +40,7 @@ mod tests {
                 "scopes": null,
                 "teams": null
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -35,6 +51,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -65,6 +83,7 @@ mod tests {
                 "scopes": null,
                 "teams": null
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -75,6 +94,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -86,6 +107,96 @@ mod tests {
         }
     }
 
+    /// Authorize a session holding `roles` against a route requiring `require`
+    fn authorize_roles(roles: &[&str], require: serde_json::Value) -> AuthResult {
+        let session =
+            create_test_session(roles.iter().map(|role| role.to_string()).collect(), vec![]);
+        let route = Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/billing/*".to_string(),
+            require,
+            ..Default::default()
+        };
+        let ctx = RequestContext {
+            original_url: "https://app.example.com/billing/invoices".to_string(),
+            host: "app.example.com".to_string(),
+            path: "/billing/invoices".to_string(),
+            session_token: Some("test-token".to_string()),
+            session: Some(session),
+            matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
+        };
+        AuthService::new().authorize(&ctx)
+    }
+
+    #[test]
+    fn test_all_roles_authorization() {
+        let require = serde_json::json!({
+            "roles": ["billing", "admin"],
+            "roles_match": "all"
+        });
+
+        assert!(matches!(
+            authorize_roles(&["admin", "billing", "user"], require.clone()),
+            AuthResult::Authorized
+        ));
+
+        // Missing one of the required roles
+        match authorize_roles(&["admin", "user"], require) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("all of the required roles"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_denied_role_overrides_allowed_roles() {
+        let require = serde_json::json!({
+            "roles": ["member"],
+            "deny_roles": ["suspended"]
+        });
+
+        assert!(matches!(
+            authorize_roles(&["member"], require.clone()),
+            AuthResult::Authorized
+        ));
+
+        // Holding an allowed role doesn't help a user with a denied role
+        match authorize_roles(&["member", "suspended"], require) {
+            AuthResult::Unauthorized(reason) => assert!(reason.contains("suspended")),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+
+        // Denied roles are checked before anything else, even `authenticated`
+        match authorize_roles(
+            &["suspended"],
+            serde_json::json!({ "authenticated": true, "deny_roles": ["suspended"] }),
+        ) {
+            AuthResult::Unauthorized(reason) => assert!(reason.contains("denied role")),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_roles_match_any_by_default() {
+        let roles = serde_json::json!(["billing", "admin"]);
+
+        assert!(matches!(
+            authorize_roles(&["admin"], serde_json::json!({ "roles": roles })),
+            AuthResult::Authorized
+        ));
+        assert!(matches!(
+            authorize_roles(
+                &["admin"],
+                serde_json::json!({ "roles": roles, "roles_match": "any" })
+            ),
+            AuthResult::Authorized
+        ));
+    }
+
     #[test]
     fn test_permission_authorization() {
         let auth_service = AuthService::new();
@@ -106,6 +217,7 @@ mod tests {
                 "scopes": null,
                 "teams": null
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -116,6 +228,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -127,6 +241,77 @@ mod tests {
         }
     }
 
+    fn email_domain_context(email: &str, domains: serde_json::Value) -> RequestContext {
+        let mut session = create_test_session(vec![], vec![]);
+        session.user.email = email.to_string();
+
+        let route = Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/internal".to_string(),
+            require: serde_json::json!({ "email_domains": domains }),
+            ..Default::default()
+        };
+
+        RequestContext {
+            original_url: "https://app.example.com/internal".to_string(),
+            host: "app.example.com".to_string(),
+            path: "/internal".to_string(),
+            session_token: Some("test-token".to_string()),
+            session: Some(session),
+            matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_email_domain_authorization() {
+        let auth_service = AuthService::new();
+
+        // Domains compare case-insensitively
+        let ctx = email_domain_context("Jane@Example.COM", serde_json::json!(["example.com"]));
+        match auth_service.authorize(&ctx) {
+            AuthResult::Authorized => {}
+            other => panic!("Expected Authorized, got {:?}", other),
+        }
+
+        // Subdomains only match wildcard entries
+        let ctx = email_domain_context("jane@eu.example.com", serde_json::json!(["*.example.com"]));
+        match auth_service.authorize(&ctx) {
+            AuthResult::Authorized => {}
+            other => panic!("Expected Authorized, got {:?}", other),
+        }
+        let ctx = email_domain_context("jane@eu.example.com", serde_json::json!(["example.com"]));
+        assert!(matches!(
+            auth_service.authorize(&ctx),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_email_domain_authorization_failure() {
+        let auth_service = AuthService::new();
+
+        let ctx = email_domain_context("mallory@example.org", serde_json::json!(["example.com"]));
+        match auth_service.authorize(&ctx) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("required domains"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+
+        // A look-alike domain is not a subdomain
+        let ctx = email_domain_context(
+            "mallory@evilexample.com",
+            serde_json::json!(["*.example.com"]),
+        );
+        assert!(matches!(
+            auth_service.authorize(&ctx),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
     #[test]
     fn test_scope_authorization() {
         let auth_service = AuthService::new();
@@ -155,6 +340,7 @@ mod tests {
                 }],
                 "teams": null
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -165,6 +351,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -176,6 +364,140 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scope_authorization_with_resource_ids() {
+        let mut session = create_test_session(vec![], vec![]);
+        session.user.teams[0].scopes.push(Scope {
+            resource_type: "client".to_string(),
+            resource_id: "client-2".to_string(),
+            action: "access".to_string(),
+        });
+        let authorize = |resource_ids: serde_json::Value| {
+            let route = Route {
+                id: None,
+                host: "app.example.com".to_string(),
+                path: "/clients/*".to_string(),
+                require: serde_json::json!({
+                    "scopes": [{
+                        "resource_type": "client",
+                        "action": "access",
+                        "resource_ids": resource_ids
+                    }]
+                }),
+                ..Default::default()
+            };
+            let ctx = RequestContext {
+                original_url: "https://app.example.com/clients/client-2".to_string(),
+                host: "app.example.com".to_string(),
+                path: "/clients/client-2".to_string(),
+                session_token: Some("test-token".to_string()),
+                session: Some(session.clone()),
+                matched_route: Some(route),
+                method: "GET".to_string(),
+                path_captures: Vec::new(),
+            };
+            AuthService::new().authorize(&ctx)
+        };
+
+        // The user's scope matches one of the allowed ids
+        assert!(matches!(
+            authorize(serde_json::json!(["client-1", "client-2", "client-3"])),
+            AuthResult::Authorized
+        ));
+
+        // None of the allowed ids match
+        assert!(matches!(
+            authorize(serde_json::json!(["client-1", "client-3"])),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_scope_authorization() {
+        let authorize = |scope: Scope, required: serde_json::Value| {
+            let mut session = create_test_session(vec![], vec![]);
+            session.user.teams[0].scopes.push(scope);
+            let route = Route {
+                id: None,
+                host: "app.example.com".to_string(),
+                path: "/reports/*".to_string(),
+                require: serde_json::json!({ "scopes": [required] }),
+                ..Default::default()
+            };
+            let ctx = RequestContext {
+                original_url: "https://app.example.com/reports/123".to_string(),
+                host: "app.example.com".to_string(),
+                path: "/reports/123".to_string(),
+                session_token: Some("test-token".to_string()),
+                session: Some(session),
+                matched_route: Some(route),
+                method: "GET".to_string(),
+                path_captures: Vec::new(),
+            };
+            AuthService::new().authorize(&ctx)
+        };
+        let scope = |resource_id: &str, action: &str| Scope {
+            resource_type: "report".to_string(),
+            resource_id: resource_id.to_string(),
+            action: action.to_string(),
+        };
+        let view_report = serde_json::json!({
+            "resource_type": "report",
+            "action": "view",
+            "resource_id": "123"
+        });
+
+        // A wildcard action covers the specific action required
+        assert!(matches!(
+            authorize(scope("123", "*"), view_report.clone()),
+            AuthResult::Authorized
+        ));
+
+        // A wildcard resource covers the specific resource required
+        assert!(matches!(
+            authorize(scope("*", "view"), view_report.clone()),
+            AuthResult::Authorized
+        ));
+        assert!(matches!(
+            authorize(
+                scope("*", "view"),
+                serde_json::json!({
+                    "resource_type": "report",
+                    "action": "view",
+                    "resource_ids": ["123", "456"]
+                })
+            ),
+            AuthResult::Authorized
+        ));
+
+        // Wildcards stay within their resource type and dimension
+        assert!(matches!(
+            authorize(
+                Scope {
+                    resource_type: "invoice".to_string(),
+                    resource_id: "*".to_string(),
+                    action: "*".to_string(),
+                },
+                view_report.clone()
+            ),
+            AuthResult::Unauthorized(_)
+        ));
+        assert!(matches!(
+            authorize(scope("456", "*"), view_report.clone()),
+            AuthResult::Unauthorized(_)
+        ));
+
+        // Specific scopes still need to match exactly
+        assert!(matches!(
+            authorize(scope("123", "view"), view_report.clone()),
+            AuthResult::Authorized
+        ));
+        assert!(matches!(
+            authorize(scope("123", "edit"), view_report),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
     #[test]
     fn test_team_authorization() {
         let auth_service = AuthService::new();
@@ -199,6 +521,7 @@ mod tests {
                     "scopes": null
                 }]
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -209,6 +532,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -220,6 +545,129 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_team_owner_authorization() {
+        let auth_service = AuthService::new();
+        let route = Route {
+            id: None,
+            host: "client.example.com".to_string(),
+            path: "/admin".to_string(),
+            require: serde_json::json!({
+                "teams": [{ "id": "team-1", "owner": true }]
+            }),
+            ..Default::default()
+        };
+        let context = |session: SessionResponse| RequestContext {
+            original_url: "https://client.example.com/admin".to_string(),
+            host: "client.example.com".to_string(),
+            path: "/admin".to_string(),
+            session_token: Some("test-token".to_string()),
+            session: Some(session),
+            matched_route: Some(route.clone()),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
+        };
+
+        // The team owner is let in
+        let owner = create_test_session(vec![], vec![]);
+        assert!(matches!(
+            auth_service.authorize(&context(owner)),
+            AuthResult::Authorized
+        ));
+
+        // A plain member of the same team isn't
+        let mut member = create_test_session(vec![], vec![]);
+        member.user.teams[0].is_owner = false;
+        assert!(matches!(
+            auth_service.authorize(&context(member)),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_tenant_id_authorization() {
+        let auth_service = AuthService::new();
+        let context = |tenant_id: &str| {
+            let mut session = create_test_session(vec!["admin".to_string()], vec![]);
+            session.tenant_id = tenant_id.to_string();
+            RequestContext {
+                original_url: "https://client.example.com/".to_string(),
+                host: "client.example.com".to_string(),
+                path: "/".to_string(),
+                session_token: Some("test-token".to_string()),
+                session: Some(session),
+                matched_route: Some(Route {
+                    id: None,
+                    host: "client.example.com".to_string(),
+                    path: "/".to_string(),
+                    require: serde_json::json!({ "tenant_id": "tenant-1", "roles": ["admin"] }),
+                    ..Default::default()
+                }),
+                method: "GET".to_string(),
+                path_captures: Vec::new(),
+            }
+        };
+
+        assert!(matches!(
+            auth_service.authorize(&context("tenant-1")),
+            AuthResult::Authorized
+        ));
+
+        // The same user from another tenant is turned away
+        match auth_service.authorize(&context("tenant-2")) {
+            AuthResult::Unauthorized(reason) => assert!(reason.contains("tenant-2")),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    fn any_team_context(session: SessionResponse) -> RequestContext {
+        let route = Route {
+            id: None,
+            host: "client.example.com".to_string(),
+            path: "/".to_string(),
+            require: serde_json::json!({ "any_team": true }),
+            ..Default::default()
+        };
+
+        RequestContext {
+            original_url: "https://client.example.com/".to_string(),
+            host: "client.example.com".to_string(),
+            path: "/".to_string(),
+            session_token: Some("test-token".to_string()),
+            session: Some(session),
+            matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_any_team_authorization() {
+        let auth_service = AuthService::new();
+
+        let ctx = any_team_context(create_test_session(vec![], vec![]));
+        match auth_service.authorize(&ctx) {
+            AuthResult::Authorized => {}
+            other => panic!("Expected Authorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_any_team_authorization_failure() {
+        let auth_service = AuthService::new();
+
+        let mut session = create_test_session(vec![], vec![]);
+        session.user.teams.clear();
+
+        let ctx = any_team_context(session);
+        match auth_service.authorize(&ctx) {
+            AuthResult::Unauthorized(reason) => {
+                assert_eq!(reason, "User is not a member of any team");
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_team_with_scope_authorization() {
         let auth_service = AuthService::new();
@@ -252,6 +700,7 @@ mod tests {
                     }]
                 }]
             }),
+            ..Default::default()
         };
 
         // Create request context
@@ -262,6 +711,8 @@ mod tests {
             session_token: Some("test-token".to_string()),
             session: Some(session),
             matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Test authorization
@@ -285,6 +736,37 @@ mod tests {
         assert!(redirect_url.contains("next="));
     }
 
+    #[test]
+    fn test_bounded_original_url() {
+        let truncating = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_original_url_limit(30, LongUrlPolicy::Truncate);
+        assert_eq!(
+            truncating.bounded_original_url("https://a.example.com/short"),
+            Some("https://a.example.com/short".to_string())
+        );
+        assert_eq!(
+            truncating.bounded_original_url("https://a.example.com/p?query=string"),
+            Some("https://a.example.com/p".to_string())
+        );
+        // Never cut inside a multi-byte character
+        assert_eq!(
+            truncating.bounded_original_url("https://a.example.com/xééééé"),
+            Some("https://a.example.com/xééé".to_string())
+        );
+
+        let omitting = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_original_url_limit(30, LongUrlPolicy::Omit);
+        assert_eq!(
+            omitting.bounded_original_url("https://a.example.com/p?query=string"),
+            None
+        );
+
+        let unlimited = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_original_url_limit(0, LongUrlPolicy::Omit);
+        let long_url = format!("https://a.example.com/{}", "a".repeat(10_000));
+        assert_eq!(unlimited.bounded_original_url(&long_url), Some(long_url));
+    }
+
     #[test]
     fn test_extract_session_token() {
         let auth_service = AuthService::new();
@@ -306,7 +788,330 @@ mod tests {
         assert_eq!(token, None);
     }
 
+    #[test]
+    fn test_token_source_parsing() {
+        assert_eq!(TokenSource::parse("cookie").unwrap(), TokenSource::Cookie);
+        assert_eq!(
+            TokenSource::parse("header").unwrap(),
+            TokenSource::Header("X-Session-Token".to_string())
+        );
+        assert_eq!(
+            TokenSource::parse(" Header:X-Api-Session ").unwrap(),
+            TokenSource::Header("X-Api-Session".to_string())
+        );
+        assert_eq!(
+            TokenSource::parse("query:token").unwrap(),
+            TokenSource::Query("token".to_string())
+        );
+        assert!(TokenSource::parse("query:").is_err());
+        assert_eq!(TokenSource::parse("bearer").unwrap(), TokenSource::Bearer);
+        assert!(TokenSource::parse("bearer:token").is_err());
+        assert!(TokenSource::parse("body").is_err());
+    }
+
+    #[test]
+    fn test_find_session_token() {
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_token_sources(vec![
+                TokenSource::Query("token".to_string()),
+                TokenSource::Header("X-Session-Token".to_string()),
+                TokenSource::Cookie,
+            ]);
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::COOKIE,
+            "session=cookie-token".parse().unwrap(),
+        );
+        assert_eq!(
+            auth_service.find_session_token(&headers, "session", "/app"),
+            Some("cookie-token".to_string())
+        );
+
+        headers.insert("X-Session-Token", "header-token".parse().unwrap());
+        assert_eq!(
+            auth_service.find_session_token(&headers, "session", "/app?token="),
+            Some("header-token".to_string())
+        );
+
+        // Query values are decoded
+        assert_eq!(
+            auth_service.find_session_token(&headers, "session", "/app?a=1&token=q%2Btoken#top"),
+            Some("q+token".to_string())
+        );
+    }
+
+    #[test]
+    fn test_weighted_session_url_distribution() {
+        let auth_service = AuthService::new();
+        let auth = AuthConfig {
+            session_url: "https://auth.example.com/session".to_string(),
+            login_redirect: "https://auth.example.com/login".to_string(),
+            session_urls: vec![
+                WeightedSessionUrl {
+                    url: "https://auth-a.example.com/session".to_string(),
+                    weight: 3,
+                },
+                WeightedSessionUrl {
+                    url: "https://auth-b.example.com/session".to_string(),
+                    weight: 1,
+                },
+            ],
+        };
+
+        let mut a = 0;
+        let mut b = 0;
+        for _ in 0..1000 {
+            match auth_service.select_session_url(&auth) {
+                "https://auth-a.example.com/session" => a += 1,
+                "https://auth-b.example.com/session" => b += 1,
+                other => panic!("Unexpected session URL: {}", other),
+            }
+        }
+
+        // Traffic should follow the configured 3:1 split
+        assert!((700..=800).contains(&a), "a received {} requests", a);
+        assert!((200..=300).contains(&b), "b received {} requests", b);
+    }
+
+    #[test]
+    fn test_single_session_url_without_weights() {
+        let auth_service = AuthService::new();
+        let auth = AuthConfig {
+            session_url: "https://auth.example.com/session".to_string(),
+            login_redirect: "https://auth.example.com/login".to_string(),
+            ..Default::default()
+        };
+
+        for _ in 0..10 {
+            assert_eq!(
+                auth_service.select_session_url(&auth),
+                "https://auth.example.com/session"
+            );
+        }
+    }
+
+    #[test]
+    fn test_peek_session_url_keeps_round_robin_order() {
+        let auth_service = AuthService::new();
+        let auth = AuthConfig {
+            session_url: "https://auth.example.com/session".to_string(),
+            login_redirect: "https://auth.example.com/login".to_string(),
+            session_urls: vec![
+                WeightedSessionUrl {
+                    url: "https://auth-a.example.com/session".to_string(),
+                    weight: 1,
+                },
+                WeightedSessionUrl {
+                    url: "https://auth-b.example.com/session".to_string(),
+                    weight: 1,
+                },
+            ],
+        };
+
+        // Peeking any number of times names the next endpoint without taking
+        // its turn
+        for _ in 0..3 {
+            assert_eq!(
+                auth_service.peek_session_url(&auth),
+                "https://auth-a.example.com/session"
+            );
+        }
+        assert_eq!(
+            auth_service.select_session_url(&auth),
+            "https://auth-a.example.com/session"
+        );
+        assert_eq!(
+            auth_service.peek_session_url(&auth),
+            "https://auth-b.example.com/session"
+        );
+        assert_eq!(
+            auth_service.select_session_url(&auth),
+            "https://auth-b.example.com/session"
+        );
+    }
+
+    /// Start a mock session endpoint that always returns the given body
+    async fn spawn_raw_session_server(body: String) -> String {
+        let app = Router::new().route(
+            "/session",
+            get(move || async move { ([(header::CONTENT_TYPE, "application/json")], body) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/session", addr)
+    }
+
+    #[tokio::test]
+    async fn test_invalid_session_payload_is_logged_without_secrets() {
+        std::env::set_var("AUTHGATE_LOG_INVALID_SESSIONS", "true");
+
+        let (logs, _guard) = capture_logs(tracing::Level::DEBUG);
+
+        // Malformed payload that echoes the session token and carries a secret
+        let session_token = "dead-letter-session-token";
+        let body = format!(
+            r#"{{"user": {{"id": "user-1"}}, "access_token": "super-secret-value", "echo": "{}""#,
+            session_token
+        );
+        let session_url = spawn_raw_session_server(body).await;
+
+        let auth_service = AuthService::new();
+        let result = auth_service
+            .validate_session(&session_url, session_token)
+            .await;
+        assert!(result.is_err());
+
+        let output = logs.contents();
+        assert!(output.contains("Undeserializable session response body"));
+        assert!(output.contains("user-1"));
+        assert!(!output.contains("super-secret-value"));
+        assert!(!output.contains(session_token));
+    }
+
+    #[tokio::test]
+    async fn test_session_token_is_kept_out_of_client_logs() {
+        let (logs, _guard) = capture_logs(tracing::Level::TRACE);
+
+        let session_token = "client-log-session-token";
+        let body = serde_json::to_string(&create_test_session(vec![], vec![])).unwrap();
+        let session_url = spawn_raw_session_server(body).await;
+
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()));
+        let result = auth_service
+            .validate_session(&session_url, session_token)
+            .await;
+        assert!(result.is_ok());
+
+        // The outgoing request is traced with its cookie value masked
+        let output = logs.contents();
+        assert!(output.contains("Session validation request headers"));
+        assert!(output.contains("Sensitive"));
+        assert!(!output.contains(session_token));
+    }
+
     // Helper function to create a test session
+    /// Authorize `session` against a route requiring `team-2`, capturing debug logs
+    fn authorize_against_missing_team(session: SessionResponse) -> (AuthResult, String) {
+        let (logs, _guard) = capture_logs(tracing::Level::DEBUG);
+
+        let route = Route {
+            id: None,
+            host: "client.example.com".to_string(),
+            path: "/".to_string(),
+            require: serde_json::json!({
+                "teams": [{ "id": "team-2" }]
+            }),
+            ..Default::default()
+        };
+        let ctx = RequestContext {
+            original_url: "https://client.example.com/".to_string(),
+            host: "client.example.com".to_string(),
+            path: "/".to_string(),
+            session_token: Some("test-token".to_string()),
+            session: Some(session),
+            matched_route: Some(route),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
+        };
+
+        let result = AuthService::new().authorize(&ctx);
+        (result, logs.contents())
+    }
+
+    #[test]
+    fn test_team_mismatch_is_diagnosed() {
+        let session = create_test_session(vec![], vec![]);
+
+        let (result, logs) = authorize_against_missing_team(session);
+        match result {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("does not have access through any of the required teams"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+        assert!(logs.contains("has teams [\"team-1\"] but none matched"));
+        assert!(!logs.contains("has no teams"));
+    }
+
+    #[test]
+    fn test_user_without_teams_is_diagnosed() {
+        let mut session = create_test_session(vec![], vec![]);
+        session.user.teams.clear();
+
+        let (result, logs) = authorize_against_missing_team(session);
+        match result {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("is not a member of any team"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+        assert!(logs.contains("has no teams"));
+        assert!(!logs.contains("but none matched"));
+    }
+
+    /// Session cache whose backend is always unavailable
+    struct FailingCache;
+
+    #[async_trait]
+    impl SessionCache for FailingCache {
+        async fn get(&self, _token: &str) -> Option<SessionResponse> {
+            None
+        }
+
+        async fn try_get_with_ttl(
+            &self,
+            _token: &str,
+        ) -> Result<Option<(SessionResponse, Option<Duration>)>, AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn set(
+            &self,
+            _token: &str,
+            _session: SessionResponse,
+            _ttl: Duration,
+        ) -> Result<(), AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn remove(&self, _token: &str) -> Result<(), AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn increment(&self, _key: &str, _window: Duration) -> Result<u64, AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_errors_fail_open_by_default() {
+        let body = serde_json::to_string(&create_test_session(vec![], vec![])).unwrap();
+        let session_url = spawn_raw_session_server(body).await;
+
+        let auth_service = AuthService::with_cache(Arc::new(FailingCache)).with_strict_cache(false);
+        let session = auth_service
+            .validate_session(&session_url, "test-token")
+            .await
+            .unwrap();
+        assert_eq!(session.user.id, "user-1");
+    }
+
+    #[tokio::test]
+    async fn test_cache_errors_fail_in_strict_mode() {
+        let body = serde_json::to_string(&create_test_session(vec![], vec![])).unwrap();
+        let session_url = spawn_raw_session_server(body).await;
+
+        let auth_service = AuthService::with_cache(Arc::new(FailingCache)).with_strict_cache(true);
+        let result = auth_service
+            .validate_session(&session_url, "test-token")
+            .await;
+        assert!(matches!(result, Err(AuthGateError::CacheError(_))));
+    }
+
     fn create_test_session(roles: Vec<String>, permissions: Vec<String>) -> SessionResponse {
         SessionResponse {
             user: User {
@@ -324,6 +1129,761 @@ mod tests {
             tenant_id: "tenant-1".to_string(),
             authority: "example.com".to_string(),
             redirect_url: None,
+            ..Default::default()
+        }
+    }
+
+    /// Cache recording the order of shutdown calls
+    #[derive(Default)]
+    struct ShutdownCache {
+        calls: std::sync::Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl SessionCache for ShutdownCache {
+        async fn get(&self, _token: &str) -> Option<SessionResponse> {
+            None
+        }
+
+        async fn set(
+            &self,
+            _token: &str,
+            _session: SessionResponse,
+            _ttl: Duration,
+        ) -> Result<(), AuthGateError> {
+            Ok(())
+        }
+
+        async fn remove(&self, _token: &str) -> Result<(), AuthGateError> {
+            Ok(())
+        }
+
+        async fn increment(&self, _key: &str, _window: Duration) -> Result<u64, AuthGateError> {
+            Ok(1)
+        }
+
+        async fn persist(&self) -> Result<(), AuthGateError> {
+            self.calls.lock().unwrap().push("persist");
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), AuthGateError> {
+            self.calls.lock().unwrap().push("close");
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_persists_then_closes_cache() {
+        let cache = Arc::new(ShutdownCache::default());
+        let auth_service = AuthService::with_cache(cache.clone());
+
+        auth_service.shutdown().await;
+
+        assert_eq!(*cache.calls.lock().unwrap(), vec!["persist", "close"]);
+    }
+
+    /// Mock session endpoint answering with `session` while `valid` is set and 401 otherwise
+    struct GraceSessionServer {
+        url: String,
+        valid: Arc<AtomicBool>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    async fn spawn_grace_session_server(session: SessionResponse) -> GraceSessionServer {
+        spawn_slow_session_server(session, Duration::ZERO).await
+    }
+
+    /// Mock session endpoint taking `delay` to answer each request
+    async fn spawn_slow_session_server(
+        session: SessionResponse,
+        delay: Duration,
+    ) -> GraceSessionServer {
+        let valid = Arc::new(AtomicBool::new(true));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/session",
+            get({
+                let valid = valid.clone();
+                let calls = calls.clone();
+                move || {
+                    let (valid, calls, session) = (valid.clone(), calls.clone(), session.clone());
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(delay).await;
+                        if valid.load(Ordering::SeqCst) {
+                            axum::Json(session).into_response()
+                        } else {
+                            axum::http::StatusCode::UNAUTHORIZED.into_response()
+                        }
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        GraceSessionServer {
+            url: format!("http://{}/session", addr),
+            valid,
+            calls,
+        }
+    }
+
+    /// Wait for background refreshes to reach the session endpoint
+    async fn wait_for_calls(calls: &AtomicUsize, expected: usize) {
+        for _ in 0..100 {
+            if calls.load(Ordering::SeqCst) >= expected {
+                // Give the refresh a moment to update the cache
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Session endpoint was not called {} times", expected);
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_within_grace_is_served_and_refreshed() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        let cache = Arc::new(InMemoryCache::new());
+        let auth_service =
+            AuthService::with_cache(cache.clone()).with_cache_grace(Duration::from_secs(60));
+
+        // Expired 30 seconds ago: cached for the grace period with 30 seconds left
+        let mut stale = create_test_session(vec![], vec![]);
+        stale.user.email = "stale@example.com".to_string();
+        cache
+            .set("grace-token", stale, Duration::from_secs(30))
+            .await
+            .unwrap();
+
+        let (session, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "grace-token")
+            .await
+            .unwrap();
+        assert_eq!(session.user.email, "stale@example.com");
+        assert_eq!(status, CacheStatus::Stale);
+
+        // The background refresh replaces the cached session with a fresh one
+        wait_for_calls(&server.calls, 1).await;
+        let (session, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "grace-token")
+            .await
+            .unwrap();
+        assert_eq!(session.user.email, "user@example.com");
+        assert!(matches!(status, CacheStatus::Hit(Some(_))));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_rejected_on_refresh_is_evicted() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        server.valid.store(false, Ordering::SeqCst);
+        let cache = Arc::new(InMemoryCache::new());
+        let auth_service =
+            AuthService::with_cache(cache.clone()).with_cache_grace(Duration::from_secs(60));
+
+        cache
+            .set(
+                "revoked-token",
+                create_test_session(vec![], vec![]),
+                Duration::from_secs(30),
+            )
+            .await
+            .unwrap();
+
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "revoked-token")
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+
+        // Once the refresh confirms the session is invalid it is no longer served
+        wait_for_calls(&server.calls, 1).await;
+        assert!(cache.get("revoked-token").await.is_none());
+        let result = auth_service
+            .validate_session(&server.url, "revoked-token")
+            .await;
+        assert!(matches!(result, Err(AuthGateError::AuthError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_session_past_grace_is_not_served() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        server.valid.store(false, Ordering::SeqCst);
+        let cache = Arc::new(InMemoryCache::new());
+        let auth_service =
+            AuthService::with_cache(cache.clone()).with_cache_grace(Duration::from_secs(60));
+
+        // Already past the end of its grace period
+        cache
+            .set(
+                "expired-token",
+                create_test_session(vec![], vec![]),
+                Duration::ZERO,
+            )
+            .await
+            .unwrap();
+
+        let result = auth_service
+            .validate_session(&server.url, "expired-token")
+            .await;
+        assert!(matches!(result, Err(AuthGateError::AuthError(_))));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_validated_sessions_are_cached_past_expiry_for_grace() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        let cache = Arc::new(InMemoryCache::new());
+        let auth_service =
+            AuthService::with_cache(cache.clone()).with_cache_grace(Duration::from_secs(60));
+
+        auth_service
+            .validate_session(&server.url, "opaque-token")
+            .await
+            .unwrap();
+
+        // Stored for the default TTL plus the grace period, reported without it
+        let (_, ttl) = cache.get_with_ttl("opaque-token").await.unwrap();
+        assert!(ttl.unwrap() > Duration::from_secs(300));
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "opaque-token")
+            .await
+            .unwrap();
+        match status {
+            CacheStatus::Hit(Some(ttl)) => assert!(ttl <= Duration::from_secs(300)),
+            other => panic!("Expected a cache hit, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_validations_share_one_request() {
+        let server = spawn_slow_session_server(
+            create_test_session(vec![], vec![]),
+            Duration::from_millis(100),
+        )
+        .await;
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()));
+
+        let results = futures::future::join_all(
+            (0..10).map(|_| auth_service.validate_session(&server.url, "popular-token")),
+        )
+        .await;
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 1);
+
+        // Rejections are shared too
+        server.valid.store(false, Ordering::SeqCst);
+        let results = futures::future::join_all(
+            (0..10).map(|_| auth_service.validate_session(&server.url, "revoked-token")),
+        )
+        .await;
+        assert!(results.iter().all(|result| result.is_err()));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 2);
+
+        // Once settled, the next validation sends a new request
+        assert!(auth_service
+            .validate_session(&server.url, "revoked-token")
+            .await
+            .is_err());
+        assert_eq!(server.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_sessions_are_negatively_cached() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        server.valid.store(false, Ordering::SeqCst);
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_negative_cache_ttl(Duration::from_millis(200));
+
+        // A repeatedly invalid token reaches the session endpoint once
+        for _ in 0..5 {
+            let result = auth_service
+                .validate_session(&server.url, "invalid-token")
+                .await;
+            assert!(matches!(result, Err(AuthGateError::AuthError(_))));
+        }
+        assert_eq!(server.calls.load(Ordering::SeqCst), 1);
+
+        // Other tokens are still validated
+        assert!(auth_service
+            .validate_session(&server.url, "other-token")
+            .await
+            .is_err());
+        assert_eq!(server.calls.load(Ordering::SeqCst), 2);
+
+        // Once the rejection expires a now valid session is accepted and cached
+        server.valid.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(auth_service
+            .validate_session(&server.url, "invalid-token")
+            .await
+            .is_ok());
+        assert_eq!(server.calls.load(Ordering::SeqCst), 3);
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "invalid-token")
+            .await
+            .unwrap();
+        assert!(matches!(status, CacheStatus::Hit(_)));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_random_revalidation_sampling_rate() {
+        let sampler = RevalidationSampler::new(20, SamplingMode::Random);
+        let sampled = (0..20_000)
+            .filter(|_| sampler.should_revalidate("popular-token"))
+            .count();
+
+        // 1000 expected
+        assert!((700..=1300).contains(&sampled), "sampled {} hits", sampled);
+    }
+
+    #[test]
+    fn test_per_token_revalidation_sampling() {
+        let sampler = RevalidationSampler::new(5, SamplingMode::PerToken);
+
+        let sampled: Vec<usize> = (1..=20)
+            .filter(|_| sampler.should_revalidate("token-a"))
+            .collect();
+        assert_eq!(sampled, vec![5, 10, 15, 20]);
+
+        // Hits are counted separately for each session
+        for _ in 0..4 {
+            assert!(!sampler.should_revalidate("token-b"));
+        }
+        assert!(!sampler.should_revalidate("token-a"));
+        assert!(sampler.should_revalidate("token-b"));
+    }
+
+    #[tokio::test]
+    async fn test_route_session_cache_ttl_expires_sessions_sooner() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()));
+        let auth = AuthConfig {
+            session_url: server.url.clone(),
+            ..Default::default()
+        };
+        let default_route = Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            ..Default::default()
+        };
+        let short_route = Route {
+            host: "billing.example.com".to_string(),
+            path: "/*".to_string(),
+            session_cache_ttl: Some(1),
+            ..Default::default()
+        };
+
+        for route in [&default_route, &short_route] {
+            let (_, status) = auth_service
+                .validate_route_session(&auth, route, "shared-token")
+                .await
+                .unwrap();
+            assert_eq!(status, CacheStatus::Miss);
+        }
+        let (_, status) = auth_service
+            .validate_route_session(&auth, &short_route, "shared-token")
+            .await
+            .unwrap();
+        match status {
+            CacheStatus::Hit(Some(ttl)) => assert!(ttl <= Duration::from_secs(1)),
+            other => panic!("Expected a cache hit, got {:?}", other),
+        }
+
+        // Once the short TTL passes, a revoked session is only still served
+        // to the default route
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        server.valid.store(false, Ordering::SeqCst);
+        assert!(auth_service
+            .validate_route_session(&auth, &default_route, "shared-token")
+            .await
+            .is_ok());
+        let result = auth_service
+            .validate_route_session(&auth, &short_route, "shared-token")
+            .await;
+        assert!(matches!(result, Err(AuthGateError::AuthError(_))));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sampled_revalidation_evicts_revoked_session() {
+        let server = spawn_grace_session_server(create_test_session(vec![], vec![])).await;
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_revalidation_sampler(RevalidationSampler::new(2, SamplingMode::PerToken));
+
+        auth_service
+            .validate_session(&server.url, "sampled-token")
+            .await
+            .unwrap();
+        server.valid.store(false, Ordering::SeqCst);
+
+        // The first hit is served from the cache without revalidation
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "sampled-token")
+            .await
+            .unwrap();
+        assert!(matches!(status, CacheStatus::Hit(_)));
+        assert_eq!(server.calls.load(Ordering::SeqCst), 1);
+
+        // The second is still served, but revalidated in the background
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&server.url, "sampled-token")
+            .await
+            .unwrap();
+        assert!(matches!(status, CacheStatus::Hit(_)));
+        wait_for_calls(&server.calls, 2).await;
+
+        // The revoked session was evicted
+        assert!(auth_service
+            .validate_session(&server.url, "sampled-token")
+            .await
+            .is_err());
+        assert_eq!(server.calls.load(Ordering::SeqCst), 3);
+    }
+
+    /// Mock session endpoint answering with `session` while `up` is set and
+    /// failing with 503 otherwise, counting its calls
+    async fn spawn_flaky_session_server(
+        session: SessionResponse,
+    ) -> (String, Arc<AtomicBool>, Arc<AtomicUsize>) {
+        let up = Arc::new(AtomicBool::new(false));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/session",
+            get({
+                let up = up.clone();
+                let calls = calls.clone();
+                move || {
+                    let (up, calls, session) = (up.clone(), calls.clone(), session.clone());
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        if up.load(Ordering::SeqCst) {
+                            axum::Json(session).into_response()
+                        } else {
+                            axum::http::StatusCode::SERVICE_UNAVAILABLE.into_response()
+                        }
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/session", addr), up, calls)
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast_while_session_endpoint_is_down() {
+        let (url, up, calls) =
+            spawn_flaky_session_server(create_test_session(vec![], vec![])).await;
+        let cooldown = Duration::from_millis(200);
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60), cooldown));
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::Closed));
+
+        // Consecutive failures trip the breaker
+        for token in ["token-1", "token-2"] {
+            assert!(auth_service.validate_session(&url, token).await.is_err());
         }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::Open));
+
+        // While open, validations fail without calling the session endpoint
+        assert!(auth_service
+            .validate_session(&url, "token-3")
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // After the cool-down, a failing test request reopens it
+        tokio::time::sleep(cooldown).await;
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::HalfOpen));
+        assert!(auth_service
+            .validate_session(&url, "token-4")
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::Open));
+
+        // Once the endpoint recovers, the next test request closes it
+        up.store(true, Ordering::SeqCst);
+        tokio::time::sleep(cooldown).await;
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::HalfOpen));
+        auth_service
+            .validate_session(&url, "token-5")
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::Closed));
+    }
+
+    #[test]
+    fn test_circuit_breaker_lets_one_request_through_when_half_open() {
+        let cooldown = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60), cooldown);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+
+        std::thread::sleep(cooldown);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        // Only one test request at a time
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+        assert!(breaker.allow());
+    }
+
+    /// Mock session endpoint recording the headers of the last request
+    async fn spawn_header_recording_session_server(
+        session: SessionResponse,
+    ) -> (String, Arc<Mutex<header::HeaderMap>>) {
+        let received = Arc::new(Mutex::new(header::HeaderMap::new()));
+        let app = Router::new().route(
+            "/session",
+            get({
+                let received = received.clone();
+                move |headers: header::HeaderMap| {
+                    let (received, session) = (received.clone(), session.clone());
+                    async move {
+                        *received.lock().unwrap() = headers;
+                        axum::Json(session).into_response()
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/session", addr), received)
+    }
+
+    #[tokio::test]
+    async fn test_session_request_carries_extra_and_forwarded_headers() {
+        let (url, received) =
+            spawn_header_recording_session_server(create_test_session(vec![], vec![])).await;
+        let mut session_headers = reqwest::header::HeaderMap::new();
+        session_headers.insert("x-api-key", "gateway-key".parse().unwrap());
+        session_headers.insert("user-agent", "authgate".parse().unwrap());
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_session_headers(session_headers)
+            .with_forwarded_headers(vec![
+                header::HeaderName::from_static("x-forwarded-for"),
+                header::USER_AGENT,
+            ]);
+        let auth = AuthConfig {
+            session_url: url,
+            ..Default::default()
+        };
+        let route = Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            ..Default::default()
+        };
+
+        let mut request_headers = header::HeaderMap::new();
+        request_headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        request_headers.insert(header::USER_AGENT, "curl/8.0".parse().unwrap());
+        request_headers.insert("x-request-id", "abc123".parse().unwrap());
+        auth_service
+            .validate_route_session_with_headers(&auth, &route, "header-token", &request_headers)
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.get("x-api-key").unwrap(), "gateway-key");
+        assert_eq!(received.get("x-forwarded-for").unwrap(), "203.0.113.7");
+        // Static headers take precedence over forwarded ones
+        assert_eq!(received.get(header::USER_AGENT).unwrap(), "authgate");
+        // Headers that aren't listed stay behind
+        assert!(received.get("x-request-id").is_none());
+        assert!(received
+            .get(header::COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("header-token"));
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_headers_key_the_session_cache() {
+        let (url, received) =
+            spawn_header_recording_session_server(create_test_session(vec![], vec![])).await;
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_forwarded_headers(vec![header::HeaderName::from_static("x-forwarded-for")]);
+        let auth = AuthConfig {
+            session_url: url,
+            ..Default::default()
+        };
+        let route = Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            ..Default::default()
+        };
+        let forwarded_for = |address: &str| {
+            let mut headers = header::HeaderMap::new();
+            headers.insert("x-forwarded-for", address.parse().unwrap());
+            headers
+        };
+
+        let (_, status) = auth_service
+            .validate_route_session_with_headers(
+                &auth,
+                &route,
+                "shared-token",
+                &forwarded_for("203.0.113.7"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+
+        // Another client address isn't served the first one's session
+        let (_, status) = auth_service
+            .validate_route_session_with_headers(
+                &auth,
+                &route,
+                "shared-token",
+                &forwarded_for("198.51.100.9"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(
+            received.lock().unwrap().get("x-forwarded-for").unwrap(),
+            "198.51.100.9"
+        );
+
+        let (_, status) = auth_service
+            .validate_route_session_with_headers(
+                &auth,
+                &route,
+                "shared-token",
+                &forwarded_for("203.0.113.7"),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(status, CacheStatus::Hit(_)));
+
+        // Logging out removes the session cached for the same address
+        auth_service
+            .invalidate_session_with_headers(
+                std::slice::from_ref(&route),
+                "shared-token",
+                &forwarded_for("203.0.113.7"),
+            )
+            .await
+            .unwrap();
+        let (_, status) = auth_service
+            .validate_route_session_with_headers(
+                &auth,
+                &route,
+                "shared-token",
+                &forwarded_for("203.0.113.7"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_counts_routes_separately_by_port_and_methods() {
+        let auth_service = AuthService::new();
+        let rate_limit = RateLimit {
+            requests: 1,
+            window_secs: 60,
+            key: RateLimitKey::User,
+        };
+        let route = Route {
+            host: "app.example.com".to_string(),
+            path: "/api/*".to_string(),
+            ..Default::default()
+        };
+        let tls_route = Route {
+            port: Some(8443),
+            ..route.clone()
+        };
+        let write_route = Route {
+            methods: Some(vec!["POST".to_string()]),
+            ..route.clone()
+        };
+
+        assert!(
+            auth_service
+                .check_rate_limit(&route, &rate_limit, "alice")
+                .await
+        );
+        assert!(
+            !auth_service
+                .check_rate_limit(&route, &rate_limit, "alice")
+                .await
+        );
+
+        // Same host and path, but another route's budget
+        assert!(
+            auth_service
+                .check_rate_limit(&tls_route, &rate_limit, "alice")
+                .await
+        );
+        assert!(
+            auth_service
+                .check_rate_limit(&write_route, &rate_limit, "alice")
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_goes_through_circuit_breaker() {
+        let (url, _up, calls) =
+            spawn_flaky_session_server(create_test_session(vec![], vec![])).await;
+        let cache = Arc::new(InMemoryCache::new());
+        let auth_service = AuthService::with_cache(cache.clone())
+            .with_cache_grace(Duration::from_secs(60))
+            .with_circuit_breaker(CircuitBreaker::new(
+                1,
+                Duration::from_secs(60),
+                Duration::from_secs(60),
+            ));
+        for token in ["stale-token-1", "stale-token-2"] {
+            cache
+                .set(
+                    token,
+                    create_test_session(vec![], vec![]),
+                    Duration::from_secs(30),
+                )
+                .await
+                .unwrap();
+        }
+
+        // A failed refresh counts against the breaker
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&url, "stale-token-1")
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        wait_for_calls(&calls, 1).await;
+        assert_eq!(auth_service.breaker_state(), Some(BreakerState::Open));
+
+        // While open, stale sessions are still served but not refreshed
+        let (_, status) = auth_service
+            .validate_session_with_cache_status(&url, "stale-token-2")
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
     }
 }
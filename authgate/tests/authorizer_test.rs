@@ -0,0 +1,443 @@
+#[cfg(test)]
+mod tests {
+    use authgate::authorizer::{authorize, Authorizer};
+    use authgate::types::{AuthResult, CombineMode, Route, Scope, SessionResponse, Team, User};
+
+    fn create_test_session() -> SessionResponse {
+        SessionResponse {
+            user: User {
+                id: "user-1".to_string(),
+                email: "user@example.com".to_string(),
+                roles: vec!["editor".to_string()],
+                permissions: vec!["posts:write".to_string()],
+                teams: vec![Team {
+                    id: "team-1".to_string(),
+                    name: "Team 1".to_string(),
+                    is_owner: false,
+                    scopes: vec![Scope {
+                        resource_type: "client".to_string(),
+                        resource_id: "client-1".to_string(),
+                        action: "access".to_string(),
+                    }],
+                }],
+            },
+            tenant_id: "tenant-1".to_string(),
+            authority: "example.com".to_string(),
+            redirect_url: None,
+            ..Default::default()
+        }
+    }
+
+    fn create_route(require: serde_json::Value) -> Route {
+        Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/".to_string(),
+            require,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_authorizer_without_service() {
+        let authorizer = Authorizer::new();
+        let session = create_test_session();
+
+        let route = create_route(serde_json::json!({
+            "roles": ["editor"],
+            "teams": [{
+                "id": "team-1",
+                "scopes": [{ "resource_type": "client", "action": "access" }]
+            }]
+        }));
+        match authorizer.authorize(&session, &route) {
+            AuthResult::Authorized => {}
+            other => panic!("Expected Authorized, got {:?}", other),
+        }
+
+        let route = create_route(serde_json::json!({ "roles": ["admin"] }));
+        match authorizer.authorize(&session, &route) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("required roles"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_authorize_free_function() {
+        let session = create_test_session();
+
+        let route = create_route(serde_json::json!({ "permissions": ["posts:write"] }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+
+        let route = create_route(serde_json::json!({ "permissions": ["posts:delete"] }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_mfa_session_passes_mfa_route() {
+        let route = create_route(serde_json::json!({ "require_mfa": true }));
+
+        let mut session = create_test_session();
+        session
+            .claims
+            .insert("amr".to_string(), serde_json::json!(["pwd", "mfa"]));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+
+        let mut session = create_test_session();
+        session
+            .claims
+            .insert("mfa".to_string(), serde_json::json!(true));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+    }
+
+    #[test]
+    fn test_non_mfa_session_denied_on_mfa_route() {
+        let route = create_route(serde_json::json!({ "require_mfa": true }));
+
+        // Claims arrive through the session response passthrough
+        let session: SessionResponse = serde_json::from_value(serde_json::json!({
+            "user": {
+                "id": "user-1",
+                "email": "user@example.com",
+                "roles": [],
+                "permissions": [],
+                "teams": []
+            },
+            "tenant_id": "tenant-1",
+            "authority": "example.com",
+            "amr": ["pwd"]
+        }))
+        .unwrap();
+        assert_eq!(session.claims["amr"], serde_json::json!(["pwd"]));
+
+        match authorize(&session, &route) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("step up"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    /// Session whose roles and permissions are hierarchical groups
+    fn create_group_session(groups: &[&str]) -> SessionResponse {
+        let mut session = create_test_session();
+        session.user.roles = groups.iter().map(|g| g.to_string()).collect();
+        session.user.permissions = groups.iter().map(|g| g.to_string()).collect();
+        session
+    }
+
+    #[test]
+    fn test_nested_group_satisfies_parent_group() {
+        let session = create_group_session(&["/eng/backend"]);
+        for require in [
+            serde_json::json!({ "roles": ["/eng"], "nested_groups": true }),
+            serde_json::json!({ "roles": ["/eng/"], "nested_groups": true }),
+            serde_json::json!({
+                "roles": ["/eng", "/eng/backend"],
+                "roles_match": "all",
+                "nested_groups": true
+            }),
+            serde_json::json!({ "permissions": ["/eng"], "nested_groups": true }),
+        ] {
+            assert!(
+                matches!(
+                    authorize(&session, &create_route(require.clone())),
+                    AuthResult::Authorized
+                ),
+                "{}",
+                require
+            );
+        }
+
+        // Exact matching by default
+        let route = create_route(serde_json::json!({ "roles": ["/eng"] }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_nested_group_does_not_satisfy_sibling_or_child() {
+        for (groups, required) in [
+            // Siblings and groups sharing a name prefix
+            (vec!["/eng/backend"], "/eng/frontend"),
+            (vec!["/engineering"], "/eng"),
+            (vec!["/eng-ops/backend"], "/eng"),
+            // A parent group doesn't grant its children
+            (vec!["/eng"], "/eng/backend"),
+        ] {
+            let session = create_group_session(&groups);
+            let route = create_route(serde_json::json!({
+                "roles": [required],
+                "nested_groups": true
+            }));
+            assert!(
+                matches!(authorize(&session, &route), AuthResult::Unauthorized(_)),
+                "{:?} should not satisfy {}",
+                groups,
+                required
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_group_denied_through_parent() {
+        let session = create_group_session(&["/contractors/acme", "/eng"]);
+        let route = create_route(serde_json::json!({
+            "roles": ["/eng"],
+            "deny_roles": ["/contractors"],
+            "nested_groups": true
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_requirements_are_errors() {
+        let session = create_test_session();
+        // A wrongly typed field would otherwise be skipped and grant access
+        for require in [
+            serde_json::json!("roles: admin"),
+            serde_json::json!({ "roles": "admin" }),
+            serde_json::json!({ "roles_match": "most" }),
+            serde_json::json!({ "teams": [{ "id": 1 }] }),
+        ] {
+            let route = create_route(require.clone());
+            assert!(
+                matches!(authorize(&session, &route), AuthResult::Error(_)),
+                "{} should be invalid",
+                require
+            );
+        }
+
+        // No requirements at all are still just a valid session
+        let route = create_route(serde_json::Value::Null);
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+    }
+
+    #[test]
+    fn test_combined_deny_reasons() {
+        let session = create_test_session();
+        let require = serde_json::json!({
+            "roles": ["admin"],
+            "permissions": ["posts:delete"],
+        });
+
+        // By default only the first failed requirement is reported
+        let reason = match authorize(&session, &create_route(require.clone())) {
+            AuthResult::Unauthorized(reason) => reason,
+            other => panic!("expected a denial, got {:?}", other),
+        };
+        assert!(reason.contains("required roles"));
+        assert!(!reason.contains("required permissions"));
+
+        // Reporting all of them lists both dimensions
+        let mut combined = require.clone();
+        combined["deny_reasons"] = serde_json::json!("all");
+        let reason = match authorize(&session, &create_route(combined.clone())) {
+            AuthResult::Unauthorized(reason) => reason,
+            other => panic!("expected a denial, got {:?}", other),
+        };
+        assert!(reason.contains("required roles: [\"admin\"]"));
+        assert!(reason.contains("required permissions: [\"posts:delete\"]"));
+
+        // Requirements that pass aren't listed, and all passing still authorizes
+        combined["roles"] = serde_json::json!(["editor"]);
+        let reason = match authorize(&session, &create_route(combined.clone())) {
+            AuthResult::Unauthorized(reason) => reason,
+            other => panic!("expected a denial, got {:?}", other),
+        };
+        assert!(!reason.contains("required roles"));
+        assert!(reason.contains("required permissions"));
+
+        combined["permissions"] = serde_json::json!(["posts:write"]);
+        assert!(matches!(
+            authorize(&session, &create_route(combined)),
+            AuthResult::Authorized
+        ));
+    }
+
+    #[test]
+    fn test_tenant_restriction() {
+        let session = create_test_session();
+
+        // Any valid session from an allowed tenant passes
+        let route = create_route(serde_json::json!({ "tenants": ["tenant-1", "tenant-2"] }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+
+        let route = create_route(serde_json::json!({ "tenants": ["tenant-2"] }));
+        match authorize(&session, &route) {
+            AuthResult::Unauthorized(reason) => assert!(reason.contains("tenant-1")),
+            other => panic!("expected a denial, got {:?}", other),
+        }
+
+        // Other requirements still apply to sessions from an allowed tenant
+        let route = create_route(serde_json::json!({
+            "tenants": ["tenant-1"],
+            "roles": ["admin"],
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+
+        // So do denied roles
+        let route = create_route(serde_json::json!({
+            "tenants": ["tenant-1"],
+            "deny_roles": ["editor"],
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[test]
+    fn test_override_permissions() {
+        let session = create_test_session();
+
+        // An override permission grants access despite the missing role
+        let route = create_route(serde_json::json!({
+            "roles": ["admin"],
+            "override_permissions": ["posts:write"],
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+
+        // Without it, the role is still required
+        let route = create_route(serde_json::json!({
+            "roles": ["admin"],
+            "override_permissions": ["posts:admin"],
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+
+        // Denied roles still apply
+        let route = create_route(serde_json::json!({
+            "roles": ["admin"],
+            "deny_roles": ["editor"],
+            "override_permissions": ["posts:write"],
+        }));
+        match authorize(&session, &route) {
+            AuthResult::Unauthorized(reason) => assert!(reason.contains("denied role")),
+            other => panic!("expected a denial, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_override_permissions_stay_within_tenants() {
+        let session = create_test_session();
+
+        // An override permission doesn't reach across tenants
+        for tenants in [
+            serde_json::json!({ "tenant_id": "tenant-2" }),
+            serde_json::json!({ "tenants": ["tenant-2", "tenant-3"] }),
+        ] {
+            let mut require = serde_json::json!({
+                "roles": ["admin"],
+                "override_permissions": ["posts:write"],
+            });
+            require
+                .as_object_mut()
+                .unwrap()
+                .extend(tenants.as_object().unwrap().clone());
+            match authorize(&session, &create_route(require)) {
+                AuthResult::Unauthorized(reason) => assert!(reason.contains("tenant")),
+                other => panic!("expected a denial, got {:?}", other),
+            }
+        }
+
+        // Within the session's tenant it still applies
+        let route = create_route(serde_json::json!({
+            "roles": ["admin"],
+            "tenant_id": "tenant-1",
+            "override_permissions": ["posts:write"],
+        }));
+        assert!(matches!(
+            authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+    }
+
+    #[test]
+    fn test_default_combine_mode() {
+        let session = create_test_session();
+        // The user is an editor but lacks the permission
+        let route = create_route(serde_json::json!({
+            "roles": ["editor"],
+            "permissions": ["posts:delete"],
+        }));
+
+        // Every requirement must pass by default
+        assert!(matches!(
+            Authorizer::new().authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+
+        // A global `any` default applies to routes without their own mode
+        let any = Authorizer::new().with_default_combine(CombineMode::Any);
+        assert!(matches!(
+            any.authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+
+        // Failing every requirement is still denied, naming each one
+        let route = create_route(serde_json::json!({
+            "roles": ["admin"],
+            "permissions": ["posts:delete"],
+        }));
+        match any.authorize(&session, &route) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("roles") && reason.contains("permissions"))
+            }
+            other => panic!("expected a denial, got {:?}", other),
+        }
+
+        // A route setting its own mode overrides the default
+        let route = create_route(serde_json::json!({
+            "roles": ["editor"],
+            "permissions": ["posts:delete"],
+            "combine": "all",
+        }));
+        assert!(matches!(
+            any.authorize(&session, &route),
+            AuthResult::Unauthorized(_)
+        ));
+        let route = create_route(serde_json::json!({
+            "roles": ["editor"],
+            "permissions": ["posts:delete"],
+            "combine": "any",
+        }));
+        assert!(matches!(
+            Authorizer::new().authorize(&session, &route),
+            AuthResult::Authorized
+        ));
+    }
+}
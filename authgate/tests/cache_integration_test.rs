@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use authgate::cache::{InMemoryCache, RedisCache, SessionCache};
+    use authgate::cache::{session_key, InMemoryCache, RedisCache, SessionCache};
     use authgate::types::{SessionResponse, Team, User};
     use std::env;
     use std::time::Duration;
@@ -22,6 +22,7 @@ mod tests {
             tenant_id: "tenant-1".to_string(),
             authority: "example.com".to_string(),
             redirect_url: None,
+            ..Default::default()
         }
     }
 
@@ -65,6 +66,95 @@ mod tests {
         assert!(cached_session.is_none());
     }
 
+    // Requires a Redis server, like test_redis_cache_integration
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_cache_shared_connection() {
+        let redis_url = match env::var("REDIS_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping Redis test because REDIS_URL is not set");
+                return;
+            }
+        };
+        let cache = std::sync::Arc::new(RedisCache::new(&redis_url));
+        let session = create_test_session();
+        let ttl = Duration::from_secs(60);
+        // Counters outlive the test, so each run gets keys of its own
+        let run = uuid::Uuid::new_v4();
+
+        // Rapid sequential operations, each of which used to open a connection
+        for i in 0..500 {
+            let token = format!("test-token-shared-{}-{}", run, i % 10);
+            cache.set(&token, session.clone(), ttl).await.unwrap();
+            assert!(cache.get(&token).await.is_some());
+            assert_eq!(
+                cache.increment(&token, ttl).await.unwrap() as usize,
+                i / 10 + 1
+            );
+        }
+
+        // And concurrent ones multiplexed over the same connection
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let cache = cache.clone();
+                let session = session.clone();
+                tokio::spawn(async move {
+                    let token = format!("test-token-concurrent-{}", i);
+                    cache.set(&token, session, ttl).await.unwrap();
+                    let cached = cache.get(&token).await;
+                    cache.remove(&token).await.unwrap();
+                    cached
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().is_some());
+        }
+
+        for i in 0..10 {
+            cache
+                .remove(&format!("test-token-shared-{}-{}", run, i))
+                .await
+                .unwrap();
+        }
+    }
+
+    // Requires a Redis server, like test_redis_cache_integration
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_cache_unreadable_entry_is_a_miss() {
+        let redis_url = match env::var("REDIS_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping Redis test because REDIS_URL is not set");
+                return;
+            }
+        };
+        let cache = RedisCache::new(&redis_url);
+        let token = format!("test-token-unreadable-{}", uuid::Uuid::new_v4());
+        let key = format!("authgate:session:{}", session_key(&token));
+
+        let client = redis::Client::open(redis_url.as_str()).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let _: () = redis::cmd("SETEX")
+            .arg(&key)
+            .arg(60)
+            .arg("{\"not\": \"a session\"}")
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+
+        // Not an error even when strict, and the entry is dropped
+        assert!(cache.try_get_with_ttl(&token).await.unwrap().is_none());
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(!exists);
+    }
+
     // This test verifies that both cache implementations behave the same way
     #[tokio::test]
     async fn test_cache_implementations_consistency() {
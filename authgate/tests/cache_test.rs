@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use authgate::cache::{extract_jwt_expiration, InMemoryCache, SessionCache};
+    use authgate::cache::{
+        extract_jwt_expiration, session_key, CacheBackend, CacheFactory, CachePurpose, CacheStats,
+        InMemoryCache, RedisCache, SessionCache,
+    };
     use authgate::types::{SessionResponse, Team, User};
     use jsonwebtoken::{encode, EncodingKey, Header};
     use serde::{Deserialize, Serialize};
@@ -30,6 +33,7 @@ mod tests {
             tenant_id: "tenant-1".to_string(),
             authority: "example.com".to_string(),
             redirect_url: None,
+            ..Default::default()
         }
     }
 
@@ -104,6 +108,47 @@ mod tests {
         assert!(cached_session.is_none());
     }
 
+    #[tokio::test]
+    async fn test_expired_sessions_swept_in_background() {
+        let cache = InMemoryCache::new().with_cleanup_interval(Duration::from_millis(50));
+        cache
+            .set(
+                "short-lived",
+                create_test_session(),
+                Duration::from_millis(100),
+            )
+            .await
+            .unwrap();
+        cache
+            .set("long-lived", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.entry_count().await, 2);
+
+        // No lookups in between, the cleanup task drops the expired session on its own
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(cache.entry_count().await, 1);
+        assert!(cache.get("long-lived").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_expired_sessions_kept_without_cleanup_task() {
+        let cache = InMemoryCache::new().with_cleanup_interval(Duration::ZERO);
+        cache
+            .set(
+                "short-lived",
+                create_test_session(),
+                Duration::from_millis(50),
+            )
+            .await
+            .unwrap();
+
+        // Lookups don't sweep, but never return the expired session
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(cache.get("short-lived").await.is_none());
+        assert_eq!(cache.entry_count().await, 1);
+    }
+
     #[tokio::test]
     async fn test_jwt_expiration_extraction() {
         // Create a JWT token that expires in 60 seconds
@@ -160,4 +205,312 @@ mod tests {
         let cached_session = cache.get(&token).await;
         assert!(cached_session.is_none());
     }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+
+        let cache = InMemoryCache::new();
+        cache
+            .set("test-token", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.save_snapshot(&path).await.unwrap(), 1);
+
+        // A fresh cache restores the session from the snapshot
+        let restored = InMemoryCache::with_snapshot(&path);
+        let (session, ttl) = restored.get_with_ttl("test-token").await.unwrap();
+        assert_eq!(session.user.id, "user-1");
+        assert!(ttl.unwrap() <= Duration::from_secs(60));
+
+        // Loading into an existing cache works too
+        let loaded = InMemoryCache::new();
+        assert_eq!(loaded.load_snapshot(&path).await.unwrap(), 1);
+        assert!(loaded.get("test-token").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_hashed_into_cache_keys() {
+        let key = session_key("token-a");
+        assert_eq!(key.len(), 64);
+        assert!(!key.contains("token-a"));
+        assert_eq!(key, session_key("token-a"));
+        assert_ne!(key, session_key("token-b"));
+
+        // Sessions are still looked up by their token
+        let cache = InMemoryCache::new();
+        let mut other = create_test_session();
+        other.user.id = "user-2".to_string();
+        cache
+            .set("token-a", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        cache
+            .set("token-b", other, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.get("token-a").await.unwrap().user.id, "user-1");
+        assert_eq!(cache.get("token-b").await.unwrap().user.id, "user-2");
+
+        // Snapshots hold the digest rather than the token
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        cache.save_snapshot(&path).await.unwrap();
+        let snapshot = std::fs::read_to_string(&path).unwrap();
+        assert!(snapshot.contains(&key));
+        assert!(!snapshot.contains("token-a"));
+
+        cache.remove("token-a").await.unwrap();
+        assert!(cache.get("token-a").await.is_none());
+        assert!(cache.get("token-b").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_drops_expired_entries_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+
+        let cache = InMemoryCache::new();
+        cache
+            .set(
+                "fresh-token",
+                create_test_session(),
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        cache
+            .set("stale-token", create_test_session(), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(cache.save_snapshot(&path).await.unwrap(), 2);
+
+        // Let the short-lived session expire while "restarting"
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let restored = InMemoryCache::new();
+        assert_eq!(restored.load_snapshot(&path).await.unwrap(), 1);
+        assert!(restored.get("fresh-token").await.is_some());
+        assert!(restored.get("stale-token").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_missing_file_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let cache = InMemoryCache::with_snapshot(dir.path().join("missing.json"));
+        assert!(cache.get("test-token").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_counts_hits_and_misses() {
+        let cache = InMemoryCache::new();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.stats(), CacheStats::default());
+
+        assert!(cache.get("token-1").await.is_none());
+        cache
+            .set("token-1", create_test_session(), ttl)
+            .await
+            .unwrap();
+        cache
+            .set("token-2", create_test_session(), ttl)
+            .await
+            .unwrap();
+        assert!(cache.get("token-1").await.is_some());
+        assert!(cache.get("token-2").await.is_some());
+        assert!(cache.get("token-3").await.is_none());
+
+        // Removing a session doesn't count, the next lookup misses
+        cache.remove("token-1").await.unwrap();
+        assert!(cache.get("token-1").await.is_none());
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 3,
+                sets: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_evicts_least_recently_used() {
+        let cache = InMemoryCache::new().with_max_entries(3);
+        let ttl = Duration::from_secs(60);
+
+        for token in ["token-1", "token-2", "token-3"] {
+            cache.set(token, create_test_session(), ttl).await.unwrap();
+        }
+
+        // Over the cap, the oldest session is evicted
+        cache
+            .set("token-4", create_test_session(), ttl)
+            .await
+            .unwrap();
+        assert!(cache.get("token-1").await.is_none());
+        for token in ["token-2", "token-3", "token-4"] {
+            assert!(cache.get(token).await.is_some(), "{} was evicted", token);
+        }
+
+        // Reading a session makes it recently used
+        assert!(cache.get("token-2").await.is_some());
+        cache
+            .set("token-5", create_test_session(), ttl)
+            .await
+            .unwrap();
+        assert!(cache.get("token-3").await.is_none());
+        for token in ["token-2", "token-4", "token-5"] {
+            assert!(cache.get(token).await.is_some(), "{} was evicted", token);
+        }
+
+        // Replacing a cached session doesn't evict anything
+        cache
+            .set("token-5", create_test_session(), ttl)
+            .await
+            .unwrap();
+        for token in ["token-2", "token-4", "token-5"] {
+            assert!(cache.get(token).await.is_some(), "{} was evicted", token);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_evicts_expired_before_recent() {
+        let cache = InMemoryCache::new().with_max_entries(2);
+
+        cache
+            .set("recent", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        cache
+            .set("expiring", create_test_session(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        cache
+            .set("new", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(cache.get("recent").await.is_some());
+        assert!(cache.get("new").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_evicts_by_latest_expiry() {
+        let cache = InMemoryCache::new().with_max_entries(2);
+
+        // Re-caching a session replaces its expiry
+        cache
+            .set("renewed", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        cache
+            .set("renewed", create_test_session(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        cache
+            .set("recent", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(cache.get("renewed").await.is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // So the renewed session is expired and goes first, although it was
+        // used last
+        cache
+            .set("new", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.entry_count().await, 2);
+        assert!(cache.get("recent").await.is_some());
+        assert!(cache.get("new").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_caches_use_per_purpose_backends() {
+        std::env::set_var("AUTHGATE_SESSION_CACHE_BACKEND", "redis");
+        std::env::set_var("AUTHGATE_SESSION_REDIS_URL", "redis://127.0.0.1:1");
+        std::env::set_var("AUTHGATE_ADMIN_CACHE_BACKEND", "memory");
+
+        assert_eq!(
+            CacheFactory::backend_for(CachePurpose::Sessions),
+            CacheBackend::Redis {
+                url: "redis://127.0.0.1:1".to_string()
+            }
+        );
+        assert_eq!(
+            CacheFactory::backend_for(CachePurpose::Admin),
+            CacheBackend::Memory
+        );
+
+        // The in-memory cache works while the unreachable Redis one fails
+        let admin = CacheFactory::create_for(CachePurpose::Admin);
+        admin
+            .set("key", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(admin.get("key").await.is_some());
+
+        let sessions = CacheFactory::create_for(CachePurpose::Sessions);
+        assert!(sessions
+            .set("key", create_test_session(), Duration::from_secs(60))
+            .await
+            .is_err());
+        assert!(sessions.get("key").await.is_none());
+        std::env::remove_var("AUTHGATE_SESSION_CACHE_BACKEND");
+        std::env::remove_var("AUTHGATE_SESSION_REDIS_URL");
+    }
+
+    #[test]
+    fn test_cache_purpose_falls_back_to_global_backend() {
+        // No AUTHGATE_ADMIN_* settings, so the global ones apply
+        std::env::remove_var("AUTHGATE_ADMIN_CACHE_BACKEND");
+        std::env::remove_var("AUTHGATE_ADMIN_REDIS_URL");
+        let global = match std::env::var("AUTHGATE_CACHE_BACKEND") {
+            Ok(backend) if backend.eq_ignore_ascii_case("redis") => CacheBackend::Redis {
+                url: std::env::var("AUTHGATE_REDIS_URL")
+                    .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string()),
+            },
+            _ => CacheBackend::Memory,
+        };
+
+        assert_eq!(CacheFactory::backend_for(CachePurpose::Admin), global);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_counter_restarts_after_window() {
+        let cache = InMemoryCache::new();
+        let window = Duration::from_millis(50);
+
+        assert_eq!(cache.increment("client", window).await.unwrap(), 1);
+        assert_eq!(cache.increment("client", window).await.unwrap(), 2);
+        assert_eq!(cache.increment("other", window).await.unwrap(), 1);
+
+        // An ended window starts over even before ended counters are swept
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.increment("client", window).await.unwrap(), 1);
+        assert_eq!(cache.increment("other", window).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redis_cache_refuses_calls_after_close() {
+        // Nothing listens on port 1, and a closed cache must not try to connect
+        let cache = RedisCache::new("redis://127.0.0.1:1");
+        cache.close().await.unwrap();
+
+        let error = cache
+            .set("token", create_test_session(), Duration::from_secs(60))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("closed"), "{}", error);
+        assert!(cache
+            .ping()
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("closed"));
+    }
 }
@@ -1,11 +1,37 @@
 #[cfg(test)]
 mod tests {
-    use authgate::config_provider::{ConfigProvider, JsonFileProvider};
-    use authgate::types::{AuthConfig, Config, RequireConfig, Route};
+    use async_trait::async_trait;
+    use authgate::authorizer::authorize;
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::{
+        CompositeProvider, ConfigProvider, JsonFileProvider, YamlFileProvider,
+    };
+    use authgate::matcher::RouteMatcher;
+    use authgate::types::{
+        AuthConfig, AuthGateError, AuthResult, Config, RateLimit, RateLimitKey, RequireConfig,
+        Route, SessionResponse, User,
+    };
     use std::fs::File;
     use std::io::Write;
+    use std::sync::Arc;
     use tempfile::tempdir;
 
+    /// Provider standing in for the database, which only contributes routes
+    struct MockDbProvider(Vec<Route>);
+
+    #[async_trait]
+    impl ConfigProvider for MockDbProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            Err(AuthGateError::ConfigError(
+                "no auth_config in the database".to_string(),
+            ))
+        }
+
+        async fn load_routes(&self) -> Result<Vec<Route>, AuthGateError> {
+            Ok(self.0.clone())
+        }
+    }
+
     #[tokio::test]
     async fn test_json_file_provider() {
         // Create a temporary directory for the test
@@ -17,6 +43,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![
                 Route {
@@ -28,8 +55,10 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: None,
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
                 Route {
                     id: None,
@@ -40,11 +69,14 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: Some(vec![]),
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
             ],
             cookie_name: Some("custom-session".to_string()),
+            ..Default::default()
         };
 
         let config_json = serde_json::to_string_pretty(&config).unwrap();
@@ -77,6 +109,571 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_yaml_file_provider() {
+        let temp_dir = tempdir().unwrap();
+        let json_path = temp_dir.path().join("test-config.json");
+        let yaml_path = temp_dir.path().join("test-config.yaml");
+
+        let config_json = r#"{
+            "auth": {
+                "session_url": "https://auth.example.com/session",
+                "login_redirect": "https://auth.example.com/login"
+            },
+            "routes": [
+                {
+                    "host": "app.example.com",
+                    "path": "/admin/*",
+                    "require": { "roles": ["admin"] }
+                },
+                {
+                    "host": "*.client.example.com",
+                    "path": "/",
+                    "require": { "teams": [] }
+                }
+            ],
+            "cookie_name": "custom-session"
+        }"#;
+        let config_yaml = r#"
+auth:
+  session_url: https://auth.example.com/session
+  login_redirect: https://auth.example.com/login
+routes:
+  - host: app.example.com
+    path: /admin/*
+    require:
+      roles: [admin]
+  - host: "*.client.example.com"
+    path: /
+    require:
+      teams: []
+cookie_name: custom-session
+"#;
+        File::create(&json_path)
+            .unwrap()
+            .write_all(config_json.as_bytes())
+            .unwrap();
+        File::create(&yaml_path)
+            .unwrap()
+            .write_all(config_yaml.as_bytes())
+            .unwrap();
+
+        let from_json = JsonFileProvider::new(json_path.to_str().unwrap())
+            .load_config()
+            .await
+            .unwrap();
+        let from_yaml = YamlFileProvider::new(yaml_path.to_str().unwrap())
+            .load_config()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            from_yaml.auth.session_url,
+            "https://auth.example.com/session"
+        );
+        assert_eq!(
+            from_yaml.auth.login_redirect,
+            "https://auth.example.com/login"
+        );
+        assert_eq!(from_yaml.routes.len(), 2);
+        assert_eq!(from_yaml.routes[0].host, "app.example.com");
+        assert_eq!(from_yaml.routes[1].host, "*.client.example.com");
+        assert_eq!(from_yaml.cookie_name, Some("custom-session".to_string()));
+
+        // Both formats load into the same configuration
+        assert_eq!(
+            serde_json::to_value(&from_yaml).unwrap(),
+            serde_json::to_value(&from_json).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_yaml_file_provider_validates_config() {
+        let temp_dir = tempdir().unwrap();
+        let yaml_path = temp_dir.path().join("invalid.yml");
+
+        // A route without a requirement and no default_require
+        File::create(&yaml_path)
+            .unwrap()
+            .write_all(
+                b"auth:\n  session_url: https://auth.example.com/session\n  login_redirect: https://auth.example.com/login\nroutes:\n  - host: app.example.com\n    path: /admin/*\n",
+            )
+            .unwrap();
+
+        let result = YamlFileProvider::new(yaml_path.to_str().unwrap())
+            .load_config()
+            .await;
+        assert!(matches!(result, Err(AuthGateError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_rejects_directory() {
+        let temp_dir = tempdir().unwrap();
+        let dir_path = temp_dir.path().to_str().unwrap();
+
+        let provider = JsonFileProvider::new(dir_path);
+        let err = provider.load_config().await.unwrap_err();
+
+        match err {
+            AuthGateError::ConfigError(message) => {
+                assert!(message.contains(dir_path));
+                assert!(message.contains("is a directory"));
+                assert!(message.contains("authgate.json"));
+            }
+            other => panic!("Expected ConfigError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_applies_default_require() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "default_require": { "authenticated": true },
+                "routes": [
+                    { "host": "app.example.com", "path": "/admin/*", "require": { "roles": ["admin"] } },
+                    { "host": "app.example.com", "path": "/*" },
+                    { "host": "docs.example.com", "path": "/", "require": {} }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+        let config = provider.load_config().await.unwrap();
+
+        // Routes with their own requirement keep it
+        assert_eq!(
+            config.routes[0].require,
+            serde_json::json!({ "roles": ["admin"] })
+        );
+        // Missing and empty requirements get the default
+        assert_eq!(
+            config.routes[1].require,
+            serde_json::json!({ "authenticated": true })
+        );
+        assert_eq!(
+            config.routes[2].require,
+            serde_json::json!({ "authenticated": true })
+        );
+
+        // The default lets any signed-in user through
+        let session = SessionResponse {
+            user: User {
+                id: "user-1".to_string(),
+                email: "user@example.com".to_string(),
+                roles: vec![],
+                permissions: vec![],
+                teams: vec![],
+            },
+            tenant_id: "tenant-1".to_string(),
+            authority: "example.com".to_string(),
+            redirect_url: None,
+            ..Default::default()
+        };
+        assert!(matches!(
+            authorize(&session, &config.routes[1]),
+            AuthResult::Authorized
+        ));
+        assert!(matches!(
+            authorize(&session, &config.routes[0]),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_rejects_route_without_require() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [{ "host": "app.example.com", "path": "/*" }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("must have at least one requirement"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_public_route_without_require() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "default_require": { "roles": ["user"] },
+                "routes": [
+                    { "host": "app.example.com", "path": "/public/*", "public": true },
+                    { "host": "app.example.com", "path": "/*", "require": { "roles": ["admin"] } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+        let config = provider.load_config().await.unwrap();
+        assert!(config.routes[0].public);
+        // The default requirement isn't applied to public routes
+        assert!(config.routes[0].require.is_null());
+        assert!(!config.routes[1].public);
+        assert!(!serde_json::to_value(&config.routes[1])
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .contains_key("public"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_rejects_invalid_require_expr() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [{
+                    "host": "app.example.com",
+                    "path": "/*",
+                    "require": { "expr": "(role:admin OR team:ops AND NOT" }
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err.to_string().contains("Invalid require expression"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_validates_roles_match() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let write_config = |roles_match: &str| {
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [{
+                        "host": "app.example.com",
+                        "path": "/*",
+                        "require": { "roles": ["billing", "admin"], "roles_match": roles_match }
+                    }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+
+        write_config("all");
+        let config = provider.load_config().await.unwrap();
+        assert_eq!(config.routes[0].require["roles_match"], "all");
+
+        write_config("most");
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err.to_string().contains("roles_match"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_requires_https_urls() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let write_config = |session_url: &str, login_redirect: &str| {
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": session_url,
+                        "login_redirect": login_redirect
+                    },
+                    "routes": [{
+                        "host": "app.example.com",
+                        "path": "/*",
+                        "require": { "roles": ["admin"] }
+                    }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+
+        // Plain HTTP is rejected by default
+        write_config(
+            "https://auth.example.com/session",
+            "http://auth.example.com/login",
+        );
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("login_redirect must be an https:// URL"));
+
+        write_config(
+            "http://auth.example.com/session",
+            "https://auth.example.com/login",
+        );
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("session_url must be an https:// URL"));
+
+        // And allowed for local development
+        std::env::set_var("AUTHGATE_ALLOW_INSECURE_URLS", "true");
+        write_config(
+            "http://localhost:8080/session",
+            "http://localhost:8080/login",
+        );
+        let result = provider.load_config().await;
+        std::env::remove_var("AUTHGATE_ALLOW_INSECURE_URLS");
+        assert_eq!(
+            result.unwrap().auth.login_redirect,
+            "http://localhost:8080/login"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_validates_audience() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let write_config = |route: serde_json::Value| {
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [route]
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+
+        // `app` is accepted as an alias
+        write_config(serde_json::json!({
+            "host": "app.example.com",
+            "path": "/*",
+            "require": { "roles": ["admin"] },
+            "app": "billing"
+        }));
+        let config = provider.load_config().await.unwrap();
+        assert_eq!(config.routes[0].audience.as_deref(), Some("billing"));
+
+        write_config(serde_json::json!({
+            "host": "app.example.com",
+            "path": "/*",
+            "require": { "roles": ["admin"] },
+            "audience": " "
+        }));
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err.to_string().contains("empty audience"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_deny_roles() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let write_config = |require: serde_json::Value| {
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [{ "host": "app.example.com", "path": "/*", "require": require }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+
+        // deny_roles round-trips through the route's require block
+        write_config(serde_json::json!({ "roles": ["member"], "deny_roles": ["suspended"] }));
+        let config = provider.load_config().await.unwrap();
+        let require: RequireConfig =
+            serde_json::from_value(config.routes[0].require.clone()).unwrap();
+        assert_eq!(require.deny_roles, Some(vec!["suspended".to_string()]));
+        assert_eq!(
+            serde_json::to_value(&require).unwrap()["deny_roles"],
+            serde_json::json!(["suspended"])
+        );
+
+        // A role can't be both required and denied
+        write_config(serde_json::json!({ "roles": ["member"], "deny_roles": ["member"] }));
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("both requires and denies role member"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_scope_resource_ids() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let write_config = |require: serde_json::Value| {
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [{ "host": "app.example.com", "path": "/*", "require": require }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+
+        write_config(serde_json::json!({
+            "scopes": [{ "resource_type": "client", "action": "access", "resource_ids": ["a", "b"] }]
+        }));
+        let config = provider.load_config().await.unwrap();
+        let require: RequireConfig =
+            serde_json::from_value(config.routes[0].require.clone()).unwrap();
+        assert_eq!(require.scopes.unwrap()[0].resource_ids, vec!["a", "b"]);
+
+        // The singular and plural forms can't be combined, including in team scopes
+        write_config(serde_json::json!({
+            "teams": [{
+                "id": "team-1",
+                "scopes": [{
+                    "resource_type": "client",
+                    "action": "access",
+                    "resource_id": "a",
+                    "resource_ids": ["b"]
+                }]
+            }]
+        }));
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("scope client:access cannot set both resource_id and resource_ids"));
+    }
+
+    #[tokio::test]
+    async fn test_json_file_provider_rejects_invalid_path_segment_names() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [{
+                    "host": "app.example.com",
+                    "path": "/users/{user id}/profile",
+                    "require": { "roles": ["admin"] }
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let provider = JsonFileProvider::new(config_path.to_str().unwrap());
+        let err = provider.load_config().await.unwrap_err();
+        assert!(err.to_string().contains("{user id}"));
+    }
+
+    #[tokio::test]
+    async fn test_composite_provider_merges_json_and_db_routes() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "cookie_name": "base_session",
+                "routes": [{
+                    "host": "app.example.com",
+                    "path": "/*",
+                    "require": { "roles": ["user"] }
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let db_route = |host: &str, path: &str, role: &str| Route {
+            id: Some(1),
+            host: host.to_string(),
+            path: path.to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            ..Default::default()
+        };
+        let provider = CompositeProvider::new(
+            Arc::new(JsonFileProvider::new(config_path.to_str().unwrap())),
+            vec![Arc::new(MockDbProvider(vec![
+                db_route("app.example.com", "/admin/*", "admin"),
+                // Duplicates the JSON route, which wins
+                db_route("app.example.com", "/*", "db"),
+            ]))],
+        );
+
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(provider)));
+        config_manager.load_config().await.unwrap();
+
+        // Auth settings come from the primary provider
+        let config = config_manager.get_config().await;
+        assert_eq!(config.auth.session_url, "https://auth.example.com/session");
+        assert_eq!(config.cookie_name.as_deref(), Some("base_session"));
+        assert_eq!(config.routes.len(), 2);
+
+        // Routes from both providers are matched
+        let matcher = RouteMatcher::new(config_manager.get_config_ref());
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.require, serde_json::json!({ "roles": ["admin"] }));
+        let route = matcher
+            .match_route("app.example.com", "/reports", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.require, serde_json::json!({ "roles": ["user"] }));
+    }
+
     // This test is marked as ignored by default because it requires a PostgreSQL server
     // To run it: cargo test -- --ignored
     #[tokio::test]
@@ -95,7 +692,7 @@ mod tests {
         };
 
         // Create a config provider and load the config
-        let provider = PostgresProvider::new(&database_url);
+        let provider = PostgresProvider::new(&database_url).unwrap();
         let result = provider.load_config().await;
 
         // Check that the config was loaded successfully
@@ -111,11 +708,523 @@ mod tests {
             loaded_config.auth.login_redirect,
             "https://auth.example.com/login"
         );
-        assert!(loaded_config.routes.len() >= 1);
+        assert!(!loaded_config.routes.is_empty());
 
         // Check that at least one route was loaded
         let route = &loaded_config.routes[0];
         assert!(!route.host.is_empty());
         assert!(!route.path.is_empty());
     }
+
+    // Requires a PostgreSQL server, like test_postgres_provider
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_provider_persists_public_flag() {
+        use authgate::config_provider::PostgresProvider;
+        use std::env;
+
+        let database_url = match env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping PostgreSQL test because DATABASE_URL is not set");
+                return;
+            }
+        };
+        let provider = PostgresProvider::new(&database_url).unwrap();
+
+        let created = provider
+            .create_route(Route {
+                host: "public.example.com".to_string(),
+                path: "/health".to_string(),
+                require: serde_json::json!({}),
+                public: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(created.public);
+
+        let id = created.id.unwrap();
+        let loaded = provider.get_route_by_id(&id).await.unwrap();
+        assert!(loaded.public);
+
+        provider.delete_route(&id).await.unwrap();
+    }
+
+    // Requires a PostgreSQL server, like test_postgres_provider
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_provider_reuses_connections() {
+        use authgate::config_provider::PostgresProvider;
+        use std::env;
+
+        let database_url = match env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping PostgreSQL test because DATABASE_URL is not set");
+                return;
+            }
+        };
+        let provider = PostgresProvider::new(&database_url).unwrap();
+
+        async fn backend_pid(pool: &sqlx::PgPool) -> i32 {
+            sqlx::query_scalar::<_, i32>("SELECT pg_backend_pid()")
+                .fetch_one(pool)
+                .await
+                .unwrap()
+        }
+        let first_pid = backend_pid(provider.pool()).await;
+
+        // Sequential calls, each of which used to open a pool of its own
+        for _ in 0..50 {
+            provider.get_all_routes().await.unwrap();
+            provider.load_config().await.unwrap();
+            provider.routes_version().await.unwrap();
+        }
+
+        // One connection served everything, and is still the same server process
+        assert_eq!(provider.pool().size(), 1);
+        assert_eq!(backend_pid(provider.pool()).await, first_pid);
+
+        // Clones share the pool
+        provider.clone().get_all_routes().await.unwrap();
+        assert_eq!(provider.pool().size(), 1);
+    }
+
+    // Requires a PostgreSQL server, like test_postgres_provider
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_provider_bulk_delete_by_host() {
+        use authgate::admin::RouteFilter;
+        use authgate::config_provider::PostgresProvider;
+        use std::env;
+
+        let database_url = match env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping PostgreSQL test because DATABASE_URL is not set");
+                return;
+            }
+        };
+        let provider = PostgresProvider::new(&database_url).unwrap();
+
+        // Hosts unique to this run so existing routes are never touched
+        let run = uuid::Uuid::new_v4();
+        let doomed_host = format!("doomed-{}.example.com", run);
+        let kept_host = format!("kept-{}.example.com", run);
+        for (host, path) in [
+            (&doomed_host, "/a/*"),
+            (&doomed_host, "/b/*"),
+            (&kept_host, "/a/*"),
+        ] {
+            provider
+                .create_route(Route {
+                    host: host.clone(),
+                    path: path.to_string(),
+                    require: serde_json::json!({ "roles": ["user"] }),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let filter = RouteFilter {
+            host: Some(doomed_host.clone()),
+        };
+        let ids: Vec<i32> = filter
+            .select(provider.get_all_routes().await.unwrap())
+            .into_iter()
+            .filter_map(|route| route.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(provider.delete_routes(&ids).await.unwrap(), 2);
+
+        // Only the matching routes are gone
+        let remaining = provider.get_all_routes().await.unwrap();
+        assert!(!remaining.iter().any(|route| route.host == doomed_host));
+        let kept: Vec<_> = remaining
+            .iter()
+            .filter(|route| route.host == kept_host)
+            .collect();
+        assert_eq!(kept.len(), 1);
+
+        provider.delete_route(&kept[0].id.unwrap()).await.unwrap();
+    }
+
+    /// Migrated SQLite provider in a temp file, with auth settings inserted
+    #[cfg(feature = "sqlite")]
+    async fn sqlite_provider(dir: &tempfile::TempDir) -> authgate::config_provider::SqliteProvider {
+        use authgate::config_provider::SqliteProvider;
+
+        let path = dir.path().join("authgate.db");
+        let provider = SqliteProvider::new(path.to_str().unwrap()).unwrap();
+        provider.migrate().await.unwrap();
+        sqlx::query(
+            "INSERT INTO auth_config (session_url, login_redirect, cookie_name) VALUES (?, ?, ?)",
+        )
+        .bind("https://auth.example.com/session")
+        .bind("https://auth.example.com/login")
+        .bind("session")
+        .execute(provider.pool())
+        .await
+        .unwrap();
+        provider
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_provider() {
+        use authgate::config_provider::RouteStore;
+
+        let dir = tempdir().unwrap();
+        let provider = sqlite_provider(&dir).await;
+        let empty_version = provider.routes_version().await.unwrap();
+
+        let created = provider
+            .create_route(Route {
+                host: "app.example.com".to_string(),
+                path: "/admin/*".to_string(),
+                require: serde_json::json!({ "roles": ["admin"] }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let id = created.id.unwrap();
+        assert_eq!(created.require["roles"], serde_json::json!(["admin"]));
+
+        // The routes and auth settings load like any other provider's
+        let config = provider.load_config().await.unwrap();
+        assert_eq!(config.auth.session_url, "https://auth.example.com/session");
+        assert_eq!(config.cookie_name.as_deref(), Some("session"));
+        assert_eq!(config.routes.len(), 1);
+        assert_eq!(config.routes[0].id, Some(id));
+        let created_version = provider.routes_version().await.unwrap();
+        assert_ne!(created_version, empty_version);
+
+        // Updates are persisted and change the routes version, whose
+        // timestamps have millisecond precision
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let updated = provider
+            .update_route(Route {
+                public: true,
+                ..created
+            })
+            .await
+            .unwrap();
+        assert!(updated.public);
+        assert!(provider.get_route_by_id(&id).await.unwrap().public);
+        assert_ne!(provider.routes_version().await.unwrap(), created_version);
+        let since = created_version.unwrap().updated_at.unwrap();
+        assert_eq!(
+            provider.routes_updated_since(&since).await.unwrap(),
+            Some(1)
+        );
+
+        // Missing routes are reported as not found
+        provider.delete_route(&id).await.unwrap();
+        assert!(matches!(
+            provider.get_route_by_id(&id).await,
+            Err(AuthGateError::NotFound(_))
+        ));
+        assert!(matches!(
+            provider.delete_route(&id).await,
+            Err(AuthGateError::NotFound(_))
+        ));
+        assert!(provider.get_all_routes().await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_provider_bulk_delete() {
+        use authgate::config_provider::RouteStore;
+
+        let dir = tempdir().unwrap();
+        let provider = sqlite_provider(&dir).await;
+
+        let mut ids = Vec::new();
+        for host in ["a.example.com", "b.example.com", "c.example.com"] {
+            let route = provider
+                .create_route(Route {
+                    host: host.to_string(),
+                    path: "/".to_string(),
+                    require: serde_json::json!({ "roles": ["user"] }),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            ids.push(route.id.unwrap());
+        }
+
+        // IDs that no longer exist are skipped
+        assert_eq!(
+            provider
+                .delete_routes(&[ids[0], ids[2], 999])
+                .await
+                .unwrap(),
+            2
+        );
+
+        let remaining = provider.get_all_routes().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].host, "b.example.com");
+
+        // The database file is reopened with its routes intact
+        let reopened = authgate::config_provider::SqliteProvider::new(
+            dir.path().join("authgate.db").to_str().unwrap(),
+        )
+        .unwrap();
+        reopened.migrate().await.unwrap();
+        assert_eq!(reopened.load_config().await.unwrap().routes.len(), 1);
+    }
+
+    /// Route setting every option that's stored outside the require block
+    fn route_with_options(host: &str) -> Route {
+        Route {
+            host: host.to_string(),
+            path: "/api/*".to_string(),
+            require: serde_json::json!({ "roles": ["user"] }),
+            rate_limit: Some(RateLimit {
+                requests: 10,
+                window_secs: 60,
+                key: RateLimitKey::Ip,
+            }),
+            session_url: Some("https://partner.example.com/session".to_string()),
+            port: Some(8443),
+            forbidden_redirect: Some("https://app.example.com/request-access".to_string()),
+            methods: Some(vec!["POST".to_string(), "PUT".to_string()]),
+            session_cache_ttl: Some(30),
+            audience: Some("billing".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Check that a stored route kept every option of [`route_with_options`]
+    fn assert_route_options(route: &Route) {
+        let rate_limit = route.rate_limit.as_ref().unwrap();
+        assert_eq!(rate_limit.requests, 10);
+        assert_eq!(rate_limit.window_secs, 60);
+        assert_eq!(rate_limit.key, RateLimitKey::Ip);
+        assert_eq!(
+            route.session_url.as_deref(),
+            Some("https://partner.example.com/session")
+        );
+        assert_eq!(route.port, Some(8443));
+        assert_eq!(
+            route.forbidden_redirect.as_deref(),
+            Some("https://app.example.com/request-access")
+        );
+        assert_eq!(
+            route.methods,
+            Some(vec!["POST".to_string(), "PUT".to_string()])
+        );
+        assert_eq!(route.session_cache_ttl, Some(30));
+        assert_eq!(route.audience.as_deref(), Some("billing"));
+    }
+
+    // Requires a PostgreSQL server, like test_postgres_provider
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_provider_persists_route_options() {
+        use authgate::config_provider::PostgresProvider;
+        use std::env;
+
+        let database_url = match env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("Skipping PostgreSQL test because DATABASE_URL is not set");
+                return;
+            }
+        };
+        let provider = PostgresProvider::new(&database_url).unwrap();
+
+        let host = format!("options-{}.example.com", uuid::Uuid::new_v4());
+        let before = provider.routes_version().await.unwrap().unwrap();
+        let created = provider
+            .create_route(route_with_options(&host))
+            .await
+            .unwrap();
+        assert_route_options(&created);
+
+        // The write moves the latest update forward and counts as written since
+        let after = provider.routes_version().await.unwrap().unwrap();
+        assert!(after.updated_at > before.updated_at);
+        if let Some(since) = &before.updated_at {
+            let updated = provider.routes_updated_since(since).await.unwrap();
+            assert!(updated.unwrap() >= 1);
+        }
+
+        let id = created.id.unwrap();
+        assert_route_options(&provider.get_route_by_id(&id).await.unwrap());
+        let loaded = provider.load_config().await.unwrap();
+        assert_route_options(loaded.routes.iter().find(|r| r.id == Some(id)).unwrap());
+
+        // Clearing an option stores NULL rather than keeping the old value
+        let updated = provider
+            .update_route(Route {
+                rate_limit: None,
+                ..created
+            })
+            .await
+            .unwrap();
+        assert!(updated.rate_limit.is_none());
+        assert!(provider
+            .get_route_by_id(&id)
+            .await
+            .unwrap()
+            .rate_limit
+            .is_none());
+
+        provider.delete_route(&id).await.unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_provider_persists_route_options() {
+        use authgate::config_provider::RouteStore;
+
+        let dir = tempdir().unwrap();
+        let provider = sqlite_provider(&dir).await;
+
+        let created = provider
+            .create_route(route_with_options("app.example.com"))
+            .await
+            .unwrap();
+        assert_route_options(&created);
+
+        let id = created.id.unwrap();
+        assert_route_options(&provider.get_route_by_id(&id).await.unwrap());
+        assert_route_options(&provider.load_config().await.unwrap().routes[0]);
+
+        let updated = provider
+            .update_route(Route {
+                rate_limit: None,
+                ..created
+            })
+            .await
+            .unwrap();
+        assert!(updated.rate_limit.is_none());
+        assert!(provider
+            .get_route_by_id(&id)
+            .await
+            .unwrap()
+            .rate_limit
+            .is_none());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_provider_close() {
+        use authgate::config_provider::RouteStore;
+
+        let dir = tempdir().unwrap();
+        let provider = sqlite_provider(&dir).await;
+
+        // Closing through a composite closes the pool its secondaries share
+        let composite = CompositeProvider::new(
+            Arc::new(JsonFileProvider::new("unused.json")),
+            vec![Arc::new(provider.clone())],
+        );
+        composite.close().await;
+
+        assert!(provider.pool().is_closed());
+        assert!(provider.get_all_routes().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_false_flags_are_not_requirements() {
+        for require in [
+            serde_json::json!({ "any_team": false }),
+            serde_json::json!({ "authenticated": false }),
+            serde_json::json!({ "require_mfa": false }),
+        ] {
+            let temp_dir = tempdir().unwrap();
+            let config_path = temp_dir.path().join("authgate.json");
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [
+                        { "host": "app.example.com", "path": "/admin/*", "require": require }
+                    ]
+                })
+                .to_string(),
+            )
+            .unwrap();
+
+            let result = JsonFileProvider::new(config_path.to_str().unwrap())
+                .load_config()
+                .await;
+            assert!(
+                matches!(result, Err(AuthGateError::ConfigError(_))),
+                "{} was accepted as a requirement",
+                require
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_domains_must_be_a_list() {
+        for email_domains in [serde_json::json!("example.com"), serde_json::json!([42])] {
+            let temp_dir = tempdir().unwrap();
+            let config_path = temp_dir.path().join("authgate.json");
+            std::fs::write(
+                &config_path,
+                serde_json::json!({
+                    "auth": {
+                        "session_url": "https://auth.example.com/session",
+                        "login_redirect": "https://auth.example.com/login"
+                    },
+                    "routes": [{
+                        "host": "app.example.com",
+                        "path": "/*",
+                        "require": { "email_domains": email_domains }
+                    }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+
+            let err = JsonFileProvider::new(config_path.to_str().unwrap())
+                .load_config()
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("email_domains must be a list"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_redirect_must_be_an_absolute_url() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [{
+                    "host": "app.example.com",
+                    "path": "/*",
+                    "require": { "roles": ["admin"] },
+                    "forbidden_redirect": "/request-access"
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let err = JsonFileProvider::new(config_path.to_str().unwrap())
+            .load_config()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("forbidden_redirect must be an"));
+    }
 }
@@ -1,12 +1,114 @@
 #[cfg(test)]
 mod tests {
-    use authgate::config::{ConfigManager, DEFAULT_COOKIE_NAME};
-    use authgate::config_provider::{ConfigProvider, JsonFileProvider};
-    use authgate::types::{AuthConfig, Config, RequireConfig, Route};
+    use async_trait::async_trait;
+    use authgate::config::{config_schema, ConfigManager, RouteChange};
+    use authgate::config_provider::{ConfigProvider, JsonFileProvider, RoutesVersion};
+    use authgate::matcher::RouteMatcher;
+    use authgate::types::{AuthConfig, AuthGateError, Config, RequireConfig, Route};
+    use std::collections::HashMap;
     use std::fs::File;
     use std::io::Write;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
     use tempfile::tempdir;
 
+    /// Serializes tests that change the route limit
+    static MAX_ROUTES_ENV: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Provider backed by an in-memory route table that counts full loads
+    struct CountingProvider {
+        routes: Mutex<Vec<Route>>,
+        loads: AtomicUsize,
+        /// Routes written by another replica, visible only through the version
+        external_writes: AtomicUsize,
+        /// When each route was last written, by a logical clock
+        written_at: Mutex<HashMap<i32, u64>>,
+        clock: AtomicU64,
+    }
+
+    impl CountingProvider {
+        fn new(routes: Vec<Route>) -> Self {
+            Self {
+                routes: Mutex::new(routes),
+                loads: AtomicUsize::new(0),
+                external_writes: AtomicUsize::new(0),
+                written_at: Mutex::new(HashMap::new()),
+                clock: AtomicU64::new(0),
+            }
+        }
+
+        /// Store a route like the database would, stamping its update time
+        fn write(&self, route: Route) {
+            let id = route.id.unwrap();
+            let mut routes = self.routes.lock().unwrap();
+            match routes.iter_mut().find(|existing| existing.id == Some(id)) {
+                Some(existing) => *existing = route,
+                None => routes.push(route),
+            }
+            let now = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
+            self.written_at.lock().unwrap().insert(id, now);
+        }
+
+        fn delete(&self, id: i32) {
+            self.routes
+                .lock()
+                .unwrap()
+                .retain(|route| route.id != Some(id));
+            self.written_at.lock().unwrap().remove(&id);
+        }
+    }
+
+    #[async_trait]
+    impl ConfigProvider for CountingProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            Ok(Config {
+                auth: AuthConfig {
+                    session_url: "https://auth.example.com/session".to_string(),
+                    login_redirect: "https://auth.example.com/login".to_string(),
+                    ..Default::default()
+                },
+                routes: self.routes.lock().unwrap().clone(),
+                ..Default::default()
+            })
+        }
+
+        async fn routes_version(&self) -> Result<Option<RoutesVersion>, AuthGateError> {
+            Ok(Some(RoutesVersion {
+                count: self.routes.lock().unwrap().len()
+                    + self.external_writes.load(Ordering::SeqCst),
+                updated_at: self
+                    .written_at
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .max()
+                    .map(|written| format!("{:020}", written)),
+            }))
+        }
+
+        async fn routes_updated_since(&self, since: &str) -> Result<Option<usize>, AuthGateError> {
+            let since: u64 = since.parse().unwrap();
+            let written_at = self.written_at.lock().unwrap();
+            Ok(Some(
+                written_at
+                    .values()
+                    .filter(|written| **written > since)
+                    .count(),
+            ))
+        }
+    }
+
+    fn db_route(id: i32, path: &str, role: &str) -> Route {
+        Route {
+            id: Some(id),
+            host: "app.example.com".to_string(),
+            path: path.to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            ..Default::default()
+        }
+    }
+
     #[tokio::test]
     async fn test_json_provider_config_loading() {
         // Create a temporary directory for the test
@@ -18,6 +120,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![
                 Route {
@@ -29,8 +132,10 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: None,
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
                 Route {
                     id: None,
@@ -41,11 +146,14 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: Some(vec![]),
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
             ],
             cookie_name: Some("custom-session".to_string()),
+            ..Default::default()
         };
 
         let config_json = serde_json::to_string_pretty(&config).unwrap();
@@ -78,6 +186,47 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_route_limit() {
+        let _env = MAX_ROUTES_ENV.lock().await;
+        let temp_dir = tempdir().unwrap();
+        let write_config = |name: &str, route_count: i32| {
+            let config = Config {
+                auth: AuthConfig {
+                    session_url: "https://auth.example.com/session".to_string(),
+                    login_redirect: "https://auth.example.com/login".to_string(),
+                    ..Default::default()
+                },
+                routes: (0..route_count)
+                    .map(|i| db_route(i, &format!("/app-{}/*", i), "user"))
+                    .collect(),
+                ..Default::default()
+            };
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+            JsonFileProvider::new(path.to_str().unwrap())
+        };
+
+        std::env::set_var("AUTHGATE_MAX_ROUTES", "5");
+
+        // A route table within the limit loads
+        let within = write_config("within.json", 5).load_config().await;
+        assert_eq!(within.unwrap().routes.len(), 5);
+
+        // One that would exceed it is rejected with the limit in the error
+        let beyond = write_config("beyond.json", 6).load_config().await;
+        std::env::remove_var("AUTHGATE_MAX_ROUTES");
+        match beyond {
+            Err(AuthGateError::ConfigError(message)) => {
+                assert!(message.contains("maximum of 5"), "{}", message)
+            }
+            other => panic!(
+                "Expected a config error, got {:?}",
+                other.map(|c| c.routes.len())
+            ),
+        }
+    }
+
     #[tokio::test]
     async fn test_config_manager_with_json_provider() {
         // Create a temporary directory for the test
@@ -89,6 +238,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![Route {
                 id: None,
@@ -99,10 +249,13 @@ mod tests {
                     permissions: None,
                     scopes: None,
                     teams: None,
+                    ..Default::default()
                 })
                 .unwrap(),
+                ..Default::default()
             }],
             cookie_name: Some("custom-session".to_string()),
+            ..Default::default()
         };
 
         let config_json = serde_json::to_string_pretty(&config).unwrap();
@@ -147,6 +300,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![Route {
                 id: None,
@@ -157,10 +311,13 @@ mod tests {
                     permissions: None,
                     scopes: None,
                     teams: None,
+                    ..Default::default()
                 })
                 .unwrap(),
+                ..Default::default()
             }],
             cookie_name: None,
+            ..Default::default()
         };
 
         let config_json = serde_json::to_string_pretty(&config).unwrap();
@@ -208,4 +365,280 @@ mod tests {
         // Check that loading failed
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_route_change_applied_without_full_reload() {
+        let provider = Arc::new(CountingProvider::new(vec![db_route(1, "/*", "user")]));
+        let config_manager = ConfigManager::with_provider(provider.clone());
+        config_manager.load_config().await.unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 1);
+        let matcher = RouteMatcher::new(config_manager.get_config_ref());
+
+        // Create: the provider stores the route, the manager applies it in place
+        let created = db_route(2, "/admin/*", "admin");
+        provider.write(created.clone());
+        config_manager
+            .apply_route_change(RouteChange::Upsert(Box::new(created)))
+            .await
+            .unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 1);
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
+        assert_eq!(
+            route.unwrap().require,
+            serde_json::json!({ "roles": ["admin"] })
+        );
+
+        // Update and delete are applied in place too
+        let updated = db_route(2, "/admin/*", "superuser");
+        provider.write(updated.clone());
+        config_manager
+            .apply_route_change(RouteChange::Upsert(Box::new(updated)))
+            .await
+            .unwrap();
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
+        assert_eq!(
+            route.unwrap().require,
+            serde_json::json!({ "roles": ["superuser"] })
+        );
+
+        provider.delete(2);
+        config_manager
+            .apply_route_change(RouteChange::Delete(2))
+            .await
+            .unwrap();
+        assert_eq!(config_manager.get_config().await.routes.len(), 1);
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 1);
+
+        // A full reload still works on demand
+        provider
+            .routes
+            .lock()
+            .unwrap()
+            .push(db_route(3, "/reports/*", "analyst"));
+        config_manager.load_config().await.unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 2);
+        assert_eq!(config_manager.get_config().await.routes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_external_route_change_triggers_full_reload() {
+        let provider = Arc::new(CountingProvider::new(vec![db_route(1, "/*", "user")]));
+        let config_manager = ConfigManager::with_provider(provider.clone());
+        config_manager.load_config().await.unwrap();
+
+        // Unchanged routes are not reloaded
+        assert!(!config_manager.reload_if_changed().await.unwrap());
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 1);
+
+        // Another replica wrote a route as well, so the version doesn't add up
+        provider.external_writes.fetch_add(1, Ordering::SeqCst);
+        let created = db_route(2, "/admin/*", "admin");
+        provider.write(created.clone());
+        config_manager
+            .apply_route_change(RouteChange::Upsert(Box::new(created)))
+            .await
+            .unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 2);
+        assert_eq!(config_manager.get_config().await.routes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_external_route_update_alongside_change_triggers_full_reload() {
+        let provider = Arc::new(CountingProvider::new(Vec::new()));
+        provider.write(db_route(1, "/*", "user"));
+        provider.write(db_route(2, "/admin/*", "admin"));
+        let config_manager = ConfigManager::with_provider(provider.clone());
+        config_manager.load_config().await.unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 1);
+
+        // Another replica updates route 1 while this one updates route 2, so
+        // the route count and latest update both look like a single write
+        provider.write(db_route(1, "/*", "staff"));
+        let updated = db_route(2, "/admin/*", "superuser");
+        provider.write(updated.clone());
+        config_manager
+            .apply_route_change(RouteChange::Upsert(Box::new(updated)))
+            .await
+            .unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 2);
+        let routes = config_manager.get_config().await.routes;
+        assert_eq!(routes[0].require, serde_json::json!({ "roles": ["staff"] }));
+
+        // The same goes for a deletion, which never moves the latest update forward
+        provider.write(db_route(1, "/*", "user"));
+        provider.delete(2);
+        config_manager
+            .apply_route_change(RouteChange::Delete(2))
+            .await
+            .unwrap();
+        assert_eq!(provider.loads.load(Ordering::SeqCst), 3);
+        let routes = config_manager.get_config().await.routes;
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].require, serde_json::json!({ "roles": ["user"] }));
+    }
+
+    #[tokio::test]
+    async fn test_empty_config_refused_at_startup() {
+        let config_manager = ConfigManager::with_provider(Arc::new(CountingProvider::new(vec![])));
+        config_manager.load_config().await.unwrap();
+
+        let err = config_manager.check_not_empty(true).await.unwrap_err();
+        assert!(matches!(err, AuthGateError::ConfigError(_)));
+        assert!(err.to_string().contains("no routes"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_config_only_warned_by_default() {
+        let config_manager = ConfigManager::with_provider(Arc::new(CountingProvider::new(vec![])));
+        config_manager.load_config().await.unwrap();
+        assert!(config_manager.check_not_empty(false).await.is_ok());
+
+        // A configuration with routes passes either way
+        let provider = CountingProvider::new(vec![db_route(1, "/admin/*", "admin")]);
+        let config_manager = ConfigManager::with_provider(Arc::new(provider));
+        config_manager.load_config().await.unwrap();
+        assert!(config_manager.check_not_empty(true).await.is_ok());
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = config_schema();
+
+        let routes = &schema["properties"]["routes"];
+        assert_eq!(routes["type"], "array");
+        assert_eq!(routes["items"]["$ref"], "#/definitions/Route");
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&serde_json::json!("auth")));
+        assert!(required.contains(&serde_json::json!("routes")));
+
+        // Route requirements are described by the require block, not as free-form JSON
+        let route = &schema["definitions"]["Route"]["properties"];
+        assert!(route["require"]
+            .to_string()
+            .contains("#/definitions/RequireConfig"));
+        assert!(route.get("public").is_some());
+        let require = &schema["definitions"]["RequireConfig"]["properties"];
+        for dimension in [
+            "roles",
+            "roles_match",
+            "deny_roles",
+            "nested_groups",
+            "permissions",
+            "scopes",
+            "teams",
+            "email_domains",
+            "any_team",
+            "authenticated",
+            "require_mfa",
+            "expr",
+        ] {
+            assert!(require.get(dimension).is_some(), "{} missing", dimension);
+        }
+
+        // Runtime-only fields aren't part of the file format
+        assert!(schema["properties"].get("version").is_none());
+    }
+
+    /// Config file content with a route for each of `paths`
+    #[cfg(feature = "config_reload")]
+    fn config_file_json(paths: &[&str]) -> String {
+        let routes: Vec<serde_json::Value> = paths
+            .iter()
+            .map(|path| {
+                serde_json::json!({
+                    "host": "app.example.com",
+                    "path": path,
+                    "require": { "roles": ["admin"] }
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "auth": {
+                "session_url": "https://auth.example.com/session",
+                "login_redirect": "https://auth.example.com/login"
+            },
+            "routes": routes
+        })
+        .to_string()
+    }
+
+    #[cfg(feature = "config_reload")]
+    #[tokio::test]
+    async fn test_config_file_changes_reloaded() {
+        use authgate::config::setup_config_watcher;
+        use std::time::Duration;
+
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        std::fs::write(&config_path, config_file_json(&["/admin/*"])).unwrap();
+
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(
+            JsonFileProvider::new(config_path.to_str().unwrap()),
+        )));
+        config_manager.load_config().await.unwrap();
+        let version = config_manager.get_config().await.version;
+        setup_config_watcher(config_manager.clone()).await.unwrap();
+
+        // Two quick writes, as some editors do, trigger a single reload
+        std::fs::write(&config_path, config_file_json(&["/admin/*"])).unwrap();
+        std::fs::write(&config_path, config_file_json(&["/admin/*", "/api/*"])).unwrap();
+        let mut reloaded = false;
+        for _ in 0..40 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if config_manager.get_config().await.routes.len() == 2 {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded, "config file change wasn't picked up");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(config_manager.get_config().await.version, version + 1);
+
+        // An invalid file is rejected and the previous configuration kept
+        std::fs::write(&config_path, "{ not json").unwrap();
+        tokio::time::sleep(Duration::from_millis(750)).await;
+        let config = config_manager.get_config().await;
+        assert_eq!(config.routes.len(), 2);
+        assert_eq!(config.version, version + 1);
+    }
+
+    #[tokio::test]
+    async fn test_loaded_routes_hold_parsed_expr() {
+        use authgate::expr::RequireExpr;
+
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("authgate.json");
+        let expr = "(role:admin OR team:ops) AND NOT role:contractor";
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [
+                    { "host": "app.example.com", "path": "/*", "require": { "expr": expr } },
+                    { "host": "app.example.com", "path": "/public/*", "require": { "roles": ["user"] } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let config_manager = ConfigManager::with_provider(Arc::new(JsonFileProvider::new(
+            config_path.to_str().unwrap(),
+        )));
+        config_manager.load_config().await.unwrap();
+
+        let routes = config_manager.get_config().await.routes;
+        assert_eq!(
+            routes[0].require_expr.as_deref(),
+            Some(&RequireExpr::parse(expr).unwrap())
+        );
+        assert!(routes[1].require_expr.is_none());
+    }
 }
@@ -0,0 +1,170 @@
+#[cfg(test)]
+mod tests {
+    use authgate::authorizer::authorize;
+    use authgate::expr::{RequireExpr, MAX_EXPR_DEPTH, MAX_EXPR_TOKENS};
+    use authgate::types::{AuthResult, Route, Scope, SessionResponse, Team, User};
+
+    fn create_session(roles: Vec<&str>, teams: Vec<&str>) -> SessionResponse {
+        SessionResponse {
+            user: User {
+                id: "user-1".to_string(),
+                email: "user@example.com".to_string(),
+                roles: roles.into_iter().map(|r| r.to_string()).collect(),
+                permissions: vec!["posts:write".to_string()],
+                teams: teams
+                    .into_iter()
+                    .map(|name| Team {
+                        id: format!("{}-id", name),
+                        name: name.to_string(),
+                        is_owner: false,
+                        scopes: vec![Scope {
+                            resource_type: "client".to_string(),
+                            resource_id: "client-1".to_string(),
+                            action: "access".to_string(),
+                        }],
+                    })
+                    .collect(),
+            },
+            tenant_id: "tenant-1".to_string(),
+            authority: "example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn evaluate(expr: &str, session: &SessionResponse) -> bool {
+        RequireExpr::parse(expr).unwrap().evaluate(session, false)
+    }
+
+    #[test]
+    fn test_and_or_not() {
+        let expr = "(role:admin OR team:ops) AND NOT role:contractor";
+
+        assert!(evaluate(expr, &create_session(vec!["admin"], vec![])));
+        assert!(evaluate(expr, &create_session(vec!["viewer"], vec!["ops"])));
+        assert!(!evaluate(
+            expr,
+            &create_session(vec!["admin", "contractor"], vec!["ops"])
+        ));
+        assert!(!evaluate(
+            expr,
+            &create_session(vec!["viewer"], vec!["dev"])
+        ));
+    }
+
+    #[test]
+    fn test_precedence() {
+        // AND binds tighter than OR, NOT tighter than AND
+        let expr = "role:admin OR role:editor AND NOT team:dev";
+
+        assert!(evaluate(expr, &create_session(vec!["admin"], vec!["dev"])));
+        assert!(evaluate(expr, &create_session(vec!["editor"], vec!["ops"])));
+        assert!(!evaluate(
+            expr,
+            &create_session(vec!["editor"], vec!["dev"])
+        ));
+
+        // Operators are case-insensitive and NOT can be repeated
+        assert!(evaluate(
+            "not not role:admin and permission:posts:write",
+            &create_session(vec!["admin"], vec![])
+        ));
+    }
+
+    #[test]
+    fn test_scope_and_team_predicates() {
+        let session = create_session(vec![], vec!["ops"]);
+
+        assert!(evaluate("scope:client:access", &session));
+        assert!(evaluate("scope:client:access:client-1", &session));
+        assert!(!evaluate("scope:client:access:client-2", &session));
+        assert!(evaluate("team:ops-id AND team:ops", &session));
+        assert!(!evaluate("team:dev OR scope:client:delete", &session));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expr in [
+            "",
+            "role:admin AND",
+            "(role:admin OR team:ops",
+            "role:admin)",
+            "role:admin team:ops",
+            "group:admin",
+            "admin",
+            "scope:client",
+        ] {
+            assert!(
+                RequireExpr::parse(expr).is_err(),
+                "Expected `{}` to be rejected",
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn test_authorize_with_expr() {
+        let route = Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({
+                "expr": "(role:admin OR team:ops) AND NOT role:contractor"
+            }),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            authorize(&create_session(vec!["admin"], vec![]), &route),
+            AuthResult::Authorized
+        ));
+        match authorize(&create_session(vec!["contractor"], vec!["ops"]), &route) {
+            AuthResult::Unauthorized(reason) => {
+                assert!(reason.contains("required expression"));
+            }
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_nesting_and_length_are_capped() {
+        let nested = |depth: usize| format!("{}role:admin{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(RequireExpr::parse(&nested(MAX_EXPR_DEPTH)).is_ok());
+        assert!(RequireExpr::parse(&nested(MAX_EXPR_DEPTH + 1)).is_err());
+
+        let negated = |depth: usize| format!("{}role:admin", "NOT ".repeat(depth));
+        assert!(RequireExpr::parse(&negated(MAX_EXPR_DEPTH)).is_ok());
+        assert!(RequireExpr::parse(&negated(MAX_EXPR_DEPTH + 1)).is_err());
+
+        // Long chains nest just as deep once parsed
+        let chain = |terms: usize| vec!["role:admin"; terms].join(" OR ");
+        assert!(RequireExpr::parse(&chain(MAX_EXPR_TOKENS / 2)).is_ok());
+        assert!(RequireExpr::parse(&chain(MAX_EXPR_TOKENS)).is_err());
+    }
+
+    #[test]
+    fn test_expr_follows_nested_groups() {
+        let route = |nested: bool| Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({
+                "expr": "role:/eng AND NOT role:/contractors",
+                "nested_groups": nested,
+            }),
+            ..Default::default()
+        };
+        let backend = create_session(vec!["/eng/backend"], vec![]);
+        let contractor = create_session(vec!["/eng/backend", "/contractors/acme"], vec![]);
+
+        assert!(matches!(
+            authorize(&backend, &route(false)),
+            AuthResult::Unauthorized(_)
+        ));
+        assert!(matches!(
+            authorize(&backend, &route(true)),
+            AuthResult::Authorized
+        ));
+        assert!(matches!(
+            authorize(&contractor, &route(true)),
+            AuthResult::Unauthorized(_)
+        ));
+    }
+}
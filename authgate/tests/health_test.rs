@@ -0,0 +1,288 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use authgate::auth::AuthService;
+    use authgate::cache::SessionCache;
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::ConfigProvider;
+    use authgate::health::{handle_livez, handle_readyz, HealthCache};
+    use authgate::matcher::RouteMatcher;
+    use authgate::proxy::AppState;
+    use authgate::types::{AuthConfig, AuthGateError, Config, Route, SessionResponse};
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::util::ServiceExt;
+
+    /// Config provider serving a fixed configuration
+    struct StaticProvider(Config);
+
+    #[async_trait]
+    impl ConfigProvider for StaticProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Build a router serving `/readyz` for the given session URL
+    async fn build_app(session_url: &str) -> Router {
+        build_app_with_health(session_url, HealthCache::with_interval(Duration::ZERO)).await
+    }
+
+    /// Build a router serving `/readyz` with the given backend health cache
+    async fn build_app_with_health(session_url: &str, health: HealthCache) -> Router {
+        let routes = vec![Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({ "roles": ["user"] }),
+            ..Default::default()
+        }];
+        let config_manager = loaded_config_manager(session_url, routes).await;
+
+        build_app_with_state(config_manager, AuthService::new(), health)
+    }
+
+    /// Config manager with a loaded configuration holding `routes`
+    async fn loaded_config_manager(session_url: &str, routes: Vec<Route>) -> Arc<ConfigManager> {
+        let config = Config {
+            auth: AuthConfig {
+                session_url: session_url.to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes,
+            ..Default::default()
+        };
+
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            config,
+        ))));
+        config_manager.load_config().await.unwrap();
+        config_manager
+    }
+
+    /// Build a router serving `/readyz` and `/livez` for a config manager
+    fn build_app_with_state(
+        config_manager: Arc<ConfigManager>,
+        auth_service: AuthService,
+        health: HealthCache,
+    ) -> Router {
+        let state = AppState {
+            config_manager: config_manager.clone(),
+            route_matcher: Arc::new(RouteMatcher::new(config_manager.get_config_ref())),
+            auth_service: Arc::new(auth_service),
+            health: Arc::new(health),
+        };
+
+        Router::new()
+            .route("/readyz", get(handle_readyz))
+            .route("/livez", get(handle_livez))
+            .with_state(state)
+    }
+
+    /// Spawn a session endpoint, answering every probe as unauthenticated
+    async fn spawn_session_endpoint() -> String {
+        let app = Router::new().route("/session", get(|| async { StatusCode::UNAUTHORIZED }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/session", addr)
+    }
+
+    /// Request `/readyz`, returning the status and the result of each check
+    async fn readyz_checks(app: Router) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .uri("/readyz")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        (status, body["checks"].clone())
+    }
+
+    async fn readyz(app: Router) -> StatusCode {
+        let request = Request::builder()
+            .uri("/readyz")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_readyz_probes_session_endpoint() {
+        std::env::set_var("AUTHGATE_READY_CHECK_SESSION", "true");
+
+        // A reachable session endpoint; any status counts as reachable
+        let app = Router::new().route("/session", get(|| async { StatusCode::UNAUTHORIZED }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let reachable = build_app(&format!("http://{}/session", addr)).await;
+        assert_eq!(readyz(reachable).await, StatusCode::OK);
+
+        // Nothing listens on a port that was bound and released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let unreachable = build_app(&format!("http://{}/session", addr)).await;
+        assert_eq!(readyz(unreachable).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_readyz_caches_backend_probe() {
+        std::env::set_var("AUTHGATE_READY_CHECK_SESSION", "true");
+
+        let probes = Arc::new(AtomicUsize::new(0));
+        let counter = probes.clone();
+        let app = Router::new().route(
+            "/session",
+            get(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    StatusCode::UNAUTHORIZED
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let app = build_app_with_health(
+            &format!("http://{}/session", addr),
+            HealthCache::with_interval(Duration::from_secs(60)),
+        )
+        .await;
+
+        for _ in 0..5 {
+            assert_eq!(readyz(app.clone()).await, StatusCode::OK);
+        }
+        assert_eq!(probes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_readyz_requires_loaded_routes() {
+        let session_url = spawn_session_endpoint().await;
+
+        // Nothing loaded yet
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            Config::default(),
+        ))));
+        let app = build_app_with_state(
+            config_manager,
+            AuthService::new(),
+            HealthCache::with_interval(Duration::ZERO),
+        );
+        let (status, checks) = readyz_checks(app).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(checks["config"], "not loaded");
+
+        // Loaded, but every request would be let through
+        let app = build_app_with_state(
+            loaded_config_manager(&session_url, vec![]).await,
+            AuthService::new(),
+            HealthCache::with_interval(Duration::ZERO),
+        );
+        let (status, checks) = readyz_checks(app).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(checks["config"], "no routes");
+
+        // Loaded with routes
+        let app =
+            build_app_with_health(&session_url, HealthCache::with_interval(Duration::ZERO)).await;
+        let (status, checks) = readyz_checks(app).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(checks["config"], "ok");
+        assert_eq!(checks["cache"], "ok");
+    }
+
+    /// Session cache whose backend can't be reached
+    struct UnreachableCache;
+
+    #[async_trait]
+    impl SessionCache for UnreachableCache {
+        async fn get(&self, _token: &str) -> Option<SessionResponse> {
+            None
+        }
+
+        async fn set(
+            &self,
+            _token: &str,
+            _session: SessionResponse,
+            _ttl: Duration,
+        ) -> Result<(), AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn remove(&self, _token: &str) -> Result<(), AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn increment(&self, _key: &str, _window: Duration) -> Result<u64, AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+
+        async fn ping(&self) -> Result<(), AuthGateError> {
+            Err(AuthGateError::CacheError("backend unavailable".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_unreachable_cache() {
+        let session_url = spawn_session_endpoint().await;
+        let routes = vec![Route {
+            host: "app.example.com".to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({ "roles": ["user"] }),
+            ..Default::default()
+        }];
+
+        let app = build_app_with_state(
+            loaded_config_manager(&session_url, routes).await,
+            AuthService::with_cache(Arc::new(UnreachableCache)),
+            HealthCache::with_interval(Duration::ZERO),
+        );
+        let (status, checks) = readyz_checks(app).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(checks["config"], "ok");
+        assert_eq!(checks["cache"], "unreachable");
+    }
+
+    #[tokio::test]
+    async fn test_livez_ignores_readiness() {
+        // Alive even though no configuration was ever loaded
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            Config::default(),
+        ))));
+        let app = build_app_with_state(
+            config_manager,
+            AuthService::with_cache(Arc::new(UnreachableCache)),
+            HealthCache::with_interval(Duration::ZERO),
+        );
+
+        let request = Request::builder()
+            .uri("/livez")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
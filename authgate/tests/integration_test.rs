@@ -21,6 +21,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![
                 Route {
@@ -32,8 +33,10 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: None,
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
                 Route {
                     id: None,
@@ -44,11 +47,14 @@ mod tests {
                         permissions: None,
                         scopes: None,
                         teams: Some(vec![]),
+                        ..Default::default()
                     })
                     .unwrap(),
+                    ..Default::default()
                 },
             ],
             cookie_name: Some("session".to_string()),
+            ..Default::default()
         };
 
         let config_json = serde_json::to_string_pretty(&config).unwrap();
@@ -69,20 +75,22 @@ mod tests {
 
         // 4. Test route matching
         let route = route_matcher
-            .match_route("app.example.com", "/admin/dashboard")
+            .match_route("app.example.com", "/admin/dashboard", "GET")
             .await;
         assert!(route.is_some());
         assert_eq!(route.as_ref().unwrap().host, "app.example.com");
 
         // 5. Test wildcard route matching
         let route = route_matcher
-            .match_route("client1.client.example.com", "/")
+            .match_route("client1.client.example.com", "/", "GET")
             .await;
         assert!(route.is_some());
         assert_eq!(route.as_ref().unwrap().host, "*.client.example.com");
 
         // 6. Test no match
-        let route = route_matcher.match_route("other.example.com", "/").await;
+        let route = route_matcher
+            .match_route("other.example.com", "/", "GET")
+            .await;
         assert!(route.is_none());
 
         // 7. Test auth service initialization
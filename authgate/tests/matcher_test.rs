@@ -1,8 +1,14 @@
 #[cfg(test)]
 mod tests {
-    use authgate::matcher::RouteMatcher;
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::JsonFileProvider;
+    use authgate::matcher::{
+        canonicalize_path, find_route_overlaps, match_path_template, path_captures,
+        route_specificity, specificity_order, HostMatcher, RouteMatcher, RouteOverlap,
+    };
     use authgate::types::{AuthConfig, Config, RequireConfig, Route};
     use std::sync::Arc;
+    use tempfile::tempdir;
     use tokio::sync::RwLock;
 
     #[tokio::test]
@@ -12,6 +18,7 @@ mod tests {
             auth: AuthConfig {
                 session_url: "https://auth.example.com/session".to_string(),
                 login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
             },
             routes: vec![
                 Route {
@@ -24,6 +31,7 @@ mod tests {
                         "scopes": null,
                         "teams": null
                     }),
+                    ..Default::default()
                 },
                 Route {
                     id: None,
@@ -35,26 +43,1003 @@ mod tests {
                         "scopes": null,
                         "teams": []
                     }),
+                    ..Default::default()
                 },
             ],
             cookie_name: Some("session".to_string()),
+            ..Default::default()
         };
 
         let config_lock = Arc::new(RwLock::new(config));
         let matcher = RouteMatcher::new(config_lock);
 
         // Test exact host match
-        let route = matcher.match_route("app.example.com", "/admin/users").await;
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
         assert!(route.is_some());
         assert_eq!(route.unwrap().host, "app.example.com");
 
         // Test wildcard host match
-        let route = matcher.match_route("client1.client.example.com", "/").await;
+        let route = matcher
+            .match_route("client1.client.example.com", "/", "GET")
+            .await;
         assert!(route.is_some());
         assert_eq!(route.unwrap().host, "*.client.example.com");
 
         // Test no match
-        let route = matcher.match_route("other.example.com", "/").await;
+        let route = matcher.match_route("other.example.com", "/", "GET").await;
         assert!(route.is_none());
     }
+
+    #[tokio::test]
+    async fn test_route_match_cache() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("test-config.json");
+
+        let config = Config {
+            auth: AuthConfig {
+                session_url: "https://auth.example.com/session".to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![Route {
+                id: None,
+                host: "app.example.com".to_string(),
+                path: "/admin/*".to_string(),
+                require: serde_json::to_value(RequireConfig {
+                    roles: Some(vec!["admin".to_string()]),
+                    permissions: None,
+                    scopes: None,
+                    teams: None,
+                    ..Default::default()
+                })
+                .unwrap(),
+                ..Default::default()
+            }],
+            cookie_name: Some("session".to_string()),
+            ..Default::default()
+        };
+        std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+        let config_manager = ConfigManager::with_provider(Arc::new(JsonFileProvider::new(
+            config_path.to_str().unwrap(),
+        )));
+        config_manager.load_config().await.unwrap();
+        let matcher = RouteMatcher::new(config_manager.get_config_ref());
+
+        // The first lookup scans the route table
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
+        assert!(route.is_some());
+        assert_eq!(matcher.scan_count(), 1);
+
+        // The same lookup again is answered from the cache
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
+        assert!(route.is_some());
+        assert_eq!(matcher.scan_count(), 1);
+
+        // Misses are remembered as well
+        assert!(matcher
+            .match_route("app.example.com", "/public", "GET")
+            .await
+            .is_none());
+        assert!(matcher
+            .match_route("app.example.com", "/public", "GET")
+            .await
+            .is_none());
+        assert_eq!(matcher.scan_count(), 2);
+
+        // Reloading the config clears the cache
+        config_manager.load_config().await.unwrap();
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await;
+        assert!(route.is_some());
+        assert_eq!(matcher.scan_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_host_matchers_compiled_once_per_reload() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("test-config.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [
+                    { "host": "app.example.com", "path": "/*", "require": { "roles": ["admin"] } },
+                    { "host": "*.client.example.com", "path": "/*", "require": { "roles": ["user"] } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let config_manager = ConfigManager::with_provider(Arc::new(JsonFileProvider::new(
+            config_path.to_str().unwrap(),
+        )));
+        config_manager.load_config().await.unwrap();
+        let matcher = RouteMatcher::new(config_manager.get_config_ref());
+        assert_eq!(matcher.compile_count(), 0);
+
+        // Each host misses the match cache, so every lookup scans the routes
+        for host in [
+            "app.example.com",
+            "a.client.example.com",
+            "b.client.example.com",
+        ] {
+            assert!(matcher.match_route(host, "/", "GET").await.is_some());
+        }
+        assert!(matcher
+            .match_route("client.example.com", "/", "GET")
+            .await
+            .is_none());
+        assert_eq!(matcher.scan_count(), 4);
+        assert_eq!(matcher.compile_count(), 1);
+
+        // Reloading the config compiles the host patterns again, once
+        config_manager.load_config().await.unwrap();
+        for host in ["app.example.com", "c.client.example.com"] {
+            assert!(matcher.match_route(host, "/", "GET").await.is_some());
+        }
+        assert_eq!(matcher.compile_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_hosts_normalized_once_per_reload() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("test-config.json");
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "auth": {
+                    "session_url": "https://auth.example.com/session",
+                    "login_redirect": "https://auth.example.com/login"
+                },
+                "routes": [
+                    { "host": "app.example.com", "path": "/*", "require": { "roles": ["admin"] } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let config_manager = ConfigManager::with_provider(Arc::new(JsonFileProvider::new(
+            config_path.to_str().unwrap(),
+        )));
+        config_manager.load_config().await.unwrap();
+        let matcher = RouteMatcher::new(config_manager.get_config_ref());
+
+        // Different paths miss the match cache, but the host is normalized once
+        for path in ["/a", "/b", "/c"] {
+            assert!(matcher
+                .match_route("app.example.com:8443", path, "GET")
+                .await
+                .is_some());
+        }
+        assert_eq!(matcher.scan_count(), 3);
+        assert_eq!(matcher.normalize_count(), 1);
+
+        assert!(matcher
+            .match_route("app.example.com", "/a", "GET")
+            .await
+            .is_some());
+        assert_eq!(matcher.normalize_count(), 2);
+
+        // Reloading the config clears the host cache
+        config_manager.load_config().await.unwrap();
+        assert!(matcher
+            .match_route("app.example.com:8443", "/a", "GET")
+            .await
+            .is_some());
+        assert_eq!(matcher.normalize_count(), 3);
+
+        // Without a host cache every request is normalized
+        let matcher = RouteMatcher::new(config_manager.get_config_ref()).with_host_cache_size(0);
+        for _ in 0..2 {
+            assert!(matcher
+                .match_route("app.example.com", "/a", "GET")
+                .await
+                .is_some());
+        }
+        assert_eq!(matcher.normalize_count(), 2);
+    }
+
+    #[test]
+    fn test_host_matcher_compilation() {
+        assert_eq!(
+            HostMatcher::compile("app.example.com"),
+            HostMatcher::Exact("app.example.com".to_string())
+        );
+        let wildcard = HostMatcher::compile("*.example.com");
+        assert_eq!(wildcard, HostMatcher::Wildcard("example.com".to_string()));
+
+        assert!(wildcard.matches("app.example.com"));
+        assert!(!wildcard.matches("a.b.example.com"));
+        assert!(!wildcard.matches("example.com"));
+        assert!(!wildcard.matches("badexample.com"));
+
+        let multi = HostMatcher::compile("**.example.com");
+        assert_eq!(multi, HostMatcher::MultiWildcard("example.com".to_string()));
+        assert!(multi.matches("app.example.com"));
+        assert!(multi.matches("a.b.example.com"));
+        assert!(!multi.matches("example.com"));
+        assert!(!multi.matches("badexample.com"));
+    }
+
+    #[tokio::test]
+    async fn test_single_and_multi_label_wildcards() {
+        let config = Config {
+            routes: vec![
+                specificity_route("**.example.com", "/*", None),
+                specificity_route("*.example.com", "/*", None),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        let matched_host = |host: &'static str| {
+            let matcher = &matcher;
+            async move {
+                matcher
+                    .match_route(host, "/", "GET")
+                    .await
+                    .map(|route| route.host)
+            }
+        };
+
+        // One label is matched by both, and the single-label wildcard is more specific
+        assert_eq!(
+            matched_host("app.example.com").await.as_deref(),
+            Some("*.example.com")
+        );
+        // Deeper hosts are only matched by the multi-level wildcard
+        assert_eq!(
+            matched_host("a.b.example.com").await.as_deref(),
+            Some("**.example.com")
+        );
+        assert_eq!(matched_host("example.com").await, None);
+
+        // Without the multi-level route, deeper hosts match nothing
+        let config = Config {
+            routes: vec![specificity_route("*.example.com", "/*", None)],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        assert!(matcher
+            .match_route("a.b.example.com", "/", "GET")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hosts_matched_case_insensitively() {
+        let route = |host: &str, role: &str| Route {
+            id: None,
+            host: host.to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            ..Default::default()
+        };
+        let config = Config {
+            auth: AuthConfig {
+                session_url: "https://auth.example.com/session".to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![
+                route("app.example.com", "app"),
+                route("*.Client.Example.com.", "client"),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        let role = |route: Option<Route>| route.map(|route| route.require["roles"][0].clone());
+
+        // Uppercase, fully qualified and port-suffixed hosts reach the exact route
+        for host in [
+            "App.Example.COM",
+            "app.example.com.",
+            "app.example.com:8443",
+            "APP.example.com.:8443",
+        ] {
+            assert_eq!(
+                role(matcher.match_route(host, "/", "GET").await),
+                Some(serde_json::json!("app")),
+                "{}",
+                host
+            );
+        }
+
+        // Wildcard patterns are normalized like request hosts
+        for host in ["acme.client.example.com", "ACME.Client.example.com.:443"] {
+            assert_eq!(
+                role(matcher.match_route(host, "/", "GET").await),
+                Some(serde_json::json!("client")),
+                "{}",
+                host
+            );
+        }
+
+        // Only a single trailing dot is dropped
+        assert!(matcher
+            .match_route("app.example.com..", "/", "GET")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_port_specific_routing() {
+        let route = |port: Option<u16>, role: &str| Route {
+            id: None,
+            host: "example.com".to_string(),
+            path: "/*".to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            port,
+            ..Default::default()
+        };
+        let config = Config {
+            auth: AuthConfig {
+                session_url: "https://auth.example.com/session".to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![
+                route(Some(8080), "metrics"),
+                route(Some(9090), "admin"),
+                route(None, "user"),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        // Port taken from the host
+        let matched = matcher
+            .match_route("example.com:8080", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(matched.port, Some(8080));
+        let matched = matcher
+            .match_route("example.com:9090", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(matched.port, Some(9090));
+
+        // Port taken from X-Forwarded-Port
+        let matched = matcher
+            .match_route_with_port("example.com", Some(9090), "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(matched.port, Some(9090));
+
+        // Other or missing ports fall through to the port-less route
+        let matched = matcher
+            .match_route("example.com:3000", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(matched.port, None);
+        let matched = matcher
+            .match_route("example.com", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(matched.port, None);
+    }
+
+    fn specificity_route(host: &str, path: &str, port: Option<u16>) -> Route {
+        Route {
+            id: None,
+            host: host.to_string(),
+            path: path.to_string(),
+            require: serde_json::json!({ "roles": ["user"] }),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_route_specificity_total_order() {
+        // Listed from most to least specific
+        let routes = vec![
+            Route {
+                methods: Some(vec!["POST".to_string()]),
+                ..specificity_route("app.example.com", "/admin/users", None)
+            },
+            Route {
+                methods: Some(vec!["POST".to_string()]),
+                ..specificity_route("**.example.com", "/*", None)
+            },
+            specificity_route("app.example.com", "/admin/users", Some(8443)),
+            specificity_route("app.example.com", "/admin/users", None),
+            specificity_route("app.example.com", "/admin/*", None),
+            specificity_route("app.example.com", "/admin", None),
+            specificity_route("www.app.example.com", "/", None),
+            specificity_route("app.example.com", "/", None),
+            specificity_route("app.example.com", "/*", None),
+            specificity_route("*.eu.example.com", "/admin/users", None),
+            specificity_route("*.example.com", "/admin/users", None),
+            specificity_route("*.example.com", "/*", None),
+            specificity_route("**.example.com", "/admin/users", None),
+            specificity_route("**.example.com", "/*", None),
+        ];
+
+        for pair in routes.windows(2) {
+            assert!(
+                route_specificity(&pair[0]) < route_specificity(&pair[1]),
+                "{}{} should be more specific than {}{}",
+                pair[0].host,
+                pair[0].path,
+                pair[1].host,
+                pair[1].path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_most_specific_route_wins() {
+        // Listed from least to most specific, so config order never decides
+        let config = Config {
+            auth: AuthConfig {
+                session_url: "https://auth.example.com/session".to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![
+                specificity_route("*.example.com", "/*", None),
+                specificity_route("*.example.com", "/admin/*", None),
+                specificity_route("app.example.com", "/*", None),
+                specificity_route("app.example.com", "/admin/*", None),
+                specificity_route("app.example.com", "/admin/*", Some(8443)),
+                specificity_route("app.example.com", "/admin/users", None),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        let matched = |host: &'static str, path: &'static str| {
+            let matcher = &matcher;
+            async move {
+                let route = matcher.match_route(host, path, "GET").await.unwrap();
+                (route.host, route.path, route.port)
+            }
+        };
+
+        // Exact host beats wildcard host, even with a longer wildcard path
+        assert_eq!(
+            matched("app.example.com", "/reports").await,
+            ("app.example.com".to_string(), "/*".to_string(), None)
+        );
+        // Longer prefix beats shorter prefix
+        assert_eq!(
+            matched("app.example.com", "/admin/settings").await,
+            ("app.example.com".to_string(), "/admin/*".to_string(), None)
+        );
+        // Exact path beats prefix
+        assert_eq!(
+            matched("app.example.com", "/admin/users").await,
+            (
+                "app.example.com".to_string(),
+                "/admin/users".to_string(),
+                None
+            )
+        );
+        // Port-specific beats any port
+        assert_eq!(
+            matched("app.example.com:8443", "/admin/users").await,
+            (
+                "app.example.com".to_string(),
+                "/admin/*".to_string(),
+                Some(8443)
+            )
+        );
+        // Wildcard hosts fall back to the longest wildcard prefix
+        assert_eq!(
+            matched("api.example.com", "/admin/users").await,
+            ("*.example.com".to_string(), "/admin/*".to_string(), None)
+        );
+        assert_eq!(
+            matched("api.example.com", "/").await,
+            ("*.example.com".to_string(), "/*".to_string(), None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_specific_route_after_general_route_wins() {
+        let routes = vec![
+            specificity_route("*.example.com", "/", None),
+            specificity_route("*.example.com", "/*", None),
+            specificity_route("app.example.com", "/admin", None),
+            specificity_route("app.example.com", "/admin/*", None),
+        ];
+
+        // Ranked once per config load, keeping config order for equal keys
+        assert_eq!(specificity_order(&routes), vec![3, 2, 0, 1]);
+
+        let config = Config {
+            routes,
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        let route = matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await
+            .unwrap();
+        assert_eq!(
+            (route.host.as_str(), route.path.as_str()),
+            ("app.example.com", "/admin/*")
+        );
+        let route = matcher
+            .match_route("www.example.com", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(
+            (route.host.as_str(), route.path.as_str()),
+            ("*.example.com", "/")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_equally_specific_routes_use_config_order() {
+        let mut first = specificity_route("app.example.com", "/*", None);
+        first.require = serde_json::json!({ "roles": ["first"] });
+        let mut second = specificity_route("app.example.com", "/*", None);
+        second.require = serde_json::json!({ "roles": ["second"] });
+
+        let config = Config {
+            routes: vec![first, second],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        let route = matcher
+            .match_route("app.example.com", "/", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.require, serde_json::json!({ "roles": ["first"] }));
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_overlap_detected_with_deterministic_winner() {
+        let routes = vec![
+            specificity_route("*.example.com", "/a/*", None),
+            specificity_route("app.example.com", "/a/b", None),
+            // Nested paths on a single host are not reported
+            specificity_route("app.example.com", "/a/*", None),
+            // Disjoint from the wildcard route's paths
+            specificity_route("api.example.com", "/b", None),
+            // Different required ports never match the same request
+            specificity_route("*.example.com", "/c/*", Some(8443)),
+            specificity_route("*.eu.example.com", "/c/*", Some(9443)),
+        ];
+
+        let overlaps = find_route_overlaps(&routes);
+        assert_eq!(
+            overlaps,
+            vec![
+                RouteOverlap {
+                    winner: 1,
+                    shadowed: 0
+                },
+                RouteOverlap {
+                    winner: 2,
+                    shadowed: 0
+                },
+            ]
+        );
+
+        // The matcher resolves the overlap the same way
+        let config = Config {
+            routes,
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        let route = matcher
+            .match_route("app.example.com", "/a/b", "GET")
+            .await
+            .unwrap();
+        assert_eq!(
+            (route.host.as_str(), route.path.as_str()),
+            ("app.example.com", "/a/b")
+        );
+    }
+
+    #[test]
+    fn test_duplicate_routes_reported_as_overlap() {
+        let routes = vec![
+            specificity_route("app.example.com", "/*", None),
+            specificity_route("app.example.com", "/*", None),
+        ];
+
+        // Equally specific, so the first in config order wins
+        assert_eq!(
+            find_route_overlaps(&routes),
+            vec![RouteOverlap {
+                winner: 0,
+                shadowed: 1
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_matches_bare_prefix() {
+        let config = Config {
+            routes: vec![specificity_route("app.example.com", "/admin/*", None)],
+            ..Default::default()
+        };
+        let config = Arc::new(RwLock::new(config));
+
+        let matcher = RouteMatcher::new(config.clone()).with_wildcard_bare_prefix(true);
+        assert!(matcher
+            .match_route("app.example.com", "/admin", "GET")
+            .await
+            .is_some());
+        assert!(matcher
+            .match_route("app.example.com", "/admin/", "GET")
+            .await
+            .is_some());
+        assert!(matcher
+            .match_route("app.example.com", "/admin/users", "GET")
+            .await
+            .is_some());
+        // Only whole path segments are covered
+        assert!(matcher
+            .match_route("app.example.com", "/administrator", "GET")
+            .await
+            .is_none());
+
+        // Disabled, the trailing slash is required
+        let matcher = RouteMatcher::new(config).with_wildcard_bare_prefix(false);
+        assert!(matcher
+            .match_route("app.example.com", "/admin", "GET")
+            .await
+            .is_none());
+        assert!(matcher
+            .match_route("app.example.com", "/administrator", "GET")
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_canonicalize_path() {
+        for (path, expected) in [
+            ("/admin/../secret", "/secret"),
+            ("/admin//users", "/admin/users"),
+            ("/admin/./users/", "/admin/users/"),
+            ("/admin/%2E%2e/secret", "/secret"),
+            ("/../../etc", "/etc"),
+            ("/admin/..", "/"),
+            ("/public/../admin?next=/a/../b", "/admin?next=/a/../b"),
+            ("/admin%2f..%2fsecret", "/secret"),
+            ("/admin%2F%2E%2E%2Fsecret", "/secret"),
+            ("/admin\\..\\secret", "/secret"),
+            ("/admin%5c..%5Csecret", "/secret"),
+            ("/files%2fa.txt", "/files/a.txt"),
+            ("/%61dmin/%7Euser", "/admin/~user"),
+            ("/admin/%2E%2E/%73ecret", "/secret"),
+            ("/files/a%20b%3F", "/files/a%20b%3F"),
+            ("/caf\u{e9}/%zz", "/caf\u{e9}/%zz"),
+            ("/", "/"),
+        ] {
+            assert_eq!(canonicalize_path(path), expected, "{}", path);
+        }
+    }
+
+    async fn matched_path(matcher: &RouteMatcher, path: &str) -> Option<String> {
+        matcher
+            .match_route("app.example.com", &matcher.request_path(path), "GET")
+            .await
+            .map(|route| route.path)
+    }
+
+    #[tokio::test]
+    async fn test_request_paths_canonicalized_before_matching() {
+        let config = Config {
+            routes: vec![
+                specificity_route("app.example.com", "/admin/*", None),
+                specificity_route("app.example.com", "/secret", None),
+                specificity_route("app.example.com", "/admin/users", None),
+            ],
+            ..Default::default()
+        };
+        let config = Arc::new(RwLock::new(config));
+        let matcher = RouteMatcher::new(config.clone()).with_path_canonicalization(true);
+        assert_eq!(
+            matched_path(&matcher, "/admin/../secret").await.as_deref(),
+            Some("/secret")
+        );
+        assert_eq!(
+            matched_path(&matcher, "/admin//users").await.as_deref(),
+            Some("/admin/users")
+        );
+        assert_eq!(matched_path(&matcher, "/admin/../public").await, None);
+        assert_eq!(
+            matched_path(&matcher, "/%61dmin/secret").await.as_deref(),
+            Some("/admin/*")
+        );
+        assert_eq!(
+            matched_path(&matcher, "/public%2f..%2fadmin%2fusers")
+                .await
+                .as_deref(),
+            Some("/admin/users")
+        );
+
+        // Disabled, dot segments and repeated slashes are matched literally
+        let matcher = RouteMatcher::new(config).with_path_canonicalization(false);
+        assert_eq!(
+            matched_path(&matcher, "/admin/../secret").await.as_deref(),
+            Some("/admin/*")
+        );
+        assert_eq!(
+            matched_path(&matcher, "/admin//users").await.as_deref(),
+            Some("/admin/*")
+        );
+    }
+
+    #[test]
+    fn test_path_template_captures() {
+        assert_eq!(
+            match_path_template("/users/{id}/profile", "/users/42/profile"),
+            Some(vec![("id".to_string(), "42".to_string())])
+        );
+        assert_eq!(
+            match_path_template("/orgs/{org}/users/{id}", "/orgs/acme/users/7?tab=1"),
+            Some(vec![
+                ("org".to_string(), "acme".to_string()),
+                ("id".to_string(), "7".to_string())
+            ])
+        );
+        assert_eq!(
+            match_path_template("/users/{id}/*", "/users/42/settings/email"),
+            Some(vec![("id".to_string(), "42".to_string())])
+        );
+
+        // A named segment matches exactly one non-empty segment
+        assert_eq!(
+            match_path_template("/users/{id}/profile", "/users//profile"),
+            None
+        );
+        assert_eq!(
+            match_path_template("/users/{id}/profile", "/users/1/2/profile"),
+            None
+        );
+        assert_eq!(
+            match_path_template("/users/{id}/profile", "/users/42/profile/edit"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_template_route_matching() {
+        let config = Config {
+            routes: vec![
+                specificity_route("app.example.com", "/users/*", None),
+                specificity_route("app.example.com", "/users/{id}/profile", None),
+                specificity_route("app.example.com", "/users/me/profile", None),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        // A template is more specific than a prefix with the same literal start
+        let route = matcher
+            .match_route("app.example.com", "/users/42/profile", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.path, "/users/{id}/profile");
+        assert_eq!(
+            path_captures(&route, "/users/42/profile"),
+            vec![("id".to_string(), "42".to_string())]
+        );
+
+        // ...but a longer literal path still wins
+        let route = matcher
+            .match_route("app.example.com", "/users/me/profile", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.path, "/users/me/profile");
+        assert!(path_captures(&route, "/users/me/profile").is_empty());
+
+        let route = matcher
+            .match_route("app.example.com", "/users/42/settings", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.path, "/users/*");
+    }
+
+    fn method_route(path: &str, methods: Option<&[&str]>, role: &str) -> Route {
+        Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: path.to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            methods: methods.map(|methods| methods.iter().map(|m| m.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_method_route_matching() {
+        let config = Config {
+            routes: vec![
+                method_route("/api/items", Some(&["GET"]), "viewer"),
+                method_route("/api/items", Some(&["post", "PUT"]), "editor"),
+                method_route("/api/*", None, "user"),
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+        let required_role = |route: Option<Route>| route.unwrap().require["roles"][0].clone();
+
+        assert_eq!(
+            required_role(
+                matcher
+                    .match_route("app.example.com", "/api/items", "GET")
+                    .await
+            ),
+            "viewer"
+        );
+        // A POST doesn't match the GET-only route, and methods compare case-insensitively
+        assert_eq!(
+            required_role(
+                matcher
+                    .match_route("app.example.com", "/api/items", "POST")
+                    .await
+            ),
+            "editor"
+        );
+        assert_eq!(
+            required_role(
+                matcher
+                    .match_route("app.example.com", "/api/items", "put")
+                    .await
+            ),
+            "editor"
+        );
+        // Routes without methods match any method
+        assert_eq!(
+            required_role(
+                matcher
+                    .match_route("app.example.com", "/api/items", "DELETE")
+                    .await
+            ),
+            "user"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_does_not_match_get_only_route() {
+        let config = Config {
+            routes: vec![method_route("/reports", Some(&["GET"]), "viewer")],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        assert!(matcher
+            .match_route("app.example.com", "/reports", "GET")
+            .await
+            .is_some());
+        assert!(matcher
+            .match_route("app.example.com", "/reports", "POST")
+            .await
+            .is_none());
+        // Match results are cached per method
+        assert!(matcher
+            .match_route("app.example.com", "/reports", "GET")
+            .await
+            .is_some());
+    }
+
+    #[test]
+    fn test_routes_with_disjoint_methods_do_not_overlap() {
+        let routes = vec![
+            method_route("/api/items", Some(&["GET"]), "viewer"),
+            method_route("/api/items", Some(&["POST"]), "editor"),
+            method_route("/api/items", Some(&["post", "DELETE"]), "admin"),
+        ];
+
+        assert_eq!(
+            find_route_overlaps(&routes),
+            vec![RouteOverlap {
+                winner: 1,
+                shadowed: 2
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_string_does_not_bypass_route_matching() {
+        let config = Config {
+            routes: vec![
+                specificity_route("app.example.com", "/*", None),
+                specificity_route("app.example.com", "/admin/*", None),
+                specificity_route("app.example.com", "/login", None),
+            ],
+            ..Default::default()
+        };
+        let matcher =
+            RouteMatcher::new(Arc::new(RwLock::new(config))).with_wildcard_bare_prefix(true);
+
+        // The bare prefix and exact paths match whatever query or fragment follows
+        for path in [
+            "/admin?x=1",
+            "/admin#top",
+            "/admin/?x=1",
+            "/admin/users?x=1",
+        ] {
+            assert_eq!(
+                matched_path(&matcher, path).await.as_deref(),
+                Some("/admin/*"),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            matched_path(&matcher, "/login?next=/admin")
+                .await
+                .as_deref(),
+            Some("/login")
+        );
+        assert_eq!(
+            matched_path(&matcher, "/public?next=/admin")
+                .await
+                .as_deref(),
+            Some("/*")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_method_specific_route_wins_over_more_specific_host() {
+        let config = Config {
+            routes: vec![
+                specificity_route("app.example.com", "/api/items", None),
+                Route {
+                    methods: Some(vec!["POST".to_string()]),
+                    ..specificity_route("*.example.com", "/api/*", None)
+                },
+            ],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config)));
+
+        let route = matcher
+            .match_route("app.example.com", "/api/items", "POST")
+            .await
+            .unwrap();
+        assert_eq!(route.host, "*.example.com");
+        let route = matcher
+            .match_route("app.example.com", "/api/items", "GET")
+            .await
+            .unwrap();
+        assert_eq!(route.host, "app.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_route_cache_evicts_least_recently_used_path() {
+        let config = Config {
+            routes: vec![specificity_route("app.example.com", "/*", None)],
+            ..Default::default()
+        };
+        let matcher = RouteMatcher::new(Arc::new(RwLock::new(config))).with_route_cache_size(2);
+
+        // Varying the query string reuses the cached result
+        for path in ["/a", "/a?x=1", "/a?x=2", "/a#top"] {
+            assert!(matched_path(&matcher, path).await.is_some());
+        }
+        assert_eq!(matcher.scan_count(), 1);
+
+        // The least recently used path is evicted first
+        matched_path(&matcher, "/b").await;
+        matched_path(&matcher, "/a").await;
+        matched_path(&matcher, "/c").await;
+        assert_eq!(matcher.scan_count(), 3);
+        matched_path(&matcher, "/a").await;
+        assert_eq!(matcher.scan_count(), 3);
+        matched_path(&matcher, "/b").await;
+        assert_eq!(matcher.scan_count(), 4);
+    }
 }
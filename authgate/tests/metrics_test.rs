@@ -0,0 +1,283 @@
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use async_trait::async_trait;
+    use authgate::auth::AuthService;
+    use authgate::breaker::BreakerState;
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::ConfigProvider;
+    use authgate::health::HealthCache;
+    use authgate::matcher::RouteMatcher;
+    use authgate::metrics::{
+        handle_metrics, install, record_breaker_state, RouteMetrics, OTHER_LABEL, ROUTE_METRICS,
+    };
+    use authgate::proxy::{handle_forward_auth, AppState};
+    use authgate::types::{AuthConfig, AuthGateError, Config, Route, SessionResponse, User};
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::{header, StatusCode};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use std::sync::Arc;
+    use tower::util::ServiceExt;
+
+    /// Serializes tests that read or toggle `AUTHGATE_METRICS_ENABLED`
+    static METRICS_ENV: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Config provider serving a fixed configuration
+    struct StaticProvider(Config);
+
+    #[async_trait]
+    impl ConfigProvider for StaticProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Value of a series in rendered metrics, 0 if it hasn't been recorded
+    fn sample(rendered: &str, series: &str) -> f64 {
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+            .map_or(0.0, |value| value.parse().unwrap())
+    }
+
+    fn route(host: &str, path: &str) -> Route {
+        Route {
+            id: None,
+            host: host.to_string(),
+            path: path.to_string(),
+            require: serde_json::json!({ "roles": ["admin"] }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_auth_counts_decision_per_route() {
+        let config = Config {
+            auth: AuthConfig {
+                session_url: "http://127.0.0.1:1/session".to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![route("metrics.example.com", "/admin/*")],
+            cookie_name: Some("session".to_string()),
+            ..Default::default()
+        };
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            config,
+        ))));
+        config_manager.load_config().await.unwrap();
+
+        let app = Router::new()
+            .route("/auth", get(handle_forward_auth))
+            .with_state(AppState {
+                config_manager: config_manager.clone(),
+                route_matcher: Arc::new(RouteMatcher::new(config_manager.get_config_ref())),
+                auth_service: Arc::new(AuthService::new()),
+                health: Arc::new(HealthCache::new()),
+            });
+
+        let series = "authgate_route_decisions_total{host=\"metrics.example.com\",path=\"/admin/*\",decision=\"unauthenticated\"}";
+        let before = sample(&install().render(), series);
+
+        // No session cookie, so the request is sent to the login page
+        let request = Request::builder()
+            .uri("/auth")
+            .header("X-Forwarded-Proto", "https")
+            .header("X-Forwarded-Host", "metrics.example.com")
+            .header("X-Forwarded-Uri", "/admin/users")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        assert_eq!(sample(&install().render(), series), before + 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_include_session_cache_stats() {
+        let _env = METRICS_ENV.lock().await;
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            Config::default(),
+        ))));
+        let auth_service = Arc::new(AuthService::new());
+
+        let app = Router::new()
+            .route("/metrics", get(handle_metrics))
+            .with_state(AppState {
+                config_manager: config_manager.clone(),
+                route_matcher: Arc::new(RouteMatcher::new(config_manager.get_config_ref())),
+                auth_service: auth_service.clone(),
+                health: Arc::new(HealthCache::new()),
+            });
+
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        // Counters only grow, so other tests' lookups may already be counted
+        for name in [
+            "# TYPE authgate_session_cache_hits_total counter",
+            "# TYPE authgate_session_cache_misses_total counter",
+            "# TYPE authgate_session_cache_sets_total counter",
+            "# TYPE authgate_session_cache_hit_ratio gauge",
+        ] {
+            assert!(body.contains(name), "missing {} in:\n{}", name, body);
+        }
+        assert!(body.contains("authgate_session_cache_hit_ratio 0"));
+    }
+
+    /// Spawn a session endpoint accepting any token as an admin session
+    async fn spawn_session_server() -> String {
+        let app = Router::new().route(
+            "/session",
+            get(|| async {
+                Json(SessionResponse {
+                    user: User {
+                        id: "user-1".to_string(),
+                        roles: vec!["admin".to_string()],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/session", addr)
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_after_request() {
+        let _env = METRICS_ENV.lock().await;
+        let config = Config {
+            auth: AuthConfig {
+                session_url: spawn_session_server().await,
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes: vec![route("scrape.example.com", "/admin/*")],
+            cookie_name: Some("session".to_string()),
+            ..Default::default()
+        };
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            config,
+        ))));
+        config_manager.load_config().await.unwrap();
+
+        let app = Router::new()
+            .route("/auth", get(handle_forward_auth))
+            .route("/metrics", get(handle_metrics))
+            .with_state(AppState {
+                config_manager: config_manager.clone(),
+                route_matcher: Arc::new(RouteMatcher::new(config_manager.get_config_ref())),
+                auth_service: Arc::new(AuthService::new()),
+                health: Arc::new(HealthCache::new()),
+            });
+
+        let before = install().render();
+        let request = Request::builder()
+            .uri("/auth")
+            .header("X-Forwarded-Proto", "https")
+            .header("X-Forwarded-Host", "scrape.example.com")
+            .header("X-Forwarded-Uri", "/admin/users")
+            .header(header::COOKIE, "session=scrape-token")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let after = install().render();
+        for series in [
+            "authgate_auth_requests_total",
+            "authgate_session_validation_duration_seconds_count",
+        ] {
+            assert!(
+                sample(&after, series) > sample(&before, series),
+                "{}",
+                series
+            );
+        }
+
+        let scrape = || {
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(scrape()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        for name in [
+            "# TYPE authgate_auth_requests_total counter",
+            "authgate_route_decisions_total{host=\"scrape.example.com\",path=\"/admin/*\",decision=\"authorized\"} 1",
+            "# TYPE authgate_session_validation_duration_seconds histogram",
+            "authgate_session_validation_duration_seconds_bucket{le=\"+Inf\"}",
+            "authgate_session_validation_duration_seconds_count",
+            "# TYPE authgate_session_cache_misses_total counter",
+            "# TYPE authgate_session_cache_hit_ratio gauge",
+        ] {
+            assert!(body.contains(name), "missing {} in:\n{}", name, body);
+        }
+
+        // The endpoint can be turned off
+        std::env::set_var("AUTHGATE_METRICS_ENABLED", "false");
+        let response = app.oneshot(scrape()).await.unwrap();
+        std::env::remove_var("AUTHGATE_METRICS_ENABLED");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_route_labels_are_capped() {
+        let metrics = RouteMetrics::with_max_routes(1);
+        let labels = |host: &str| metrics.labels(&route(host, "/*"));
+
+        assert_eq!(
+            labels("a.example.com"),
+            ("a.example.com".into(), "/*".into())
+        );
+        assert_eq!(
+            labels("b.example.com"),
+            (OTHER_LABEL.into(), OTHER_LABEL.into())
+        );
+        assert_eq!(
+            labels("c.example.com"),
+            (OTHER_LABEL.into(), OTHER_LABEL.into())
+        );
+        assert_eq!(
+            labels("a.example.com"),
+            ("a.example.com".into(), "/*".into())
+        );
+    }
+
+    #[test]
+    fn test_render_escapes_labels() {
+        ROUTE_METRICS.record(&route("escape.example.com", "/say\"hi\""), "authorized");
+
+        assert!(install().render().contains(
+            "authgate_route_decisions_total{host=\"escape.example.com\",path=\"/say\\\"hi\\\"\",decision=\"authorized\"} 1"
+        ));
+    }
+
+    #[test]
+    fn test_render_breaker_state() {
+        record_breaker_state(BreakerState::HalfOpen);
+        let rendered = install().render();
+
+        assert!(rendered.contains("authgate_session_breaker_state{state=\"closed\"} 0"));
+        assert!(rendered.contains("authgate_session_breaker_state{state=\"open\"} 0"));
+        assert!(rendered.contains("authgate_session_breaker_state{state=\"half_open\"} 1"));
+    }
+}
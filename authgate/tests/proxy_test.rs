@@ -1,8 +1,165 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
-    use authgate::types::{RequestContext, Route, Scope, SessionResponse, Team, User};
+    use crate::common::capture_logs;
+    use async_trait::async_trait;
+    use authgate::auth::{AuthService, LongUrlPolicy, TokenSource};
+    use authgate::cache::{InMemoryCache, SessionCache};
+    use authgate::config::ConfigManager;
+    use authgate::config_provider::ConfigProvider;
+    use authgate::health::HealthCache;
+    use authgate::matcher::RouteMatcher;
+    use authgate::proxy::{handle_forward_auth, handle_logout, unauth_status, AppState};
+    use authgate::types::{
+        AuthConfig, AuthGateError, Config, RateLimit, RateLimitKey, RequestContext, Route, Scope,
+        SessionResponse, Team, User,
+    };
+    use authgate::webhook::DenialWebhook;
+    use axum::body::Body;
+    use axum::extract::Request;
     use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
     use http::header;
+    use std::sync::Arc;
+    use tower::util::ServiceExt;
+
+    /// Serializes tests that change how unauthenticated requests are answered
+    static UNAUTH_ENV: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Config provider serving a fixed configuration
+    struct StaticProvider(Config);
+
+    #[async_trait]
+    impl ConfigProvider for StaticProvider {
+        async fn load_config(&self) -> Result<Config, AuthGateError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn create_test_session() -> SessionResponse {
+        SessionResponse {
+            user: User {
+                id: "user-1".to_string(),
+                email: "user@example.com".to_string(),
+                roles: vec!["admin".to_string(), "user".to_string()],
+                permissions: vec!["users:read".to_string()],
+                teams: vec![Team {
+                    id: "team-1".to_string(),
+                    name: "Team 1".to_string(),
+                    is_owner: true,
+                    scopes: vec![],
+                }],
+            },
+            tenant_id: "tenant-1".to_string(),
+            authority: "example.com".to_string(),
+            redirect_url: None,
+            ..Default::default()
+        }
+    }
+
+    /// Start a mock session endpoint that always returns the given session
+    async fn spawn_session_server(session: SessionResponse) -> String {
+        spawn_session_server_with(move |_| Some(session.clone())).await
+    }
+
+    /// Start a mock session endpoint resolving the session cookie with `resolve`
+    async fn spawn_session_server_with<F>(resolve: F) -> String
+    where
+        F: Fn(&str) -> Option<SessionResponse> + Clone + Send + Sync + 'static,
+    {
+        let app = Router::new().route(
+            "/session",
+            get(move |headers: HeaderMap| async move {
+                let token = headers
+                    .get(header::COOKIE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("session="))
+                    .unwrap_or_default();
+                match resolve(token) {
+                    Some(session) => Json(session).into_response(),
+                    None => StatusCode::UNAUTHORIZED.into_response(),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/session", addr)
+    }
+
+    fn create_user_session(user_id: &str) -> SessionResponse {
+        let mut session = create_test_session();
+        session.user.id = user_id.to_string();
+        session.user.email = format!("{}@example.com", user_id);
+        session
+    }
+
+    /// Build the forward auth router for the given session URL and routes
+    async fn build_app(session_url: &str, routes: Vec<Route>) -> Router {
+        build_app_with_auth(session_url, routes, AuthService::new()).await
+    }
+
+    /// Build the forward auth router with the given auth service
+    async fn build_app_with_auth(
+        session_url: &str,
+        routes: Vec<Route>,
+        auth_service: AuthService,
+    ) -> Router {
+        let config = Config {
+            auth: AuthConfig {
+                session_url: session_url.to_string(),
+                login_redirect: "https://auth.example.com/login".to_string(),
+                ..Default::default()
+            },
+            routes,
+            cookie_name: Some("session".to_string()),
+            ..Default::default()
+        };
+
+        let config_manager = Arc::new(ConfigManager::with_provider(Arc::new(StaticProvider(
+            config,
+        ))));
+        config_manager.load_config().await.unwrap();
+
+        let state = AppState {
+            config_manager: config_manager.clone(),
+            route_matcher: Arc::new(RouteMatcher::new(config_manager.get_config_ref())),
+            auth_service: Arc::new(auth_service),
+            health: Arc::new(HealthCache::new()),
+        };
+
+        Router::new()
+            .route("/auth", get(handle_forward_auth))
+            .route("/auth/logout", post(handle_logout))
+            .with_state(state)
+    }
+
+    fn admin_route() -> Route {
+        Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/admin/*".to_string(),
+            require: serde_json::json!({ "roles": ["admin"] }),
+            ..Default::default()
+        }
+    }
+
+    fn forward_auth_request(host: &str, uri: &str, session_token: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder()
+            .uri("/auth")
+            .header("X-Forwarded-Proto", "https")
+            .header("X-Forwarded-Host", host)
+            .header("X-Forwarded-Uri", uri);
+        if let Some(token) = session_token {
+            builder = builder.header(header::COOKIE, format!("session={}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
 
     #[test]
     fn test_auth_headers() {
@@ -30,6 +187,7 @@ mod tests {
             tenant_id: "tenant-1".to_string(),
             authority: "example.com".to_string(),
             redirect_url: None,
+            ..Default::default()
         };
 
         // Create a RequestContext with the session
@@ -49,7 +207,10 @@ mod tests {
                     "scopes": null,
                     "teams": null
                 }),
+                ..Default::default()
             }),
+            method: "GET".to_string(),
+            path_captures: Vec::new(),
         };
 
         // Create an authorized response using the same logic as in proxy.rs
@@ -118,4 +279,1330 @@ mod tests {
 
         assert_eq!(session_token, Some("test-token".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_cache_debug_header() {
+        std::env::set_var("AUTHGATE_DEBUG_HEADERS", "true");
+
+        let session_url = spawn_session_server(create_test_session()).await;
+        let app = build_app(&session_url, vec![admin_route()]).await;
+
+        // The first request has to go to the session endpoint
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("cache-debug-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("X-Auth-Cache").unwrap(), "miss");
+        assert!(response.headers().get("X-Auth-Cache-TTL").is_none());
+
+        // The second request for the same token is served from the cache
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("cache-debug-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("X-Auth-Cache").unwrap(), "hit");
+        assert!(response.headers().get("X-Auth-Cache-TTL").is_some());
+    }
+
+    /// The `X-Auth-*` headers of a response, leaving out the cache debug headers
+    fn auth_headers(response: &axum::response::Response) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_string()))
+            .filter(|(name, _)| name.starts_with("x-auth-") && !name.starts_with("x-auth-cache"))
+            .collect();
+        headers.sort();
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_emits_same_headers_as_miss() {
+        let _env = UNAUTH_ENV.lock().await;
+        // Cache miss: the session is fetched from the session endpoint
+        let session_url = spawn_session_server(create_test_session()).await;
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let miss = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("cache-miss-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(miss.status(), StatusCode::OK);
+
+        // Cache hit: the session is pre-populated and the session endpoint is
+        // unreachable, so the response can only come from the cache
+        let cache = Arc::new(InMemoryCache::new());
+        cache
+            .set(
+                "cache-hit-token",
+                create_test_session(),
+                std::time::Duration::from_secs(300),
+            )
+            .await
+            .unwrap();
+        let app = build_app_with_auth(
+            "http://127.0.0.1:1/session",
+            vec![admin_route()],
+            AuthService::with_cache(cache),
+        )
+        .await;
+        let hit = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("cache-hit-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(hit.status(), StatusCode::OK);
+
+        let headers = auth_headers(&hit);
+        assert!(headers
+            .iter()
+            .any(|(name, value)| name == "x-auth-user-id" && value == "user-1"));
+        assert_eq!(headers, auth_headers(&miss));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_team_and_scope_headers() {
+        let _env = UNAUTH_ENV.lock().await;
+        let mut session = create_test_session();
+        session.user.teams = vec![
+            Team {
+                id: "team-1".to_string(),
+                name: "Engineering".to_string(),
+                is_owner: true,
+                scopes: vec![Scope {
+                    resource_type: "project".to_string(),
+                    resource_id: "p-1".to_string(),
+                    action: "write".to_string(),
+                }],
+            },
+            Team {
+                id: "team-2".to_string(),
+                name: "Sales".to_string(),
+                is_owner: false,
+                scopes: vec![
+                    Scope {
+                        resource_type: "project".to_string(),
+                        resource_id: "p-1".to_string(),
+                        action: "write".to_string(),
+                    },
+                    Scope {
+                        resource_type: "report".to_string(),
+                        resource_id: "r-9".to_string(),
+                        action: "read".to_string(),
+                    },
+                ],
+            },
+        ];
+        let session_url = spawn_session_server(session).await;
+        let request =
+            || forward_auth_request("app.example.com", "/admin/dashboard", Some("valid-token"));
+
+        // Tenant and team IDs by default, without scopes
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers["X-Auth-Tenant-Id"], "tenant-1");
+        assert_eq!(headers["X-Auth-User-Teams"], "team-1,team-2");
+        assert!(headers.get("X-Auth-Scopes").is_none());
+
+        // Team names and deduplicated scopes on request, read when the auth
+        // service is built
+        std::env::set_var("AUTHGATE_TEAMS_HEADER", "id_name");
+        std::env::set_var("AUTHGATE_SCOPES_HEADER", "true");
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let response = app.oneshot(request()).await.unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers["X-Auth-User-Teams"],
+            "team-1:Engineering,team-2:Sales"
+        );
+        assert_eq!(
+            headers["X-Auth-Scopes"],
+            "project:write:p-1,report:read:r-9"
+        );
+
+        // Each header can be turned off
+        std::env::set_var("AUTHGATE_TEAMS_HEADER", "off");
+        std::env::set_var("AUTHGATE_TENANT_HEADER", "false");
+        std::env::remove_var("AUTHGATE_SCOPES_HEADER");
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let response = app.oneshot(request()).await.unwrap();
+        let headers = response.headers();
+        assert!(headers.get("X-Auth-Tenant-Id").is_none());
+        assert!(headers.get("X-Auth-User-Teams").is_none());
+        assert!(headers.get("X-Auth-Scopes").is_none());
+        assert_eq!(headers["X-Auth-User-Id"], "user-1");
+
+        std::env::remove_var("AUTHGATE_TEAMS_HEADER");
+        std::env::remove_var("AUTHGATE_TENANT_HEADER");
+    }
+
+    #[tokio::test]
+    async fn test_custom_header_names() {
+        let _env = UNAUTH_ENV.lock().await;
+        let session_url = spawn_session_server(create_test_session()).await;
+
+        // Header names are read once, when the auth service is built
+        std::env::set_var("AUTHGATE_HEADER_USER_ID", "Remote-User");
+        std::env::set_var("AUTHGATE_HEADER_ROLES", "Remote-Groups");
+        std::env::set_var("AUTHGATE_HEADER_TENANT_ID", "Remote-Tenant");
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()));
+        std::env::remove_var("AUTHGATE_HEADER_USER_ID");
+        std::env::remove_var("AUTHGATE_HEADER_ROLES");
+        std::env::remove_var("AUTHGATE_HEADER_TENANT_ID");
+
+        let app = build_app_with_auth(&session_url, vec![admin_route()], auth_service).await;
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("valid-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers["Remote-User"], "user-1");
+        assert_eq!(headers["Remote-Groups"], "admin,user");
+        assert_eq!(headers["Remote-Tenant"], "tenant-1");
+        assert!(headers.get("X-Auth-User-Id").is_none());
+        assert!(headers.get("X-Auth-User-Roles").is_none());
+        assert!(headers.get("X-Auth-Tenant-Id").is_none());
+        // Names that aren't overridden keep their defaults
+        assert_eq!(headers["X-Auth-User-Email"], "user@example.com");
+        assert_eq!(headers["X-Auth-User-Teams"], "team-1");
+    }
+
+    #[tokio::test]
+    async fn test_scopes_header_encoding_and_limit() {
+        let _env = UNAUTH_ENV.lock().await;
+        let scope = |resource_type: &str, action: &str, resource_id: &str| Scope {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+            action: action.to_string(),
+        };
+        let mut session = create_test_session();
+        session.user.teams[0].scopes = vec![
+            scope("project", "read", "p-1"),
+            // Separators inside a part are escaped
+            scope("doc", "read", "a:b,c"),
+            scope("report", "export", "100%"),
+        ];
+        let session_url = spawn_session_server(session).await;
+        let request =
+            || forward_auth_request("app.example.com", "/admin/dashboard", Some("valid-token"));
+
+        std::env::set_var("AUTHGATE_SCOPES_HEADER", "true");
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(
+            response.headers()["X-Auth-Scopes"],
+            "project:read:p-1,doc:read:a%3Ab%2Cc,report:export:100%25"
+        );
+        assert!(response.headers().get("X-Auth-Scopes-Truncated").is_none());
+
+        // Scopes past the size limit are left out whole and flagged
+        std::env::set_var("AUTHGATE_SCOPES_HEADER_MAX_BYTES", "40");
+        let app = build_app(&session_url, vec![admin_route()]).await;
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(
+            response.headers()["X-Auth-Scopes"],
+            "project:read:p-1,doc:read:a%3Ab%2Cc"
+        );
+        assert_eq!(response.headers()["X-Auth-Scopes-Truncated"], "true");
+
+        std::env::remove_var("AUTHGATE_SCOPES_HEADER");
+        std::env::remove_var("AUTHGATE_SCOPES_HEADER_MAX_BYTES");
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_status() {
+        let _env = UNAUTH_ENV.lock().await;
+        let app = build_app("http://127.0.0.1:1/session", vec![admin_route()]).await;
+
+        // Redirect to the login page by default
+        std::env::remove_var("AUTHGATE_UNAUTH_STATUS");
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers().get(header::LOCATION).unwrap();
+        assert!(location
+            .to_str()
+            .unwrap()
+            .starts_with("https://auth.example.com/login"));
+
+        // 511 with the login URL in the body
+        std::env::set_var("AUTHGATE_UNAUTH_STATUS", "511");
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::NETWORK_AUTHENTICATION_REQUIRED
+        );
+        assert!(response.headers().get(header::LOCATION).is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .starts_with("https://auth.example.com/login"));
+
+        // Unsupported statuses are rejected
+        std::env::set_var("AUTHGATE_UNAUTH_STATUS", "200");
+        assert!(unauth_status().is_err());
+        std::env::remove_var("AUTHGATE_UNAUTH_STATUS");
+    }
+
+    /// Unauthenticated forward auth request for the admin dashboard with extra headers
+    fn unauthenticated_request(extra_headers: &[(&'static str, &str)]) -> Request<Body> {
+        let mut request = forward_auth_request("app.example.com", "/admin/dashboard", None);
+        for (name, value) in extra_headers {
+            request
+                .headers_mut()
+                .insert(*name, header::HeaderValue::from_str(value).unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_api_clients_get_json() {
+        let _env = UNAUTH_ENV.lock().await;
+        let app = build_app("http://127.0.0.1:1/session", vec![admin_route()]).await;
+        let api_requests = [
+            vec![("Accept", "application/json")],
+            vec![("Accept", "text/html;q=0.5, application/problem+json")],
+            vec![("Accept", "*/*"), ("X-Requested-With", "XMLHttpRequest")],
+        ];
+
+        // Redirected like browsers unless enabled
+        std::env::remove_var("AUTHGATE_UNAUTH_JSON");
+        let response = app
+            .clone()
+            .oneshot(unauthenticated_request(&api_requests[0]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        std::env::set_var("AUTHGATE_UNAUTH_JSON", "true");
+        for headers in &api_requests {
+            let response = app
+                .clone()
+                .oneshot(unauthenticated_request(headers))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{:?}", headers);
+            let location = response.headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert!(location.starts_with("https://auth.example.com/login"));
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"], "unauthenticated");
+            assert_eq!(body["login_url"], location);
+        }
+
+        // Browsers are still redirected to the login page
+        for headers in [
+            vec![(
+                "Accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            )],
+            vec![("Accept", "text/html, application/json;q=0.9")],
+            vec![("Accept", "*/*")],
+            vec![],
+        ] {
+            let response = app
+                .clone()
+                .oneshot(unauthenticated_request(&headers))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SEE_OTHER, "{:?}", headers);
+            assert!(response.headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .starts_with("https://auth.example.com/login"));
+        }
+        std::env::remove_var("AUTHGATE_UNAUTH_JSON");
+    }
+
+    /// The original URL encoded in the `next` parameter of a login redirect
+    fn login_next(response: &axum::response::Response) -> String {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let next = location.split("next=").nth(1).unwrap();
+        String::from_utf8(URL_SAFE_NO_PAD.decode(next).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_long_original_url_handled_per_policy() {
+        let _env = UNAUTH_ENV.lock().await;
+        let long_uri = format!("/admin/{}?q={}", "a".repeat(200), "b".repeat(200));
+
+        // Truncated: the query is dropped and the path cut to the limit
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_original_url_limit(64, LongUrlPolicy::Truncate);
+        let app = build_app_with_auth(
+            "http://127.0.0.1:1/session",
+            vec![admin_route()],
+            auth_service,
+        )
+        .await;
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request("app.example.com", &long_uri, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let next = login_next(&response);
+        assert_eq!(next.len(), 64);
+        assert!(next.starts_with("https://app.example.com/admin/aaa"));
+        assert!(!next.contains('?'));
+
+        // URLs within the limit are left alone
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/x?y=1",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(login_next(&response), "https://app.example.com/admin/x?y=1");
+
+        // Omitted: the login page is sent without a next parameter
+        let auth_service = AuthService::with_cache(Arc::new(InMemoryCache::new()))
+            .with_original_url_limit(64, LongUrlPolicy::Omit);
+        let app = build_app_with_auth(
+            "http://127.0.0.1:1/session",
+            vec![admin_route()],
+            auth_service,
+        )
+        .await;
+        let response = app
+            .oneshot(forward_auth_request("app.example.com", &long_uri, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://auth.example.com/login"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_query_preserved_in_login_redirect() {
+        let _env = UNAUTH_ENV.lock().await;
+        let app = build_app("http://127.0.0.1:1/session", vec![admin_route()]).await;
+
+        // Query forwarded in its own header
+        let mut request = forward_auth_request("app.example.com", "/admin/reports", None);
+        request
+            .headers_mut()
+            .insert("X-Forwarded-Query", "year=2024&format=csv".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            login_next(&response),
+            "https://app.example.com/admin/reports?year=2024&format=csv"
+        );
+
+        // A query already in X-Forwarded-Uri is not duplicated
+        let mut request = forward_auth_request("app.example.com", "/admin/reports?year=2024", None);
+        request
+            .headers_mut()
+            .insert("X-Forwarded-Query", "year=2024".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            login_next(&response),
+            "https://app.example.com/admin/reports?year=2024"
+        );
+
+        // Without a forwarded query the URL is left as is
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/reports",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            login_next(&response),
+            "https://app.example.com/admin/reports"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_form_forwarded_headers_use_leftmost_value() {
+        let _env = UNAUTH_ENV.lock().await;
+        let app = build_app("http://127.0.0.1:1/session", vec![admin_route()]).await;
+
+        let request = Request::builder()
+            .uri("/auth")
+            .header("X-Forwarded-Proto", "https, http")
+            .header("X-Forwarded-Host", "app.example.com, internal.lb.local")
+            .header("X-Forwarded-Port", "443, 8080")
+            .header("X-Forwarded-Uri", "/admin/reports")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            login_next(&response),
+            "https://app.example.com/admin/reports"
+        );
+
+        // The same applies to the query parameter form
+        let request = Request::builder()
+            .uri("/auth?X-Forwarded-Proto=https,http&X-Forwarded-Host=app.example.com,%20lb&X-Forwarded-Uri=/admin/reports")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(
+            login_next(&response),
+            "https://app.example.com/admin/reports"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_captures_forwarded_as_headers() {
+        let session_url = spawn_session_server(create_test_session()).await;
+        let mut profile_route = admin_route();
+        profile_route.path = "/orgs/{org}/users/{user_id}/profile".to_string();
+        let app = build_app(&session_url, vec![profile_route]).await;
+
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/orgs/acme/users/42/profile",
+                Some("valid-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("X-Auth-Path-org").unwrap(), "acme");
+        assert_eq!(response.headers().get("X-Auth-Path-user_id").unwrap(), "42");
+    }
+
+    /// Forward a request with the given `X-Forwarded-Method`
+    fn forward_auth_request_with_method(
+        method: &str,
+        uri: &str,
+        session_token: Option<&str>,
+    ) -> Request<Body> {
+        let mut request = forward_auth_request("app.example.com", uri, session_token);
+        request
+            .headers_mut()
+            .insert("X-Forwarded-Method", method.parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_head_authorized_like_get() {
+        let _env = UNAUTH_ENV.lock().await;
+        let admin_url = spawn_session_server(create_test_session()).await;
+        let mut user_session = create_test_session();
+        user_session.user.roles = vec!["user".to_string()];
+        let user_url = spawn_session_server(user_session).await;
+
+        for (session_url, token) in [
+            (&admin_url, Some("valid-token")),
+            (&user_url, Some("valid-token")),
+            (&admin_url, None),
+        ] {
+            let app = build_app(session_url, vec![admin_route()]).await;
+            let get = app
+                .clone()
+                .oneshot(forward_auth_request_with_method(
+                    "GET",
+                    "/admin/dashboard",
+                    token,
+                ))
+                .await
+                .unwrap();
+            let head = app
+                .oneshot(forward_auth_request_with_method(
+                    "HEAD",
+                    "/admin/dashboard",
+                    token,
+                ))
+                .await
+                .unwrap();
+
+            assert_eq!(head.status(), get.status());
+            assert_eq!(auth_headers(&head), auth_headers(&get));
+            assert_eq!(
+                head.headers().get(header::LOCATION),
+                get.headers().get(header::LOCATION)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_matched_by_forwarded_method() {
+        let session_url = spawn_session_server(create_test_session()).await;
+        let route = |methods: &[&str], role: &str| Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/api/items".to_string(),
+            require: serde_json::json!({ "roles": [role] }),
+            methods: Some(methods.iter().map(|m| m.to_string()).collect()),
+            ..Default::default()
+        };
+        let app = build_app(
+            &session_url,
+            vec![route(&["GET"], "user"), route(&["POST"], "editor")],
+        )
+        .await;
+
+        let status = |method: &'static str| {
+            let app = app.clone();
+            async move {
+                app.oneshot(forward_auth_request_with_method(
+                    method,
+                    "/api/items",
+                    Some("valid-token"),
+                ))
+                .await
+                .unwrap()
+                .status()
+            }
+        };
+
+        assert_eq!(status("GET").await, StatusCode::OK);
+        assert_eq!(status("POST").await, StatusCode::FORBIDDEN);
+        // HEAD is matched against the GET-only route
+        assert_eq!(status("HEAD").await, StatusCode::OK);
+        // No route lists DELETE, so the request isn't protected
+        assert_eq!(status("DELETE").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_public_route_skips_authentication() {
+        let _env = UNAUTH_ENV.lock().await;
+        let protected = Route {
+            path: "/*".to_string(),
+            ..admin_route()
+        };
+        let public = Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/public/*".to_string(),
+            public: true,
+            ..Default::default()
+        };
+        let app = build_app("http://127.0.0.1:1/session", vec![protected, public]).await;
+
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/public/logo.png",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("X-Auth-User-Id").is_none());
+
+        // The rest of the host still requires a session
+        let response = app
+            .oneshot(forward_auth_request("app.example.com", "/dashboard", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_dot_segments_cannot_bypass_protected_routes() {
+        let _env = UNAUTH_ENV.lock().await;
+        let public = Route {
+            id: None,
+            host: "app.example.com".to_string(),
+            path: "/public/*".to_string(),
+            public: true,
+            ..Default::default()
+        };
+        let app = build_app("http://127.0.0.1:1/session", vec![admin_route(), public]).await;
+
+        // Matched as /admin/..., not as the public route the path starts with
+        for uri in [
+            "/public/../admin/secret",
+            "/public/%2e%2e/admin/secret",
+            "/public/./../admin//users",
+        ] {
+            let response = app
+                .clone()
+                .oneshot(forward_auth_request("app.example.com", uri, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SEE_OTHER, "{}", uri);
+        }
+
+        // And the other way round
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/../public/logo.png",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Auth service reading session tokens from the given sources
+    fn auth_with_token_sources(sources: Vec<TokenSource>) -> AuthService {
+        AuthService::with_cache(Arc::new(InMemoryCache::new())).with_token_sources(sources)
+    }
+
+    #[tokio::test]
+    async fn test_session_token_from_header_or_query() {
+        let _env = UNAUTH_ENV.lock().await;
+        let session = create_test_session();
+        let session_url = spawn_session_server_with(move |token| {
+            (token == "valid-token").then(|| session.clone())
+        })
+        .await;
+        let sources = vec![
+            TokenSource::Header("X-Session-Token".to_string()),
+            TokenSource::Query("session_token".to_string()),
+        ];
+        let app = build_app_with_auth(
+            &session_url,
+            vec![admin_route()],
+            auth_with_token_sources(sources),
+        )
+        .await;
+
+        // Header-sourced token
+        let mut request = forward_auth_request("app.example.com", "/admin/dashboard", None);
+        request
+            .headers_mut()
+            .insert("X-Session-Token", "valid-token".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("X-Auth-User-Id").unwrap(), "user-1");
+
+        // Query-sourced token in the forwarded URI
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard?tab=1&session_token=valid-token",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Query-sourced token in a separately forwarded query string
+        let mut request = forward_auth_request("app.example.com", "/admin/dashboard", None);
+        request.headers_mut().insert(
+            "X-Forwarded-Query",
+            "session_token=valid-token".parse().unwrap(),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Cookies aren't read unless listed as a source
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("valid-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_session_token_sources_tried_in_order() {
+        let session = create_test_session();
+        let session_url = spawn_session_server_with(move |token| {
+            (token == "valid-token").then(|| session.clone())
+        })
+        .await;
+        let request = || {
+            let mut request =
+                forward_auth_request("app.example.com", "/admin/dashboard", Some("stale-token"));
+            request
+                .headers_mut()
+                .insert("X-Session-Token", "valid-token".parse().unwrap());
+            request
+        };
+
+        // The cookie and a bearer token by default, so the header is ignored
+        let app = build_app_with_auth(
+            &session_url,
+            vec![admin_route()],
+            AuthService::with_cache(Arc::new(InMemoryCache::new())),
+        )
+        .await;
+        let response = app.oneshot(request()).await.unwrap();
+        assert_ne!(response.status(), StatusCode::OK);
+
+        // The first source carrying a token wins
+        let app = build_app_with_auth(
+            &session_url,
+            vec![admin_route()],
+            auth_with_token_sources(vec![
+                TokenSource::Header("X-Session-Token".to_string()),
+                TokenSource::Cookie,
+            ]),
+        )
+        .await;
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Forward auth request carrying an `Authorization: Bearer` header
+    fn bearer_request(token: &str, cookie_token: Option<&str>) -> Request<Body> {
+        let mut request = forward_auth_request("app.example.com", "/admin/dashboard", cookie_token);
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        request
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_without_cookie() {
+        let _env = UNAUTH_ENV.lock().await;
+        let session = create_test_session();
+        let session_url = spawn_session_server_with(move |token| {
+            (token == "valid-token").then(|| session.clone())
+        })
+        .await;
+        // The default sources: the cookie, then a bearer token
+        let app = build_app(&session_url, vec![admin_route()]).await;
+
+        let response = app
+            .clone()
+            .oneshot(bearer_request("valid-token", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Auth-User-Id"], "user-1");
+
+        // Other schemes aren't session tokens
+        let mut request = forward_auth_request("app.example.com", "/admin/dashboard", None);
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            "Basic dmFsaWQtdG9rZW4=".parse().unwrap(),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        // Neither a cookie nor a bearer token still redirects to the login page
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(response.headers().contains_key(header::LOCATION));
+
+        // The cookie takes precedence when both are present
+        let response = app
+            .oneshot(bearer_request("valid-token", Some("stale-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_precedence() {
+        let session = create_test_session();
+        let session_url = spawn_session_server_with(move |token| {
+            (token == "valid-token").then(|| session.clone())
+        })
+        .await;
+        let app = build_app_with_auth(
+            &session_url,
+            vec![admin_route()],
+            auth_with_token_sources(vec![TokenSource::Bearer, TokenSource::Cookie]),
+        )
+        .await;
+
+        // The bearer token is tried first
+        let response = app
+            .clone()
+            .oneshot(bearer_request("valid-token", Some("stale-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // And the cookie used without one
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("valid-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_require_fails_closed() {
+        let _env = UNAUTH_ENV.lock().await;
+        let session_url = spawn_session_server(create_test_session()).await;
+        let mut corrupt = admin_route();
+        corrupt.id = Some(7);
+        corrupt.require = serde_json::json!({ "roles": "admin", "scopes": [{ "action": 1 }] });
+        let app = build_app(&session_url, vec![corrupt]).await;
+        let request =
+            || forward_auth_request("app.example.com", "/admin/dashboard", Some("valid-token"));
+
+        // Denied by default, without exposing the error
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers().get("X-Auth-User-Id").is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"Forbidden");
+
+        // Or sent to the login page
+        std::env::set_var("AUTHGATE_INVALID_REQUIRE", "login");
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(response.headers().contains_key(header::LOCATION));
+
+        // The former server error is still available
+        std::env::set_var("AUTHGATE_INVALID_REQUIRE", "error");
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // Unknown policies fail closed too
+        std::env::set_var("AUTHGATE_INVALID_REQUIRE", "allow");
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        std::env::remove_var("AUTHGATE_INVALID_REQUIRE");
+    }
+
+    #[tokio::test]
+    async fn test_audience_header_from_matched_route() {
+        let session_url = spawn_session_server(create_test_session()).await;
+        let mut billing = admin_route();
+        billing.path = "/billing/*".to_string();
+        billing.audience = Some("billing-app".to_string());
+        let mut admin = admin_route();
+        admin.audience = Some("admin-console".to_string());
+        let mut unlabeled = admin_route();
+        unlabeled.path = "/docs/*".to_string();
+        let app = build_app(&session_url, vec![billing, admin, unlabeled]).await;
+
+        for (uri, audience) in [
+            ("/billing/invoices", Some("billing-app")),
+            ("/admin/users", Some("admin-console")),
+            ("/docs/intro", None),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(forward_auth_request(
+                    "app.example.com",
+                    uri,
+                    Some("valid-token"),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response
+                    .headers()
+                    .get("X-Auth-Audience")
+                    .map(|v| v.to_str().unwrap()),
+                audience,
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_redirect() {
+        let mut session = create_test_session();
+        session.user.roles = vec!["user".to_string()];
+        let session_url = spawn_session_server(session).await;
+
+        let mut redirecting = admin_route();
+        redirecting.path = "/billing/*".to_string();
+        redirecting.forbidden_redirect = Some("https://app.example.com/request-access".to_string());
+        let app = build_app(&session_url, vec![admin_route(), redirecting]).await;
+
+        // Forbidden users are redirected to the request access page
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/billing/invoices",
+                Some("forbidden-token"),
+            ))
+            .await
+            .unwrap();
+        assert!(response.status().is_redirection());
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://app.example.com/request-access"
+        );
+
+        // Routes without one keep the 403
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/dashboard",
+                Some("forbidden-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers().get(header::LOCATION).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_route_rate_limit_per_user() {
+        let session_url = spawn_session_server_with(|token| match token {
+            "alice-token" => Some(create_user_session("alice")),
+            "bob-token" => Some(create_user_session("bob")),
+            _ => None,
+        })
+        .await;
+
+        let route = Route {
+            rate_limit: Some(RateLimit {
+                requests: 2,
+                window_secs: 60,
+                key: RateLimitKey::User,
+            }),
+            ..admin_route()
+        };
+        let app = build_app(&session_url, vec![route]).await;
+
+        // Alice may make two requests within the window
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(forward_auth_request(
+                    "app.example.com",
+                    "/admin/reset-password",
+                    Some("alice-token"),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // The third is rejected
+        let response = app
+            .clone()
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/reset-password",
+                Some("alice-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Bob has his own budget
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/reset-password",
+                Some("bob-token"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_route_rate_limit_per_ip_ignores_spoofed_forwarded_for() {
+        let session_url = spawn_session_server(create_test_session()).await;
+        let route = Route {
+            rate_limit: Some(RateLimit {
+                requests: 2,
+                window_secs: 60,
+                key: RateLimitKey::Ip,
+            }),
+            ..admin_route()
+        };
+        let app = build_app(&session_url, vec![route]).await;
+
+        // The client varies what it sends, but the proxy appends its real address
+        let request = |spoofed: &str| {
+            let mut request =
+                forward_auth_request("app.example.com", "/admin/users", Some("valid-token"));
+            request.headers_mut().insert(
+                "X-Forwarded-For",
+                format!("{}, 192.0.2.44", spoofed).parse().unwrap(),
+            );
+            request
+        };
+
+        for spoofed in ["198.51.100.1", "198.51.100.2"] {
+            let response = app.clone().oneshot(request(spoofed)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = app.oneshot(request("198.51.100.3")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_route_session_url_override() {
+        // Two session services that both accept the same token for different users
+        let global_url =
+            spawn_session_server_with(|_| Some(create_user_session("global-user"))).await;
+        let partner_url =
+            spawn_session_server_with(|_| Some(create_user_session("partner-user"))).await;
+
+        let partner_route = Route {
+            host: "partner.example.com".to_string(),
+            session_url: Some(partner_url),
+            ..admin_route()
+        };
+        let app = build_app(&global_url, vec![admin_route(), partner_route]).await;
+
+        // Validate against both services twice, so the second round is served
+        // from the cache and would expose a cross-service collision
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(forward_auth_request(
+                    "app.example.com",
+                    "/admin/users",
+                    Some("shared-token"),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("X-Auth-User-Id").unwrap(),
+                "global-user"
+            );
+
+            let response = app
+                .clone()
+                .oneshot(forward_auth_request(
+                    "partner.example.com",
+                    "/admin/users",
+                    Some("shared-token"),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("X-Auth-User-Id").unwrap(),
+                "partner-user"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_headers_are_redacted_in_logs() {
+        let (logs, _guard) = capture_logs(tracing::Level::DEBUG);
+
+        let session_url = spawn_session_server(create_test_session()).await;
+        let app = build_app(&session_url, vec![admin_route()]).await;
+
+        let mut request = forward_auth_request(
+            "app.example.com",
+            "/admin/users",
+            Some("very-secret-cookie-value"),
+        );
+        request
+            .headers_mut()
+            .insert("X-Auth-User-Id", "spoofed-user-id".parse().unwrap());
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let output = logs.contents();
+        assert!(output.contains("Forward auth request headers"));
+        assert!(output.contains("x-forwarded-host"));
+        assert!(output.contains("[redacted]"));
+        assert!(!output.contains("very-secret-cookie-value"));
+        assert!(!output.contains("spoofed-user-id"));
+    }
+
+    /// Start a mock webhook forwarding every event it receives to the returned channel
+    async fn spawn_webhook_server() -> (String, tokio::sync::mpsc::Receiver<serde_json::Value>) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        let app = Router::new().route(
+            "/denials",
+            post(move |Json(event): Json<serde_json::Value>| {
+                let sender = sender.clone();
+                async move {
+                    sender.send(event).await.unwrap();
+                    StatusCode::NO_CONTENT
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/denials", addr), receiver)
+    }
+
+    #[tokio::test]
+    async fn test_denials_posted_to_webhook() {
+        let _env = UNAUTH_ENV.lock().await;
+        let (webhook_url, mut events) = spawn_webhook_server().await;
+        let session_url = spawn_session_server(create_test_session()).await;
+        let route = Route {
+            id: Some(7),
+            require: serde_json::json!({ "roles": ["superuser"] }),
+            ..admin_route()
+        };
+        let auth_service =
+            AuthService::new().with_denial_webhook(DenialWebhook::new(&webhook_url, 8));
+        let app = build_app_with_auth(&session_url, vec![route], auth_service).await;
+
+        let mut request = forward_auth_request(
+            "app.example.com",
+            "/admin/users?session_token=secret-session-token",
+            Some("secret-session-token"),
+        );
+        request.headers_mut().insert(
+            "X-Forwarded-For",
+            "198.51.100.1, 203.0.113.9".parse().unwrap(),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("No denial event received")
+            .unwrap();
+        assert_eq!(event["kind"], "forbidden");
+        assert_eq!(event["host"], "app.example.com");
+        assert_eq!(event["path"], "/admin/users");
+        assert_eq!(event["method"], "GET");
+        assert_eq!(event["client_ip"], "203.0.113.9");
+        assert_eq!(event["user_id"], "user-1");
+        assert_eq!(event["route_id"], 7);
+        assert!(event["reason"].as_str().unwrap().contains("superuser"));
+        assert!(!event.to_string().contains("secret-session-token"));
+
+        // Anonymous visits aren't reported
+        let response = app
+            .oneshot(forward_auth_request(
+                "app.example.com",
+                "/admin/users",
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), events.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logout_purges_cached_session() {
+        let cache = Arc::new(InMemoryCache::new());
+        let ttl = std::time::Duration::from_secs(300);
+        // Cached for routes using the global session endpoint, and for a route
+        // with its own cache TTL
+        cache
+            .set("logout-token", create_test_session(), ttl)
+            .await
+            .unwrap();
+        cache
+            .set("ttl=60|logout-token", create_test_session(), ttl)
+            .await
+            .unwrap();
+        let reports_route = Route {
+            path: "/reports/*".to_string(),
+            session_cache_ttl: Some(60),
+            ..admin_route()
+        };
+        let app = build_app_with_auth(
+            "http://127.0.0.1:1/session",
+            vec![admin_route(), reports_route],
+            AuthService::with_cache(cache.clone()),
+        )
+        .await;
+        let logout = |token: Option<&str>| {
+            let mut builder = Request::builder().method("POST").uri("/auth/logout");
+            if let Some(token) = token {
+                builder = builder.header(header::COOKIE, format!("session={}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(logout(Some("logout-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(cache.get("logout-token").await.is_none());
+        assert!(cache.get("ttl=60|logout-token").await.is_none());
+
+        // Logging out a session that isn't cached still succeeds
+        let response = app
+            .clone()
+            .oneshot(logout(Some("logout-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(logout(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }